@@ -0,0 +1,286 @@
+use std::{path::Path, str::FromStr};
+
+use reqwest::Client;
+use serde::Deserialize;
+use sqlx::{types::BigDecimal, PgPool};
+use tracing::{info, warn};
+
+use crate::{db::instruments, models::instruments::NewInstrument};
+
+const BINANCE_EXCHANGE_INFO_URL: &str = "https://api.binance.com/api/v3/exchangeInfo";
+const KUCOIN_SYMBOLS_URL: &str = "https://api.kucoin.com/api/v2/symbols";
+const BYBIT_INSTRUMENTS_URL: &str =
+    "https://api.bybit.com/v5/market/instruments-info?category=spot";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExchangeInfoError {
+    #[error("Exchange no soportado: {0}")]
+    UnsupportedExchange(String),
+    #[error("Error HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Error leyendo fixture: {0}")]
+    Fixture(#[from] std::io::Error),
+    #[error("Respuesta inválida: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Error de base de datos: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("El exchange {0} no devolvió instrumentos")]
+    EmptyCatalog(String),
+}
+
+/// Origen del catálogo: la API pública del exchange o un directorio con
+/// fixtures `<exchange>.json` que replican la respuesta de exchange-info.
+#[derive(Debug, Clone)]
+pub enum InstrumentSource {
+    Remote,
+    Fixtures(String),
+}
+
+impl InstrumentSource {
+    pub fn from_env() -> Self {
+        match std::env::var("INSTRUMENTS_FIXTURES_DIR") {
+            Ok(dir) if !dir.is_empty() => InstrumentSource::Fixtures(dir),
+            _ => InstrumentSource::Remote,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceExchangeInfo {
+    symbols: Vec<BinanceSymbol>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BinanceSymbol {
+    symbol: String,
+    status: String,
+    base_asset: String,
+    quote_asset: String,
+    filters: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KucoinResponse {
+    data: Vec<KucoinSymbol>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KucoinSymbol {
+    symbol: String,
+    base_currency: String,
+    quote_currency: String,
+    price_increment: String,
+    base_increment: String,
+    min_funds: Option<String>,
+    enable_trading: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitResponse {
+    result: BybitResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitResult {
+    list: Vec<BybitSymbol>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitSymbol {
+    symbol: String,
+    base_coin: String,
+    quote_coin: String,
+    status: String,
+    lot_size_filter: BybitLotSizeFilter,
+    price_filter: BybitPriceFilter,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitLotSizeFilter {
+    base_precision: String,
+    min_order_amt: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitPriceFilter {
+    tick_size: String,
+}
+
+fn decimal(value: &str) -> Option<BigDecimal> {
+    BigDecimal::from_str(value).ok()
+}
+
+fn binance_filter(filters: &[serde_json::Value], filter_type: &str, field: &str) -> Option<BigDecimal> {
+    filters
+        .iter()
+        .find(|f| f["filterType"] == filter_type)
+        .and_then(|f| f[field].as_str())
+        .and_then(decimal)
+}
+
+/// Descarta los símbolos cuyo tick o lot size falta o no se puede leer;
+/// guardarlos con tamaño 0 rompería el redondeo de precios y cantidades.
+fn with_sizes(
+    exchange: &str,
+    symbol: &str,
+    tick_size: Option<BigDecimal>,
+    lot_size: Option<BigDecimal>,
+) -> Option<(BigDecimal, BigDecimal)> {
+    match (tick_size, lot_size) {
+        (Some(tick_size), Some(lot_size)) => Some((tick_size, lot_size)),
+        _ => {
+            warn!("Instrumento {} de {} sin tick o lot size válidos, se omite", symbol, exchange);
+            None
+        }
+    }
+}
+
+/// Convierte la respuesta cruda de exchange-info de un exchange en
+/// instrumentos normalizados.
+pub fn parse_exchange_info(exchange: &str, body: &str) -> Result<Vec<NewInstrument>, ExchangeInfoError> {
+    let exchange = exchange.to_lowercase();
+
+    let instruments = match exchange.as_str() {
+        "binance" => {
+            let info: BinanceExchangeInfo = serde_json::from_str(body)?;
+            info.symbols
+                .into_iter()
+                .filter_map(|s| {
+                    let (tick_size, lot_size) = with_sizes(
+                        &exchange,
+                        &s.symbol,
+                        binance_filter(&s.filters, "PRICE_FILTER", "tickSize"),
+                        binance_filter(&s.filters, "LOT_SIZE", "stepSize"),
+                    )?;
+                    Some(NewInstrument {
+                        exchange: exchange.clone(),
+                        tick_size,
+                        lot_size,
+                        min_notional: binance_filter(&s.filters, "NOTIONAL", "minNotional")
+                            .or_else(|| binance_filter(&s.filters, "MIN_NOTIONAL", "minNotional")),
+                        status: s.status.to_lowercase(),
+                        exchange_symbol: s.symbol,
+                        base_asset: s.base_asset.to_uppercase(),
+                        quote_asset: s.quote_asset.to_uppercase(),
+                    })
+                })
+                .collect()
+        }
+        "kucoin" => {
+            let response: KucoinResponse = serde_json::from_str(body)?;
+            response
+                .data
+                .into_iter()
+                .filter_map(|s| {
+                    let (tick_size, lot_size) = with_sizes(
+                        &exchange,
+                        &s.symbol,
+                        decimal(&s.price_increment),
+                        decimal(&s.base_increment),
+                    )?;
+                    Some(NewInstrument {
+                        exchange: exchange.clone(),
+                        tick_size,
+                        lot_size,
+                        min_notional: s.min_funds.as_deref().and_then(decimal),
+                        status: if s.enable_trading { "trading" } else { "break" }.to_string(),
+                        exchange_symbol: s.symbol,
+                        base_asset: s.base_currency.to_uppercase(),
+                        quote_asset: s.quote_currency.to_uppercase(),
+                    })
+                })
+                .collect()
+        }
+        "bybit" => {
+            let response: BybitResponse = serde_json::from_str(body)?;
+            response
+                .result
+                .list
+                .into_iter()
+                .filter_map(|s| {
+                    let (tick_size, lot_size) = with_sizes(
+                        &exchange,
+                        &s.symbol,
+                        decimal(&s.price_filter.tick_size),
+                        decimal(&s.lot_size_filter.base_precision),
+                    )?;
+                    Some(NewInstrument {
+                        exchange: exchange.clone(),
+                        tick_size,
+                        lot_size,
+                        min_notional: s.lot_size_filter.min_order_amt.as_deref().and_then(decimal),
+                        status: s.status.to_lowercase(),
+                        exchange_symbol: s.symbol,
+                        base_asset: s.base_coin.to_uppercase(),
+                        quote_asset: s.quote_coin.to_uppercase(),
+                    })
+                })
+                .collect()
+        }
+        other => return Err(ExchangeInfoError::UnsupportedExchange(other.to_string())),
+    };
+
+    Ok(instruments)
+}
+
+pub async fn fetch_instruments(
+    exchange: &str,
+    source: &InstrumentSource,
+) -> Result<Vec<NewInstrument>, ExchangeInfoError> {
+    let body = match source {
+        InstrumentSource::Fixtures(dir) => {
+            let path = Path::new(dir).join(format!("{}.json", exchange.to_lowercase()));
+            info!("Cargando instrumentos desde fixture: {}", path.display());
+            tokio::fs::read_to_string(path).await?
+        }
+        InstrumentSource::Remote => {
+            let url = match exchange.to_lowercase().as_str() {
+                "binance" => BINANCE_EXCHANGE_INFO_URL,
+                "kucoin" => KUCOIN_SYMBOLS_URL,
+                "bybit" => BYBIT_INSTRUMENTS_URL,
+                other => return Err(ExchangeInfoError::UnsupportedExchange(other.to_string())),
+            };
+            info!("Descargando instrumentos desde {}", url);
+            Client::new()
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?
+        }
+    };
+
+    parse_exchange_info(exchange, &body)
+}
+
+/// Sincroniza el catálogo de un exchange: inserta/actualiza los instrumentos
+/// recibidos y marca como `delisted` los que ya no existen. Una respuesta
+/// vacía se trata como error para no deslistar el exchange entero.
+pub async fn sync_instruments(
+    pool: &PgPool,
+    exchange: &str,
+    source: &InstrumentSource,
+) -> Result<u64, ExchangeInfoError> {
+    let fetched = fetch_instruments(exchange, source).await?;
+    if fetched.is_empty() {
+        return Err(ExchangeInfoError::EmptyCatalog(exchange.to_string()));
+    }
+    let symbols: Vec<String> = fetched.iter().map(|i| i.symbol()).collect();
+
+    let synced = instruments::upsert_instruments(pool, &fetched).await?;
+    let delisted = instruments::mark_missing_delisted(pool, &exchange.to_lowercase(), &symbols).await?;
+
+    info!(
+        "Catálogo de {} sincronizado: {} instrumentos, {} marcados como delisted",
+        exchange, synced, delisted
+    );
+
+    Ok(synced)
+}
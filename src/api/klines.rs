@@ -0,0 +1,200 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::Client;
+use sqlx::{types::BigDecimal, PgPool};
+use tracing::{info, warn};
+
+use crate::{
+    db::candles,
+    models::{
+        candles::{find_gaps, Candle, Timeframe},
+        instruments::Instrument,
+    },
+};
+
+const BINANCE_KLINES_URL: &str = "https://api.binance.com/api/v3/klines";
+const KUCOIN_KLINES_URL: &str = "https://api.kucoin.com/api/v1/market/candles";
+const BYBIT_KLINES_URL: &str = "https://api.bybit.com/v5/market/kline";
+
+/// Máximo de velas por petición que aceptan los tres exchanges.
+const MAX_KLINES_PER_REQUEST: i64 = 1000;
+/// Rango máximo de un backfill: 31 días de velas de 1m son ~45 pedidos al
+/// exchange.
+pub const MAX_BACKFILL_DAYS: i64 = 31;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KlinesError {
+    #[error("Exchange no soportado: {0}")]
+    UnsupportedExchange(String),
+    #[error("Error HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Respuesta inválida: {0}")]
+    Parse(String),
+    #[error("Error de base de datos: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+fn field(row: &serde_json::Value, index: usize) -> Result<BigDecimal, KlinesError> {
+    let value = &row[index];
+    let text = match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    BigDecimal::from_str(&text).map_err(|_| KlinesError::Parse(format!("valor inválido: {}", value)))
+}
+
+fn timestamp(row: &serde_json::Value, seconds: bool) -> Result<DateTime<Utc>, KlinesError> {
+    let raw = match &row[0] {
+        serde_json::Value::String(s) => s.parse::<i64>().ok(),
+        other => other.as_i64(),
+    }
+    .ok_or_else(|| KlinesError::Parse("open_time inválido".to_string()))?;
+
+    let time = if seconds {
+        Utc.timestamp_opt(raw, 0).single()
+    } else {
+        Utc.timestamp_millis_opt(raw).single()
+    };
+    time.ok_or_else(|| KlinesError::Parse("open_time fuera de rango".to_string()))
+}
+
+/// Descarga velas de 1m de `[start, end)` para un instrumento.
+pub async fn fetch_minute_klines(
+    client: &Client,
+    instrument: &Instrument,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Candle>, KlinesError> {
+    let rows: Vec<serde_json::Value>;
+    // Índices de open/high/low/close/volume en cada fila.
+    let columns: [usize; 5];
+    let seconds;
+
+    match instrument.exchange.as_str() {
+        "binance" => {
+            rows = client
+                .get(BINANCE_KLINES_URL)
+                .query(&[
+                    ("symbol", instrument.exchange_symbol.clone()),
+                    ("interval", "1m".to_string()),
+                    ("startTime", start.timestamp_millis().to_string()),
+                    ("endTime", (end.timestamp_millis() - 1).to_string()),
+                    ("limit", MAX_KLINES_PER_REQUEST.to_string()),
+                ])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            columns = [1, 2, 3, 4, 5];
+            seconds = false;
+        }
+        "kucoin" => {
+            let response: serde_json::Value = client
+                .get(KUCOIN_KLINES_URL)
+                .query(&[
+                    ("symbol", instrument.exchange_symbol.clone()),
+                    ("type", "1min".to_string()),
+                    ("startAt", start.timestamp().to_string()),
+                    ("endAt", end.timestamp().to_string()),
+                ])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            rows = response["data"].as_array().cloned().unwrap_or_default();
+            // KuCoin devuelve [time, open, close, high, low, volume, turnover]
+            columns = [1, 3, 4, 2, 5];
+            seconds = true;
+        }
+        "bybit" => {
+            let response: serde_json::Value = client
+                .get(BYBIT_KLINES_URL)
+                .query(&[
+                    ("category", "spot".to_string()),
+                    ("symbol", instrument.exchange_symbol.clone()),
+                    ("interval", "1".to_string()),
+                    ("start", start.timestamp_millis().to_string()),
+                    ("end", (end.timestamp_millis() - 1).to_string()),
+                    ("limit", MAX_KLINES_PER_REQUEST.to_string()),
+                ])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            rows = response["result"]["list"].as_array().cloned().unwrap_or_default();
+            columns = [1, 2, 3, 4, 5];
+            seconds = false;
+        }
+        other => return Err(KlinesError::UnsupportedExchange(other.to_string())),
+    }
+
+    let mut result = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let open_time = timestamp(row, seconds)?;
+        if open_time < start || open_time >= end {
+            continue;
+        }
+        result.push(Candle {
+            instrument_id: instrument.id,
+            timeframe: Timeframe::M1.as_str().to_string(),
+            open_time,
+            open: field(row, columns[0])?,
+            high: field(row, columns[1])?,
+            low: field(row, columns[2])?,
+            close: field(row, columns[3])?,
+            volume: field(row, columns[4])?,
+        });
+    }
+
+    // KuCoin y Bybit devuelven las velas de la más nueva a la más vieja.
+    result.sort_by_key(|c| c.open_time);
+    Ok(result)
+}
+
+/// Detecta huecos en las velas de 1m de `[start, end)` y los rellena
+/// pidiéndolos al exchange. Devuelve la cantidad de velas insertadas.
+pub async fn backfill_minute_candles(
+    pool: &PgPool,
+    instrument: &Instrument,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<u64, KlinesError> {
+    let client = Client::new();
+    let expected = (end - start).num_minutes().max(0);
+    let existing = candles::list_candles(pool, instrument.id, Timeframe::M1, start, end, expected + 1).await?;
+    let gaps = find_gaps(&existing, Timeframe::M1, start, end);
+
+    info!(
+        "Backfill de {} en {}: {} huecos detectados",
+        instrument.symbol,
+        instrument.exchange,
+        gaps.len()
+    );
+
+    let mut inserted = 0;
+    for (gap_start, gap_end) in gaps {
+        let mut cursor = gap_start;
+        while cursor < gap_end {
+            let chunk_end = std::cmp::min(
+                gap_end,
+                cursor + Timeframe::M1.duration() * MAX_KLINES_PER_REQUEST as i32,
+            );
+            let fetched = fetch_minute_klines(&client, instrument, cursor, chunk_end).await?;
+            if fetched.is_empty() {
+                warn!(
+                    "El exchange no devolvió velas para {} entre {} y {}",
+                    instrument.symbol, cursor, chunk_end
+                );
+            } else {
+                inserted += candles::upsert_candles(pool, &fetched).await?;
+            }
+            cursor = chunk_end;
+        }
+    }
+
+    Ok(inserted)
+}
@@ -1 +1,4 @@
-pub mod coingecko; 
\ No newline at end of file
+pub mod coingecko;
+pub mod exchange_info;
+pub mod klines;
+pub mod tickers;
\ No newline at end of file
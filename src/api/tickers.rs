@@ -0,0 +1,66 @@
+use std::str::FromStr;
+
+use reqwest::Client;
+use sqlx::types::BigDecimal;
+
+use crate::models::instruments::Instrument;
+
+const BINANCE_TICKER_URL: &str = "https://api.binance.com/api/v3/ticker/price";
+const KUCOIN_TICKER_URL: &str = "https://api.kucoin.com/api/v1/market/orderbook/level1";
+const BYBIT_TICKER_URL: &str = "https://api.bybit.com/v5/market/tickers";
+
+#[derive(Debug, thiserror::Error)]
+pub enum TickerError {
+    #[error("Exchange no soportado: {0}")]
+    UnsupportedExchange(String),
+    #[error("Error HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Respuesta inválida: {0}")]
+    Parse(String),
+}
+
+/// Último precio negociado de un instrumento.
+pub async fn fetch_last_price(
+    client: &Client,
+    instrument: &Instrument,
+) -> Result<BigDecimal, TickerError> {
+    let price = match instrument.exchange.as_str() {
+        "binance" => {
+            let response: serde_json::Value = client
+                .get(BINANCE_TICKER_URL)
+                .query(&[("symbol", &instrument.exchange_symbol)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            response["price"].as_str().map(str::to_string)
+        }
+        "kucoin" => {
+            let response: serde_json::Value = client
+                .get(KUCOIN_TICKER_URL)
+                .query(&[("symbol", &instrument.exchange_symbol)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            response["data"]["price"].as_str().map(str::to_string)
+        }
+        "bybit" => {
+            let response: serde_json::Value = client
+                .get(BYBIT_TICKER_URL)
+                .query(&[("category", "spot"), ("symbol", instrument.exchange_symbol.as_str())])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            response["result"]["list"][0]["lastPrice"].as_str().map(str::to_string)
+        }
+        other => return Err(TickerError::UnsupportedExchange(other.to_string())),
+    };
+
+    let price = price.ok_or_else(|| TickerError::Parse(format!("sin precio para {}", instrument.symbol)))?;
+    BigDecimal::from_str(&price).map_err(|_| TickerError::Parse(format!("precio inválido: {}", price)))
+}
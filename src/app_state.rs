@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+use crate::notifications::{
+    bus::{bus_from_env, NotificationBus},
+    queue::{queue_from_env, NotificationQueue},
+    telegram::TelegramClient,
+    telegram_bot::TelegramBot,
+    ticker::TickerFeed,
+    whatsapp::WhatsAppClient,
+    webhook::WebhookChannel,
+    websocket::WebSocketServer,
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    pub ws_server: Arc<WebSocketServer>,
+    /// Las notificaciones se publican aquí y cada instancia las entrega a
+    /// sus propios sockets.
+    pub bus: Arc<dyn NotificationBus>,
+    /// Cola que consume el dispatcher para repartir por canales.
+    pub queue: Arc<dyn NotificationQueue>,
+    /// Bot que atiende `/telegram/webhook`; `None` sin `TELEGRAM_BOT_TOKEN`.
+    pub telegram: Option<Arc<TelegramBot>>,
+    /// Envíos de webhooks fuera del dispatcher (eventos de la API, pruebas
+    /// y reenvíos manuales).
+    pub webhooks: Arc<WebhookChannel>,
+    /// Envía los códigos de verificación de teléfono; `None` sin
+    /// credenciales de WhatsApp.
+    pub whatsapp: Option<WhatsAppClient>,
+}
+
+impl AppState {
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_backends(pool, bus_from_env(), queue_from_env())
+    }
+
+    pub fn with_backends(
+        pool: PgPool,
+        bus: Arc<dyn NotificationBus>,
+        queue: Arc<dyn NotificationQueue>,
+    ) -> Self {
+        let telegram = TelegramClient::from_env().map(|client| Arc::new(TelegramBot::new(pool.clone(), client)));
+
+        let ticker_feed = Arc::new(TickerFeed::new(pool.clone()));
+        let webhooks = Arc::new(WebhookChannel::new(pool.clone(), queue.clone()));
+
+        Self {
+            pool,
+            ws_server: Arc::new(WebSocketServer::new(ticker_feed)),
+            bus,
+            queue,
+            telegram,
+            webhooks,
+            whatsapp: WhatsAppClient::from_env(),
+        }
+    }
+}
@@ -6,19 +6,20 @@ use axum::{
     http::StatusCode,
 };
 
-use crate::{auth::jwt::Claims, endpoints::AppState};
+use crate::{auth::jwt::Claims, endpoints::AppState, i18n::Locale};
 
 pub async fn require_admin<B>(
     State(state): State<AppState>,
     req: Request<B>,
     next: Next<B>,
 ) -> Result<Response, (StatusCode, String)> {
+    let locale = Locale::from_headers(req.headers());
     let claims = req
         .extensions()
         .get::<Claims>()
         .ok_or((
             StatusCode::INTERNAL_SERVER_ERROR,
-            "Claims not found in request".to_string(),
+            locale.t("auth.claims_missing"),
         ))?;
 
     let user = sqlx::query!(
@@ -31,17 +32,17 @@ pub async fn require_admin<B>(
     )
     .fetch_one(&state.pool)
     .await
-    .map_err(|_| {
+    .map_err(|e| {
         (
             StatusCode::INTERNAL_SERVER_ERROR,
-            "Error fetching user".to_string(),
+            locale.t_with("users.fetch_error", &[("error", &e)]),
         )
     })?;
 
     if !user.is_admin {
         return Err((
             StatusCode::FORBIDDEN,
-            "Admin privileges required".to_string(),
+            locale.t("auth.admin_required"),
         ));
     }
 
@@ -7,6 +7,8 @@ use axum::{
 };
 use std::env;
 use tracing::debug;
+
+use crate::i18n::Locale;
 use lazy_static::lazy_static;
 
 lazy_static! {
@@ -23,12 +25,14 @@ where
     type Rejection = (StatusCode, String);
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let locale = Locale::from_headers(&parts.headers);
+
         // Obtener el header de autorización
         let TypedHeader(Authorization(bearer)) = TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, _state)
             .await
             .map_err(|_| {
                 debug!("API key no proporcionada");
-                (StatusCode::UNAUTHORIZED, "API key no proporcionada".to_string())
+                (StatusCode::UNAUTHORIZED, locale.t("auth.api_key_missing"))
             })?;
 
         // Verificar que la API key sea correcta
@@ -37,7 +41,7 @@ where
             Ok(ApiKey(bearer.token().to_string()))
         } else {
             debug!("API key inválida");
-            Err((StatusCode::UNAUTHORIZED, "API key inválida".to_string()))
+            Err((StatusCode::UNAUTHORIZED, locale.t("auth.api_key_invalid")))
         }
     }
 }
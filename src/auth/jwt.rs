@@ -4,14 +4,40 @@ use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 const JWT_SECRET: &[u8] = b"secret";
+const DEFAULT_TOKEN_TTL_HOURS: i64 = 24;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub user_id: i32,
+    /// Emitido en (segundos Unix). Los tokens anteriores a este campo no lo
+    /// tienen y se deserializan con 0.
+    #[serde(default)]
+    pub iat: i64,
+    /// Expira en (segundos Unix). 0 si el token no tiene expiración.
+    #[serde(default)]
+    pub exp: i64,
+}
+
+impl Claims {
+    pub fn is_expired(&self) -> bool {
+        self.exp == 0 || self.exp <= chrono::Utc::now().timestamp()
+    }
+}
+
+fn token_ttl_hours() -> i64 {
+    std::env::var("JWT_EXPIRATION_HOURS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_TOKEN_TTL_HOURS)
 }
 
 pub fn create_token(user_id: i32) -> Result<String, jsonwebtoken::errors::Error> {
-    let claims = Claims { user_id };
+    let now = chrono::Utc::now().timestamp();
+    let claims = Claims {
+        user_id,
+        iat: now,
+        exp: now + token_ttl_hours() * 3600,
+    };
     encode(
         &Header::default(),
         &claims,
@@ -35,4 +61,4 @@ pub fn verify_token(token: &str) -> Result<Claims, StatusCode> {
         debug!("Token verification error: {}", err);
         StatusCode::UNAUTHORIZED
     })
-}
\ No newline at end of file
+}
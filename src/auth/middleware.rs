@@ -5,7 +5,10 @@ use axum::{
 };
 use tracing::debug;
 
-use crate::auth::jwt::{self, Claims};
+use crate::{
+    auth::jwt::{self, Claims},
+    i18n::Locale,
+};
 
 #[async_trait]
 impl<S> FromRequestParts<S> for Claims
@@ -16,6 +19,7 @@ where
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         debug!("Headers: {:?}", parts.headers);
+        let locale = Locale::from_headers(&parts.headers);
         
         let auth_header = parts
             .headers
@@ -24,7 +28,7 @@ where
                 debug!("Missing Authorization header");
                 (
                     StatusCode::UNAUTHORIZED,
-                    "Missing Authorization header".to_string(),
+                    locale.t("auth.missing_authorization"),
                 )
             })?;
 
@@ -34,7 +38,7 @@ where
                 debug!("Invalid Authorization header: {}", e);
                 (
                     StatusCode::UNAUTHORIZED,
-                    "Invalid Authorization header".to_string(),
+                    locale.t("auth.invalid_authorization"),
                 )
             })?;
 
@@ -43,7 +47,7 @@ where
             debug!("Authorization header must start with Bearer");
             return Err((
                 StatusCode::UNAUTHORIZED,
-                "Authorization header must start with Bearer".to_string(),
+                locale.t("auth.bearer_required"),
             ));
         }
 
@@ -53,7 +57,7 @@ where
         jwt::verify_token(token)
             .map_err(|e| {
                 debug!("Token verification failed: {:?}", e);
-                (StatusCode::UNAUTHORIZED, "Invalid token".to_string())
+                (StatusCode::UNAUTHORIZED, locale.t("auth.invalid_token"))
             })
     }
 }
\ No newline at end of file
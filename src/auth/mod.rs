@@ -7,6 +7,7 @@ use axum::{
 use crate::{
     app_state::AppState,
     db::users::{self, LoginRequest},
+    i18n::Locale,
     models::users::CreateUserRequest,
 };
 
@@ -17,13 +18,14 @@ pub mod middleware;
 pub async fn register(
     State(state): State<AppState>,
     api_key::ApiKey(_): api_key::ApiKey,
+    locale: Locale,
     Json(req): Json<CreateUserRequest>,
 ) -> Result<Json<()>, (StatusCode, String)> {
     match users::create_user(&state.pool, &req).await {
         Ok(_) => Ok(Json(())),
         Err(_) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to create user".to_string(),
+            locale.t("users.create_error"),
         )),
     }
 }
@@ -31,6 +33,7 @@ pub async fn register(
 pub async fn login(
     State(state): State<AppState>,
     api_key::ApiKey(_): api_key::ApiKey,
+    locale: Locale,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<String>, (StatusCode, String)> {
     match users::authenticate_user(&state.pool, &req.username, &req.password).await {
@@ -38,14 +41,14 @@ pub async fn login(
             let token = jwt::create_token(user.id).map_err(|_| {
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to create token".to_string(),
+                    locale.t("auth.token_error"),
                 )
             })?;
             Ok(Json(token))
         }
         Err(_) => Err((
             StatusCode::UNAUTHORIZED,
-            "Invalid username or password".to_string(),
+            locale.t("auth.invalid_credentials"),
         )),
     }
 }
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use dotenv::dotenv;
+use my_rust_api::{
+    db::init::init_pool,
+    notifications::{bus::bus_from_env, dispatcher::build_dispatcher, queue::RedisNotificationQueue},
+};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Resuelve con Ctrl+C o, en Unix, con SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("Shutdown signal received, draining in-flight notifications");
+}
+
+#[tokio::main]
+async fn main() {
+    // Load environment variables
+    dotenv().ok();
+
+    // Initialize tracing
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
+        ))
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let redis_url = std::env::var("REDIS_URL").expect("REDIS_URL must be set");
+
+    let pool = init_pool(&database_url).await.expect("Failed to create pool");
+    let queue = Arc::new(RedisNotificationQueue::new(&redis_url).expect("Invalid REDIS_URL"));
+
+    build_dispatcher(pool, queue, bus_from_env())
+        .run(shutdown_signal())
+        .await;
+}
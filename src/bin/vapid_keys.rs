@@ -0,0 +1,9 @@
+use my_rust_api::notifications::web_push::VapidKeys;
+
+/// Genera un par de claves VAPID para Web Push, listo para el `.env`.
+fn main() {
+    let (private_key, public_key) = VapidKeys::generate();
+    println!("VAPID_PRIVATE_KEY={}", private_key);
+    println!("# Clave pública (también disponible en GET /notifications/push/vapid-public-key)");
+    println!("# {}", public_key);
+}
@@ -14,16 +14,18 @@ pub async fn create_asset_pair(
         r#"
         INSERT INTO asset_pairs (
             user_id, 
+            exchange,
             base_asset, 
             quote_asset, 
             slip_percentage,
             created_at,
             updated_at
         )
-        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+        VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
         RETURNING *
         "#,
         user_id,
+        req.exchange,
         req.base_asset,
         req.quote_asset,
         req.slip_percentage,
@@ -151,13 +153,15 @@ pub async fn update_asset_pair(
         AssetPair,
         r#"
         UPDATE asset_pairs
-        SET base_asset = $1,
-            quote_asset = $2,
-            slip_percentage = $3,
+        SET exchange = $1,
+            base_asset = $2,
+            quote_asset = $3,
+            slip_percentage = $4,
             updated_at = CURRENT_TIMESTAMP
-        WHERE id = $4 AND user_id = $5
+        WHERE id = $5 AND user_id = $6
         RETURNING *
         "#,
+        req.exchange,
         req.base_asset,
         req.quote_asset,
         req.slip_percentage,
@@ -234,7 +238,7 @@ pub async fn get_all_asset_pairs_admin(pool: &PgPool) -> Result<Vec<AssetPair>,
     sqlx::query_as!(
         AssetPair,
         r#"
-        SELECT id, user_id, base_asset, quote_asset, slip_percentage, created_at, updated_at
+        SELECT id, user_id, exchange, base_asset, quote_asset, slip_percentage, created_at, updated_at
         FROM asset_pairs
         ORDER BY id
         "#
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::models::candles::{aggregate_candles, find_gaps, Candle, Timeframe};
+
+pub async fn upsert_candles(pool: &PgPool, candles: &[Candle]) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut count = 0;
+
+    for candle in candles {
+        sqlx::query(
+            r#"
+            INSERT INTO candles (
+                instrument_id, timeframe, open_time, open, high, low, close, volume
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (instrument_id, timeframe, open_time) DO UPDATE
+            SET open = EXCLUDED.open,
+                high = EXCLUDED.high,
+                low = EXCLUDED.low,
+                close = EXCLUDED.close,
+                volume = EXCLUDED.volume
+            "#
+        )
+        .bind(candle.instrument_id)
+        .bind(&candle.timeframe)
+        .bind(candle.open_time)
+        .bind(&candle.open)
+        .bind(&candle.high)
+        .bind(&candle.low)
+        .bind(&candle.close)
+        .bind(&candle.volume)
+        .execute(&mut *tx)
+        .await?;
+        count += 1;
+    }
+
+    tx.commit().await?;
+    Ok(count)
+}
+
+/// Velas de `[start, end)` ordenadas por `open_time`, como máximo `limit`.
+pub async fn list_candles(
+    pool: &PgPool,
+    instrument_id: i32,
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Candle>, sqlx::Error> {
+    sqlx::query_as::<_, Candle>(
+        r#"
+        SELECT instrument_id, timeframe, open_time, open, high, low, close, volume
+        FROM candles
+        WHERE instrument_id = $1 AND timeframe = $2
+          AND open_time >= $3 AND open_time < $4
+        ORDER BY open_time
+        LIMIT $5
+        "#
+    )
+    .bind(instrument_id)
+    .bind(timeframe.as_str())
+    .bind(start)
+    .bind(end)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn count_candles(
+    pool: &PgPool,
+    instrument_id: i32,
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT COUNT(*)
+        FROM candles
+        WHERE instrument_id = $1 AND timeframe = $2
+          AND open_time >= $3 AND open_time < $4
+        "#
+    )
+    .bind(instrument_id)
+    .bind(timeframe.as_str())
+    .bind(start)
+    .bind(end)
+    .fetch_one(pool)
+    .await
+}
+
+/// Borra las velas más antiguas que la retención de cada timeframe.
+pub async fn apply_retention(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let mut deleted = 0;
+
+    for timeframe in Timeframe::ALL {
+        let Some(days) = timeframe.retention_days() else {
+            continue;
+        };
+
+        let cutoff = Utc::now() - chrono::Duration::days(days);
+        let result = sqlx::query(
+            r#"
+            DELETE FROM candles
+            WHERE timeframe = $1 AND open_time < $2
+            "#
+        )
+        .bind(timeframe.as_str())
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() > 0 {
+            info!(
+                "Retención de velas {}: {} filas eliminadas",
+                timeframe,
+                result.rows_affected()
+            );
+        }
+        deleted += result.rows_affected();
+    }
+
+    Ok(deleted)
+}
+
+/// Devuelve velas del timeframe pedido. Los huecos entre las velas
+/// guardadas para ese timeframe se rellenan agregando al vuelo las de 1m;
+/// donde hay vela guardada, manda la guardada.
+pub async fn load_candles(
+    pool: &PgPool,
+    instrument_id: i32,
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Candle>, sqlx::Error> {
+    if timeframe == Timeframe::M1 {
+        return list_candles(pool, instrument_id, timeframe, start, end, limit).await;
+    }
+
+    let start = timeframe.bucket_start(start);
+    let stored = list_candles(pool, instrument_id, timeframe, start, end, limit).await?;
+
+    // Con el límite alcanzado sólo importan los huecos hasta la última vela
+    let window_end = match stored.last() {
+        Some(last) if stored.len() as i64 >= limit => last.open_time + timeframe.duration(),
+        _ => end,
+    };
+    if find_gaps(&stored, timeframe, start, window_end).is_empty() {
+        return Ok(stored);
+    }
+
+    let minutes_per_candle = timeframe.seconds() / Timeframe::M1.seconds();
+    let base = list_candles(
+        pool,
+        instrument_id,
+        Timeframe::M1,
+        start,
+        window_end,
+        limit * minutes_per_candle,
+    )
+    .await?;
+
+    let mut merged: BTreeMap<DateTime<Utc>, Candle> = aggregate_candles(&base, timeframe)
+        .into_iter()
+        .map(|candle| (candle.open_time, candle))
+        .collect();
+    merged.extend(stored.into_iter().map(|candle| (candle.open_time, candle)));
+
+    Ok(merged.into_values().take(limit as usize).collect())
+}
@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use sqlx::{types::Json, PgPool};
+
+use crate::notifications::Notification;
+
+/// Notificación retenida para un canal por las horas de silencio o a la
+/// espera del próximo resumen.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct HeldNotification {
+    pub id: i64,
+    pub queue_id: String,
+    pub user_id: i32,
+    pub channel: String,
+    pub notification: Json<Notification>,
+    pub deliver_after: DateTime<Utc>,
+}
+
+/// Retiene la notificación. Si ya estaba retenida para el canal (un
+/// reintento desde la cola) no se duplica.
+pub async fn hold_notification(
+    pool: &PgPool,
+    queue_id: &str,
+    channel: &str,
+    notification: &Notification,
+    deliver_after: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO held_notifications (queue_id, user_id, channel, notification, deliver_after)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (queue_id, channel) DO NOTHING
+        "#
+    )
+    .bind(queue_id)
+    .bind(notification.user_id)
+    .bind(channel)
+    .bind(Json(notification))
+    .bind(deliver_after)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Saca de la tabla las notificaciones de hasta `limit` pares usuario/canal
+/// con alguna ya vencida. Se devuelven todas las vencidas de cada par, en
+/// orden de llegada, para enviarlas en un único resumen.
+pub async fn take_due(pool: &PgPool, limit: i64) -> Result<Vec<HeldNotification>, sqlx::Error> {
+    sqlx::query_as::<_, HeldNotification>(
+        r#"
+        WITH due AS (
+            SELECT user_id, channel
+            FROM held_notifications
+            WHERE deliver_after <= CURRENT_TIMESTAMP
+            GROUP BY user_id, channel
+            LIMIT $1
+        )
+        DELETE FROM held_notifications h
+        USING due
+        WHERE h.user_id = due.user_id
+          AND h.channel = due.channel
+          AND h.deliver_after <= CURRENT_TIMESTAMP
+        RETURNING h.id, h.queue_id, h.user_id, h.channel, h.notification, h.deliver_after
+        "#
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map(|mut held| {
+        held.sort_by_key(|h| h.id);
+        held
+    })
+}
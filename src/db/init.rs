@@ -42,6 +42,12 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
             email VARCHAR(255) UNIQUE NOT NULL,
             password_hash VARCHAR(255) NOT NULL,
             is_admin BOOLEAN DEFAULT false,
+            is_active BOOLEAN DEFAULT true,
+            tokens_revoked_at TIMESTAMP WITH TIME ZONE,
+            last_event_id BIGINT NOT NULL DEFAULT 0,
+            telegram_id VARCHAR(100),
+            telegram_muted_until TIMESTAMP WITH TIME ZONE,
+            locale VARCHAR(5),
             created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
             updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
         )
@@ -57,6 +63,9 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
             id SERIAL PRIMARY KEY,
             user_id INTEGER NOT NULL REFERENCES users(id),
             email VARCHAR(255) NOT NULL,
+            phone_number VARCHAR(20),
+            phone_verified_at TIMESTAMP WITH TIME ZONE,
+            whatsapp_last_inbound_at TIMESTAMP WITH TIME ZONE,
             created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
             updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
             UNIQUE(user_id)
@@ -111,8 +120,17 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
             email_enabled BOOLEAN DEFAULT true,
             telegram_enabled BOOLEAN DEFAULT true,
             whatsapp_enabled BOOLEAN DEFAULT false,
+            web_push_enabled BOOLEAN DEFAULT true,
             price_alerts_enabled BOOLEAN DEFAULT true,
+            market_sentiment_enabled BOOLEAN DEFAULT true,
+            strategy_updates_enabled BOOLEAN DEFAULT true,
+            trade_execution_enabled BOOLEAN DEFAULT true,
             system_alerts_enabled BOOLEAN DEFAULT true,
+            min_priority JSONB NOT NULL DEFAULT '{}',
+            timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+            quiet_hours_start TIME,
+            quiet_hours_end TIME,
+            digest JSONB NOT NULL DEFAULT '{}',
             created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
             updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
             UNIQUE(user_id)
@@ -122,16 +140,282 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Create notifications table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS notifications (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id),
+            event_id BIGINT NOT NULL,
+            notification_type VARCHAR(50) NOT NULL,
+            priority VARCHAR(10) NOT NULL DEFAULT 'normal',
+            title TEXT NOT NULL,
+            message TEXT NOT NULL,
+            metadata JSONB NOT NULL DEFAULT '{}',
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            read_at TIMESTAMP WITH TIME ZONE,
+            queue_id VARCHAR(64),
+            UNIQUE(user_id, event_id)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_notifications_unread
+            ON notifications (user_id)
+            WHERE read_at IS NULL
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create notification_deliveries table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_deliveries (
+            id SERIAL PRIMARY KEY,
+            queue_id VARCHAR(64) NOT NULL,
+            user_id INTEGER NOT NULL REFERENCES users(id),
+            channel VARCHAR(20) NOT NULL,
+            status VARCHAR(20) NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 1,
+            error TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(queue_id, channel)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     // Create webhooks table
     sqlx::query!(
         r#"
         CREATE TABLE IF NOT EXISTS webhooks (
             id UUID PRIMARY KEY,
             user_id INTEGER NOT NULL REFERENCES users(id),
+            kind VARCHAR(20) NOT NULL DEFAULT 'generic',
             url TEXT NOT NULL,
             secret TEXT NOT NULL,
             notification_types JSONB NOT NULL,
             enabled BOOLEAN DEFAULT true,
+            consecutive_failures INTEGER NOT NULL DEFAULT 0,
+            previous_secret TEXT,
+            previous_secret_expires_at TIMESTAMP WITH TIME ZONE,
+            api_version INTEGER NOT NULL DEFAULT 1,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create webhook_deliveries table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id UUID PRIMARY KEY,
+            delivery_id UUID NOT NULL,
+            webhook_id UUID NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+            user_id INTEGER NOT NULL REFERENCES users(id),
+            attempt INTEGER NOT NULL,
+            status VARCHAR(20) NOT NULL,
+            status_code INTEGER,
+            latency_ms INTEGER,
+            response_snippet TEXT,
+            error TEXT,
+            payload TEXT NOT NULL,
+            next_attempt_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_retry
+            ON webhook_deliveries (next_attempt_at)
+            WHERE status = 'retry_scheduled'
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create telegram_link_codes table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS telegram_link_codes (
+            code VARCHAR(16) PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create notification_rules table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_rules (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            notification_type VARCHAR(50),
+            channel VARCHAR(20),
+            asset VARCHAR(20),
+            enabled BOOLEAN NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Una regla por combinación; NULL (cualquiera) cuenta como un valor más
+    sqlx::query(
+        r#"
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_notification_rules_scope
+            ON notification_rules (user_id, (COALESCE(notification_type, '')), (COALESCE(channel, '')), (COALESCE(asset, '')))
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create held_notifications table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS held_notifications (
+            id BIGSERIAL PRIMARY KEY,
+            queue_id VARCHAR(64) NOT NULL,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            channel VARCHAR(20) NOT NULL,
+            notification JSONB NOT NULL,
+            deliver_after TIMESTAMP WITH TIME ZONE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(queue_id, channel)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_held_notifications_deliver_after
+            ON held_notifications (deliver_after)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create notification_dedup table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_dedup (
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            dedup_key TEXT NOT NULL,
+            queue_id VARCHAR(64) NOT NULL,
+            suppressed INTEGER NOT NULL DEFAULT 0,
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            PRIMARY KEY (user_id, dedup_key)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create notification_rate_limits table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_rate_limits (
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            channel VARCHAR(20) NOT NULL,
+            window_start TIMESTAMP WITH TIME ZONE NOT NULL,
+            sent INTEGER NOT NULL DEFAULT 0,
+            suppressed INTEGER NOT NULL DEFAULT 0,
+            overflow INTEGER NOT NULL DEFAULT 0,
+            allowed BOOLEAN NOT NULL DEFAULT true,
+            PRIMARY KEY (user_id, channel)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create push_subscriptions table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS push_subscriptions (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            endpoint TEXT NOT NULL UNIQUE,
+            p256dh TEXT NOT NULL,
+            auth TEXT NOT NULL,
+            user_agent TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create phone_verifications table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS phone_verifications (
+            user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+            phone_number VARCHAR(20) NOT NULL,
+            code_hash VARCHAR(64) NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create phone_verification_sends table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS phone_verification_sends (
+            id BIGSERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            phone_number VARCHAR(20) NOT NULL,
+            sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_phone_verification_sends_sent_at
+            ON phone_verification_sends (sent_at)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create whatsapp_messages table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS whatsapp_messages (
+            message_id VARCHAR(128) PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            event_id BIGINT,
+            purpose VARCHAR(20) NOT NULL,
+            status VARCHAR(20) NOT NULL DEFAULT 'accepted',
+            error TEXT,
             created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
             updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
         )
@@ -140,12 +424,22 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query!(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_whatsapp_messages_event
+            ON whatsapp_messages (user_id, event_id)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     // Create asset_pairs table
     sqlx::query!(
         r#"
         CREATE TABLE IF NOT EXISTS asset_pairs (
             id SERIAL PRIMARY KEY,
             user_id INTEGER NOT NULL REFERENCES users(id),
+            exchange VARCHAR(50) NOT NULL DEFAULT 'binance',
             base_asset VARCHAR(50) NOT NULL,
             quote_asset VARCHAR(50) NOT NULL,
             slip_percentage DECIMAL NOT NULL,
@@ -157,6 +451,47 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Create instruments table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS instruments (
+            id SERIAL PRIMARY KEY,
+            exchange VARCHAR(50) NOT NULL,
+            symbol VARCHAR(50) NOT NULL,
+            exchange_symbol VARCHAR(50) NOT NULL,
+            base_asset VARCHAR(20) NOT NULL,
+            quote_asset VARCHAR(20) NOT NULL,
+            tick_size DECIMAL NOT NULL,
+            lot_size DECIMAL NOT NULL,
+            min_notional DECIMAL,
+            status VARCHAR(20) NOT NULL,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(exchange, symbol)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Create candles table
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS candles (
+            instrument_id INTEGER NOT NULL REFERENCES instruments(id) ON DELETE CASCADE,
+            timeframe VARCHAR(5) NOT NULL,
+            open_time TIMESTAMP WITH TIME ZONE NOT NULL,
+            open DECIMAL NOT NULL,
+            high DECIMAL NOT NULL,
+            low DECIMAL NOT NULL,
+            close DECIMAL NOT NULL,
+            volume DECIMAL NOT NULL,
+            PRIMARY KEY (instrument_id, timeframe, open_time)
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     // Create price_alerts table
     sqlx::query!(
         r#"
@@ -169,6 +504,7 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
             is_active BOOLEAN DEFAULT true,
             trigger_price DECIMAL,
             triggered_at TIMESTAMP WITH TIME ZONE,
+            snoozed_until TIMESTAMP WITH TIME ZONE,
             created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
             updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
         )
@@ -177,5 +513,142 @@ pub async fn init_database(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Add columns introduced after the initial schema
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS is_active BOOLEAN DEFAULT true,
+            ADD COLUMN IF NOT EXISTS tokens_revoked_at TIMESTAMP WITH TIME ZONE,
+            ADD COLUMN IF NOT EXISTS last_event_id BIGINT NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS telegram_id VARCHAR(100),
+            ADD COLUMN IF NOT EXISTS telegram_muted_until TIMESTAMP WITH TIME ZONE,
+            ADD COLUMN IF NOT EXISTS locale VARCHAR(5)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Un chat de Telegram sólo puede estar vinculado a un usuario
+    sqlx::query(
+        r#"
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_users_telegram_id
+            ON users (telegram_id)
+            WHERE telegram_id IS NOT NULL
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE notifications
+            ADD COLUMN IF NOT EXISTS priority VARCHAR(10) NOT NULL DEFAULT 'normal',
+            ADD COLUMN IF NOT EXISTS queue_id VARCHAR(64)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Cada entrada de la cola se guarda una sola vez en la bandeja, aunque
+    // su entrega se reintente
+    sqlx::query(
+        r#"
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_notifications_queue_id
+            ON notifications (user_id, queue_id)
+            WHERE queue_id IS NOT NULL
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // El historial guardaba el nombre de la variante (`PriceAlert`) en vez
+    // del tipo que usan los filtros (`price_alert`)
+    sqlx::query(
+        r#"
+        UPDATE notifications
+        SET notification_type = CASE notification_type
+            WHEN 'PriceAlert' THEN 'price_alert'
+            WHEN 'MarketSentiment' THEN 'market_sentiment'
+            WHEN 'StrategyUpdate' THEN 'strategy_update'
+            WHEN 'TradeExecution' THEN 'trade_execution'
+            WHEN 'SystemAlert' THEN 'system_alert'
+        END
+        WHERE notification_type IN
+            ('PriceAlert', 'MarketSentiment', 'StrategyUpdate', 'TradeExecution', 'SystemAlert')
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE notification_preferences
+            ADD COLUMN IF NOT EXISTS web_push_enabled BOOLEAN DEFAULT true,
+            ADD COLUMN IF NOT EXISTS market_sentiment_enabled BOOLEAN DEFAULT true,
+            ADD COLUMN IF NOT EXISTS strategy_updates_enabled BOOLEAN DEFAULT true,
+            ADD COLUMN IF NOT EXISTS trade_execution_enabled BOOLEAN DEFAULT true,
+            ADD COLUMN IF NOT EXISTS min_priority JSONB NOT NULL DEFAULT '{}',
+            ADD COLUMN IF NOT EXISTS timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+            ADD COLUMN IF NOT EXISTS quiet_hours_start TIME,
+            ADD COLUMN IF NOT EXISTS quiet_hours_end TIME,
+            ADD COLUMN IF NOT EXISTS digest JSONB NOT NULL DEFAULT '{}'
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE personal_data
+            ADD COLUMN IF NOT EXISTS phone_number VARCHAR(20),
+            ADD COLUMN IF NOT EXISTS phone_verified_at TIMESTAMP WITH TIME ZONE,
+            ADD COLUMN IF NOT EXISTS whatsapp_last_inbound_at TIMESTAMP WITH TIME ZONE
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    // Un número verificado sólo puede pertenecer a un usuario
+    sqlx::query(
+        r#"
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_personal_data_phone_number
+            ON personal_data (phone_number)
+            WHERE phone_verified_at IS NOT NULL
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE asset_pairs
+            ADD COLUMN IF NOT EXISTS exchange VARCHAR(50) NOT NULL DEFAULT 'binance'
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE price_alerts
+            ADD COLUMN IF NOT EXISTS snoozed_until TIMESTAMP WITH TIME ZONE
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE webhooks
+            ADD COLUMN IF NOT EXISTS consecutive_failures INTEGER NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS previous_secret TEXT,
+            ADD COLUMN IF NOT EXISTS previous_secret_expires_at TIMESTAMP WITH TIME ZONE,
+            ADD COLUMN IF NOT EXISTS api_version INTEGER NOT NULL DEFAULT 1,
+            ADD COLUMN IF NOT EXISTS kind VARCHAR(20) NOT NULL DEFAULT 'generic'
+        "#
+    )
+    .execute(pool)
+    .await?;
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::models::instruments::{Instrument, NewInstrument};
+
+pub async fn upsert_instruments(
+    pool: &PgPool,
+    instruments: &[NewInstrument],
+) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut count = 0;
+
+    for instrument in instruments {
+        sqlx::query(
+            r#"
+            INSERT INTO instruments (
+                exchange, symbol, exchange_symbol, base_asset, quote_asset,
+                tick_size, lot_size, min_notional, status, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, CURRENT_TIMESTAMP)
+            ON CONFLICT (exchange, symbol) DO UPDATE
+            SET exchange_symbol = EXCLUDED.exchange_symbol,
+                tick_size = EXCLUDED.tick_size,
+                lot_size = EXCLUDED.lot_size,
+                min_notional = EXCLUDED.min_notional,
+                status = EXCLUDED.status,
+                updated_at = CURRENT_TIMESTAMP
+            "#
+        )
+        .bind(&instrument.exchange)
+        .bind(instrument.symbol())
+        .bind(&instrument.exchange_symbol)
+        .bind(&instrument.base_asset)
+        .bind(&instrument.quote_asset)
+        .bind(&instrument.tick_size)
+        .bind(&instrument.lot_size)
+        .bind(&instrument.min_notional)
+        .bind(&instrument.status)
+        .execute(&mut *tx)
+        .await?;
+        count += 1;
+    }
+
+    tx.commit().await?;
+    info!("{} instrumentos sincronizados", count);
+    Ok(count)
+}
+
+/// Marca como `delisted` los instrumentos de un exchange que ya no aparecen
+/// en la última sincronización.
+pub async fn mark_missing_delisted(
+    pool: &PgPool,
+    exchange: &str,
+    symbols: &[String],
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE instruments
+        SET status = 'delisted', updated_at = CURRENT_TIMESTAMP
+        WHERE exchange = $1 AND status <> 'delisted' AND NOT (symbol = ANY($2))
+        "#
+    )
+    .bind(exchange)
+    .bind(symbols)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn find_instrument(
+    pool: &PgPool,
+    exchange: &str,
+    base_asset: &str,
+    quote_asset: &str,
+) -> Result<Option<Instrument>, sqlx::Error> {
+    let result = sqlx::query_as::<_, Instrument>(
+        r#"
+        SELECT id, exchange, symbol, exchange_symbol, base_asset, quote_asset,
+               tick_size, lot_size, min_notional, status, updated_at
+        FROM instruments
+        WHERE exchange = $1 AND base_asset = $2 AND quote_asset = $3
+        "#
+    )
+    .bind(exchange)
+    .bind(base_asset)
+    .bind(quote_asset)
+    .fetch_optional(pool)
+    .await;
+
+    if let Err(e) = &result {
+        error!("Error al buscar instrumento {}/{} en {}: {:?}", base_asset, quote_asset, exchange, e);
+    }
+
+    result
+}
+
+pub async fn list_instruments(
+    pool: &PgPool,
+    exchange: Option<&str>,
+    quote_asset: Option<&str>,
+) -> Result<Vec<Instrument>, sqlx::Error> {
+    sqlx::query_as::<_, Instrument>(
+        r#"
+        SELECT id, exchange, symbol, exchange_symbol, base_asset, quote_asset,
+               tick_size, lot_size, min_notional, status, updated_at
+        FROM instruments
+        WHERE ($1::TEXT IS NULL OR exchange = $1)
+          AND ($2::TEXT IS NULL OR quote_asset = $2)
+        ORDER BY exchange, symbol
+        "#
+    )
+    .bind(exchange)
+    .bind(quote_asset)
+    .fetch_all(pool)
+    .await
+}
+
+/// Candidatos para sugerencias: instrumentos activos del exchange que
+/// comparten base, quote o prefijo de base con el par buscado.
+pub async fn list_similar_instruments(
+    pool: &PgPool,
+    exchange: &str,
+    base_asset: &str,
+    quote_asset: &str,
+) -> Result<Vec<Instrument>, sqlx::Error> {
+    sqlx::query_as::<_, Instrument>(
+        r#"
+        SELECT id, exchange, symbol, exchange_symbol, base_asset, quote_asset,
+               tick_size, lot_size, min_notional, status, updated_at
+        FROM instruments
+        WHERE exchange = $1
+          AND status = 'trading'
+          AND (base_asset = $2 OR quote_asset = $3
+               OR base_asset LIKE LEFT($2, 2) || '%')
+        "#
+    )
+    .bind(exchange)
+    .bind(base_asset)
+    .bind(quote_asset)
+    .fetch_all(pool)
+    .await
+}
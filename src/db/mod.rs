@@ -6,6 +6,16 @@ pub mod users;
 pub mod personal_data;
 pub mod api_keys;
 pub mod notifications;
+pub mod asset_pairs;
+pub mod price_alerts;
+pub mod instruments;
+pub mod candles;
+pub mod webhook_deliveries;
+pub mod held_notifications;
+pub mod rate_limits;
+pub mod push_subscriptions;
+pub mod telegram;
+pub mod whatsapp;
 
 pub async fn init_pool(database_url: &str) -> Result<Pool<Postgres>, sqlx::Error> {
     info!("Intentando conectar a la base de datos: {}", database_url);
@@ -2,82 +2,218 @@ use sqlx::PgPool;
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
 use tracing::{debug, error};
-use crate::models::notifications::{NotificationPreference, CreateWebhookRequest, WebhookConfig};
+use sqlx::types::Json;
+use crate::models::notifications::{
+    CreateWebhookRequest, NotificationPreference, NotificationRule, UpdatePreferencesRequest, UpdateWebhookRequest,
+    WebhookConfig,
+};
+use crate::notifications::{events, webhook::generate_webhook_secret};
+use crate::notifications::{
+    models::{DbNotification, NotificationListQuery},
+    Notification,
+};
+
+/// Resultado de entregar una notificación por un canal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Delivered,
+    Failed,
+    /// El usuario tiene el canal deshabilitado o sin destino configurado.
+    Skipped,
+    /// Retenida por las horas de silencio o para un resumen.
+    Held,
+    /// Omitida por el límite de envíos del canal; entra en el resumen de
+    /// omitidas.
+    RateLimited,
+    /// El dispatcher no tiene el canal registrado.
+    Unavailable,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+            DeliveryStatus::Skipped => "skipped",
+            DeliveryStatus::Held => "held",
+            DeliveryStatus::RateLimited => "rate_limited",
+            DeliveryStatus::Unavailable => "unavailable",
+        }
+    }
+}
+
+const PREFERENCE_COLUMNS: &str = "user_id, email_enabled, telegram_enabled, whatsapp_enabled, web_push_enabled, \
+    price_alerts_enabled, market_sentiment_enabled, strategy_updates_enabled, trade_execution_enabled, \
+    system_alerts_enabled, min_priority, timezone, quiet_hours_start, quiet_hours_end, digest, created_at, updated_at";
 
 pub async fn get_notification_preferences(pool: &PgPool, user_id: i32) -> Result<NotificationPreference, sqlx::Error> {
-    sqlx::query_as::<_, NotificationPreference>(
+    sqlx::query_as::<_, NotificationPreference>(&format!(
+        "SELECT {} FROM notification_preferences WHERE user_id = $1",
+        PREFERENCE_COLUMNS
+    ))
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Crea las preferencias por defecto si el usuario no tiene y devuelve las
+/// vigentes.
+pub async fn create_default_preferences(pool: &PgPool, user_id: i32) -> Result<NotificationPreference, sqlx::Error> {
+    sqlx::query_as::<_, NotificationPreference>(&format!(
         r#"
-        SELECT user_id, email_enabled, telegram_enabled, whatsapp_enabled,
-               price_alerts_enabled, system_alerts_enabled, created_at, updated_at
-        FROM notification_preferences
-        WHERE user_id = $1
-        "#
-    )
+        INSERT INTO notification_preferences (user_id)
+        VALUES ($1)
+        ON CONFLICT (user_id) DO UPDATE SET user_id = EXCLUDED.user_id
+        RETURNING {}
+        "#,
+        PREFERENCE_COLUMNS
+    ))
     .bind(user_id)
     .fetch_one(pool)
     .await
 }
 
+/// Aplica los campos presentes en `request`. Si el usuario no tenía
+/// preferencias se crean con los valores por defecto para el resto.
 pub async fn update_notification_preferences(
     pool: &PgPool,
     user_id: i32,
-    email_enabled: Option<bool>,
-    telegram_enabled: Option<bool>,
-    whatsapp_enabled: Option<bool>,
-    price_alerts_enabled: Option<bool>,
-    system_alerts_enabled: Option<bool>,
+    request: &UpdatePreferencesRequest,
 ) -> Result<NotificationPreference, sqlx::Error> {
-    let mut updates = Vec::new();
-    let mut params = vec![user_id];
-    let mut param_count = 2;
-
-    if let Some(email) = email_enabled {
-        updates.push(format!("email_enabled = ${}", param_count));
-        params.push(email as i32);
-        param_count += 1;
-    }
+    sqlx::query("INSERT INTO notification_preferences (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
 
-    if let Some(telegram) = telegram_enabled {
-        updates.push(format!("telegram_enabled = ${}", param_count));
-        params.push(telegram as i32);
-        param_count += 1;
-    }
+    sqlx::query_as::<_, NotificationPreference>(&format!(
+        r#"
+        UPDATE notification_preferences
+        SET email_enabled = COALESCE($2, email_enabled),
+            telegram_enabled = COALESCE($3, telegram_enabled),
+            whatsapp_enabled = COALESCE($4, whatsapp_enabled),
+            web_push_enabled = COALESCE($5, web_push_enabled),
+            price_alerts_enabled = COALESCE($6, price_alerts_enabled),
+            market_sentiment_enabled = COALESCE($7, market_sentiment_enabled),
+            strategy_updates_enabled = COALESCE($8, strategy_updates_enabled),
+            trade_execution_enabled = COALESCE($9, trade_execution_enabled),
+            system_alerts_enabled = COALESCE($10, system_alerts_enabled),
+            min_priority = COALESCE($11, min_priority),
+            timezone = COALESCE($12, timezone),
+            quiet_hours_start = CASE WHEN $13 THEN $14 ELSE quiet_hours_start END,
+            quiet_hours_end = CASE WHEN $13 THEN $15 ELSE quiet_hours_end END,
+            digest = COALESCE($16, digest),
+            updated_at = now()
+        WHERE user_id = $1
+        RETURNING {}
+        "#,
+        PREFERENCE_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(request.email_enabled)
+    .bind(request.telegram_enabled)
+    .bind(request.whatsapp_enabled)
+    .bind(request.web_push_enabled)
+    .bind(request.price_alerts_enabled)
+    .bind(request.market_sentiment_enabled)
+    .bind(request.strategy_updates_enabled)
+    .bind(request.trade_execution_enabled)
+    .bind(request.system_alerts_enabled)
+    .bind(request.min_priority.as_ref().map(Json))
+    .bind(request.timezone.as_deref())
+    .bind(request.quiet_hours.is_some())
+    .bind(request.quiet_hours.as_ref().and_then(|q| q.start))
+    .bind(request.quiet_hours.as_ref().and_then(|q| q.end))
+    .bind(request.digest.as_ref().map(Json))
+    .fetch_one(pool)
+    .await
+}
 
-    if let Some(whatsapp) = whatsapp_enabled {
-        updates.push(format!("whatsapp_enabled = ${}", param_count));
-        params.push(whatsapp as i32);
-        param_count += 1;
-    }
+pub async fn list_notification_rules(pool: &PgPool, user_id: i32) -> Result<Vec<NotificationRule>, sqlx::Error> {
+    sqlx::query_as::<_, NotificationRule>(
+        r#"
+        SELECT id, user_id, notification_type, channel, asset, enabled, created_at
+        FROM notification_rules
+        WHERE user_id = $1
+        ORDER BY id
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
 
-    if let Some(price) = price_alerts_enabled {
-        updates.push(format!("price_alerts_enabled = ${}", param_count));
-        params.push(price as i32);
-        param_count += 1;
-    }
+/// Crea la regla o actualiza `enabled` si ya existe una para la misma
+/// combinación de tipo, canal y activo.
+pub async fn upsert_notification_rule(
+    pool: &PgPool,
+    user_id: i32,
+    notification_type: Option<&str>,
+    channel: Option<&str>,
+    asset: Option<&str>,
+    enabled: bool,
+) -> Result<NotificationRule, sqlx::Error> {
+    sqlx::query_as::<_, NotificationRule>(
+        r#"
+        INSERT INTO notification_rules (user_id, notification_type, channel, asset, enabled)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (user_id, (COALESCE(notification_type, '')), (COALESCE(channel, '')), (COALESCE(asset, '')))
+        DO UPDATE SET enabled = EXCLUDED.enabled
+        RETURNING id, user_id, notification_type, channel, asset, enabled, created_at
+        "#
+    )
+    .bind(user_id)
+    .bind(notification_type)
+    .bind(channel)
+    .bind(asset)
+    .bind(enabled)
+    .fetch_one(pool)
+    .await
+}
 
-    if let Some(system) = system_alerts_enabled {
-        updates.push(format!("system_alerts_enabled = ${}", param_count));
-        params.push(system as i32);
-        param_count += 1;
-    }
+pub async fn delete_notification_rule(pool: &PgPool, user_id: i32, rule_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM notification_rules WHERE id = $1 AND user_id = $2")
+        .bind(rule_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
 
-    updates.push(format!("updated_at = now()"));
+    Ok(result.rows_affected() > 0)
+}
 
-    let query = format!(
+/// Dirección para notificaciones: la de `personal_data` si existe, si no la
+/// de la cuenta.
+pub async fn get_notification_email(pool: &PgPool, user_id: i32) -> Result<Option<String>, sqlx::Error> {
+    let email: Option<Option<String>> = sqlx::query_scalar(
         r#"
-        UPDATE notification_preferences
-        SET {}
-        WHERE user_id = $1
-        RETURNING user_id, email_enabled, telegram_enabled, whatsapp_enabled,
-                 price_alerts_enabled, system_alerts_enabled, created_at, updated_at
-        "#,
-        updates.join(", ")
-    );
+        SELECT COALESCE(NULLIF(TRIM(pd.email), ''), NULLIF(TRIM(u.email), ''))
+        FROM users u
+        LEFT JOIN personal_data pd ON pd.user_id = u.id
+        WHERE u.id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
 
-    sqlx::query_as::<_, NotificationPreference>(&query)
-        .bind(user_id)
-        .fetch_one(pool)
-        .await
+    Ok(email.flatten())
+}
+
+/// Desactiva las notificaciones por email, creando las preferencias si el
+/// usuario aún no las tiene.
+pub async fn disable_email_notifications(pool: &PgPool, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO notification_preferences (user_id, email_enabled)
+        VALUES ($1, false)
+        ON CONFLICT (user_id)
+        DO UPDATE SET email_enabled = false, updated_at = now()
+        "#
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
 }
 
 pub async fn create_webhook(
@@ -102,22 +238,27 @@ pub async fn create_webhook(
         }
     };
 
+    let secret = request.secret.clone().unwrap_or_else(generate_webhook_secret);
+
     debug!("Ejecutando query de inserción");
     let result = sqlx::query_as::<_, WebhookConfig>(
         r#"
         INSERT INTO webhooks (
-            id, user_id, url, secret, notification_types, enabled,
+            id, user_id, kind, url, secret, notification_types, enabled, api_version,
             created_at, updated_at
         )
-        VALUES ($1, $2, $3, $4, $5, true, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
-        RETURNING id, user_id, url, secret, notification_types, enabled, created_at, updated_at
+        VALUES ($1, $2, $3, $4, $5, $6, true, $7, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+        RETURNING id, user_id, kind, url, secret, notification_types, enabled,
+                  previous_secret, previous_secret_expires_at, api_version, created_at, updated_at
         "#
     )
     .bind(webhook_id)
     .bind(user_id)
+    .bind(request.kind.as_str())
     .bind(&request.url)
-    .bind(&request.secret)
+    .bind(secret)
     .bind(notification_types)
+    .bind(request.api_version.unwrap_or(events::LATEST_VERSION))
     .fetch_one(pool)
     .await;
 
@@ -132,8 +273,8 @@ pub async fn create_webhook(
 pub async fn list_webhooks(pool: &PgPool, user_id: i32) -> Result<Vec<WebhookConfig>, sqlx::Error> {
     sqlx::query_as::<_, WebhookConfig>(
         r#"
-        SELECT id, user_id, url, secret, notification_types as "notification_types",
-               enabled, created_at, updated_at
+        SELECT id, user_id, kind, url, secret, notification_types as "notification_types",
+               enabled, previous_secret, previous_secret_expires_at, api_version, created_at, updated_at
         FROM webhooks
         WHERE user_id = $1
         ORDER BY created_at DESC
@@ -144,6 +285,74 @@ pub async fn list_webhooks(pool: &PgPool, user_id: i32) -> Result<Vec<WebhookCon
     .await
 }
 
+/// Actualiza los campos presentes en `request`. Al rehabilitar un webhook se
+/// reinicia su contador de fallos consecutivos.
+pub async fn update_webhook(
+    pool: &PgPool,
+    user_id: i32,
+    webhook_id: Uuid,
+    request: &UpdateWebhookRequest,
+) -> Result<Option<WebhookConfig>, sqlx::Error> {
+    let notification_types = request
+        .notification_types
+        .as_ref()
+        .map(serde_json::to_value)
+        .transpose()
+        .map_err(|e| sqlx::Error::Protocol(format!("Error al serializar notification_types: {}", e)))?;
+
+    sqlx::query_as::<_, WebhookConfig>(
+        r#"
+        UPDATE webhooks
+        SET url = COALESCE($3, url),
+            notification_types = COALESCE($4, notification_types),
+            enabled = COALESCE($5, enabled),
+            api_version = COALESCE($6, api_version),
+            consecutive_failures = CASE WHEN $5 IS TRUE THEN 0 ELSE consecutive_failures END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, kind, url, secret, notification_types, enabled,
+                  previous_secret, previous_secret_expires_at, api_version, created_at, updated_at
+        "#
+    )
+    .bind(webhook_id)
+    .bind(user_id)
+    .bind(&request.url)
+    .bind(notification_types)
+    .bind(request.enabled)
+    .bind(request.api_version)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Reemplaza el secreto del webhook conservando el actual como
+/// `previous_secret` hasta `previous_expires_at`.
+pub async fn rotate_webhook_secret(
+    pool: &PgPool,
+    user_id: i32,
+    webhook_id: Uuid,
+    new_secret: &str,
+    previous_expires_at: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<WebhookConfig>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookConfig>(
+        r#"
+        UPDATE webhooks
+        SET previous_secret = secret,
+            previous_secret_expires_at = $4,
+            secret = $3,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, kind, url, secret, notification_types, enabled,
+                  previous_secret, previous_secret_expires_at, api_version, created_at, updated_at
+        "#
+    )
+    .bind(webhook_id)
+    .bind(user_id)
+    .bind(new_secret)
+    .bind(previous_expires_at)
+    .fetch_optional(pool)
+    .await
+}
+
 pub async fn delete_webhook(pool: &PgPool, user_id: i32, webhook_id: Uuid) -> Result<(), sqlx::Error> {
     let result = sqlx::query(
         r#"
@@ -162,3 +371,248 @@ pub async fn delete_webhook(pool: &PgPool, user_id: i32, webhook_id: Uuid) -> Re
         Ok(())
     }
 }
+
+/// Guarda la notificación en el historial asignándole el siguiente
+/// `event_id` del usuario. Si la entrada `queue_id` de la cola ya se
+/// guardó en un intento anterior se devuelve esa.
+pub async fn store_notification(
+    pool: &PgPool,
+    notification: &Notification,
+    queue_id: &str,
+) -> Result<DbNotification, sqlx::Error> {
+    let existing = sqlx::query_as::<_, DbNotification>(
+        r#"
+        SELECT id, user_id, event_id, notification_type, priority, title, message,
+               metadata, created_at, read_at
+        FROM notifications
+        WHERE user_id = $1 AND queue_id = $2
+        "#
+    )
+    .bind(notification.user_id)
+    .bind(queue_id)
+    .fetch_optional(pool)
+    .await?;
+    if let Some(existing) = existing {
+        return Ok(existing);
+    }
+
+    let mut tx = pool.begin().await?;
+
+    // El UPDATE bloquea la fila del usuario, así dos notificaciones
+    // simultáneas no pueden recibir el mismo event_id.
+    let (event_id,): (i64,) = sqlx::query_as(
+        r#"
+        UPDATE users
+        SET last_event_id = last_event_id + 1
+        WHERE id = $1
+        RETURNING last_event_id
+        "#
+    )
+    .bind(notification.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let stored = sqlx::query_as::<_, DbNotification>(
+        r#"
+        INSERT INTO notifications (
+            user_id, event_id, notification_type, title, message, metadata, created_at, priority,
+            queue_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id, user_id, event_id, notification_type, priority, title, message,
+                  metadata, created_at, read_at
+        "#
+    )
+    .bind(notification.user_id)
+    .bind(event_id)
+    .bind(notification.notification_type.as_str())
+    .bind(&notification.title)
+    .bind(&notification.message)
+    .bind(&notification.metadata)
+    .bind(notification.created_at)
+    .bind(notification.priority.as_str())
+    .bind(queue_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(stored)
+}
+
+/// Notificaciones del usuario posteriores a `after_event_id`, en orden.
+pub async fn list_notifications_after(
+    pool: &PgPool,
+    user_id: i32,
+    after_event_id: i64,
+    limit: i64,
+) -> Result<Vec<DbNotification>, sqlx::Error> {
+    sqlx::query_as::<_, DbNotification>(
+        r#"
+        SELECT id, user_id, event_id, notification_type, priority, title, message,
+               metadata, created_at, read_at
+        FROM notifications
+        WHERE user_id = $1 AND event_id > $2
+        ORDER BY event_id ASC
+        LIMIT $3
+        "#
+    )
+    .bind(user_id)
+    .bind(after_event_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Marca como leídas las notificaciones indicadas. Devuelve cuántas cambiaron.
+pub async fn mark_notifications_read(
+    pool: &PgPool,
+    user_id: i32,
+    event_ids: &[i64],
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE notifications
+        SET read_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND event_id = ANY($2) AND read_at IS NULL
+        "#
+    )
+    .bind(user_id)
+    .bind(event_ids)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Bandeja del usuario, de la más reciente a la más antigua.
+pub async fn list_notifications(
+    pool: &PgPool,
+    user_id: i32,
+    query: &NotificationListQuery,
+    limit: i64,
+) -> Result<Vec<DbNotification>, sqlx::Error> {
+    sqlx::query_as::<_, DbNotification>(
+        r#"
+        SELECT id, user_id, event_id, notification_type, priority, title, message,
+               metadata, created_at, read_at
+        FROM notifications
+        WHERE user_id = $1
+          AND ($2::TEXT IS NULL OR notification_type = $2)
+          AND ($3::BOOLEAN IS NULL OR (read_at IS NULL) = $3)
+          AND ($4::TIMESTAMPTZ IS NULL OR created_at >= $4)
+          AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+          AND ($6::BIGINT IS NULL OR event_id < $6)
+        ORDER BY event_id DESC
+        LIMIT $7
+        "#
+    )
+    .bind(user_id)
+    .bind(&query.notification_type)
+    .bind(query.unread)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(query.cursor)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn count_unread_notifications(pool: &PgPool, user_id: i32) -> Result<i64, sqlx::Error> {
+    let (count,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*)
+        FROM notifications
+        WHERE user_id = $1 AND read_at IS NULL
+        "#
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
+
+pub async fn mark_all_notifications_read(pool: &PgPool, user_id: i32) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE notifications
+        SET read_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND read_at IS NULL
+        "#
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn delete_notification(pool: &PgPool, user_id: i32, event_id: i64) -> Result<(), sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM notifications
+        WHERE user_id = $1 AND event_id = $2
+        "#
+    )
+    .bind(user_id)
+    .bind(event_id)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        Err(sqlx::Error::RowNotFound)
+    } else {
+        Ok(())
+    }
+}
+
+/// Registra (o actualiza, en los reintentos) el estado de entrega de una
+/// entrada de la cola por un canal.
+pub async fn record_delivery(
+    pool: &PgPool,
+    queue_id: &str,
+    user_id: i32,
+    channel: &str,
+    status: DeliveryStatus,
+    attempts: i32,
+    error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO notification_deliveries (queue_id, user_id, channel, status, attempts, error)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (queue_id, channel) DO UPDATE
+        SET status = EXCLUDED.status,
+            attempts = EXCLUDED.attempts,
+            error = EXCLUDED.error,
+            updated_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(queue_id)
+    .bind(user_id)
+    .bind(channel)
+    .bind(status.as_str())
+    .bind(attempts)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Canales por los que la entrada ya se entregó (o quedó retenida u
+/// omitida por límite de envíos), para no repetirlos al reintentar.
+pub async fn delivered_channels(pool: &PgPool, queue_id: &str) -> Result<Vec<String>, sqlx::Error> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        r#"
+        SELECT channel
+        FROM notification_deliveries
+        WHERE queue_id = $1 AND status IN ('delivered', 'held', 'rate_limited')
+        "#
+    )
+    .bind(queue_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|(channel,)| channel).collect())
+}
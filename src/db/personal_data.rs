@@ -42,7 +42,8 @@ pub async fn get_personal_data(
     sqlx::query_as!(
         PersonalData,
         r#"
-        SELECT * FROM personal_data 
+        SELECT id, user_id, email, created_at, updated_at
+        FROM personal_data
         WHERE user_id = $1
         "#,
         user_id
@@ -51,7 +51,7 @@ pub async fn list_price_alerts(
 pub async fn get_price_alert(
     pool: &PgPool,
     id: i32,
-) -> Result<PriceAlert, sqlx::Error> {
+) -> Result<Option<PriceAlert>, sqlx::Error> {
     sqlx::query_as!(
         PriceAlert,
         r#"
@@ -61,7 +61,7 @@ pub async fn get_price_alert(
         "#,
         id
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await
 }
 
@@ -70,7 +70,7 @@ pub async fn update_price_alert(
     id: i32,
     user_id: i32,
     req: &CreatePriceAlertRequest,
-) -> Result<PriceAlert, sqlx::Error> {
+) -> Result<Option<PriceAlert>, sqlx::Error> {
     sqlx::query_as!(
         PriceAlert,
         r#"
@@ -90,7 +90,7 @@ pub async fn update_price_alert(
         id,
         user_id
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await
 }
 
@@ -117,11 +117,34 @@ pub async fn get_all_price_alerts_admin(pool: &PgPool) -> Result<Vec<PriceAlert>
     sqlx::query_as!(
         PriceAlert,
         r#"
-        SELECT id, user_id, asset, target_price as "target_price: _", condition, trigger_price as "trigger_price: _", created_at, updated_at
+        SELECT id, user_id, asset, target_price as "target_price: _", condition, trigger_price as "trigger_price: _", snoozed_until, created_at, updated_at
         FROM price_alerts
         ORDER BY id
         "#
     )
     .fetch_all(pool)
     .await
-}
\ No newline at end of file
+}
+
+/// Pospone la alerta hasta `until`: no se evalúa mientras tanto.
+pub async fn snooze_price_alert(
+    pool: &PgPool,
+    id: i32,
+    user_id: i32,
+    until: chrono::DateTime<chrono::Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE price_alerts
+        SET snoozed_until = $1, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2 AND user_id = $3
+        "#
+    )
+    .bind(until)
+    .bind(id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
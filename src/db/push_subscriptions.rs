@@ -0,0 +1,66 @@
+use sqlx::PgPool;
+
+use crate::models::notifications::{CreatePushSubscriptionRequest, PushSubscription};
+
+/// Registra la suscripción. Si el endpoint ya existía (el navegador volvió a
+/// suscribirse) se actualizan sus claves y pasa a ser del usuario actual.
+pub async fn upsert_subscription(
+    pool: &PgPool,
+    user_id: i32,
+    request: &CreatePushSubscriptionRequest,
+    user_agent: Option<&str>,
+) -> Result<PushSubscription, sqlx::Error> {
+    sqlx::query_as::<_, PushSubscription>(
+        r#"
+        INSERT INTO push_subscriptions (user_id, endpoint, p256dh, auth, user_agent)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (endpoint) DO UPDATE
+        SET user_id = EXCLUDED.user_id,
+            p256dh = EXCLUDED.p256dh,
+            auth = EXCLUDED.auth,
+            user_agent = EXCLUDED.user_agent
+        RETURNING id, user_id, endpoint, p256dh, auth, user_agent, created_at
+        "#
+    )
+    .bind(user_id)
+    .bind(&request.endpoint)
+    .bind(&request.keys.p256dh)
+    .bind(&request.keys.auth)
+    .bind(user_agent)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn list_subscriptions(pool: &PgPool, user_id: i32) -> Result<Vec<PushSubscription>, sqlx::Error> {
+    sqlx::query_as::<_, PushSubscription>(
+        r#"
+        SELECT id, user_id, endpoint, p256dh, auth, user_agent, created_at
+        FROM push_subscriptions
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_subscription(pool: &PgPool, user_id: i32, subscription_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM push_subscriptions WHERE id = $1 AND user_id = $2")
+        .bind(subscription_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Para las suscripciones que el servicio de push dio por expiradas.
+pub async fn delete_by_id(pool: &PgPool, subscription_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM push_subscriptions WHERE id = $1")
+        .bind(subscription_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+/// Registra la clave de deduplicación de la entrada de la cola. Devuelve
+/// `None` si otra entrada la registró dentro de la ventana; la misma
+/// entrada (un reintento) siempre pasa. Al abrir una ventana nueva devuelve
+/// cuántas repeticiones se descartaron en la anterior, para avisarlas.
+pub async fn claim_dedup_key(
+    pool: &PgPool,
+    user_id: i32,
+    dedup_key: &str,
+    queue_id: &str,
+    window: Duration,
+) -> Result<Option<i32>, sqlx::Error> {
+    let claimed: Option<(i32,)> = sqlx::query_as(
+        r#"
+        WITH previous AS (
+            SELECT suppressed
+            FROM notification_dedup
+            WHERE user_id = $1 AND dedup_key = $2 AND expires_at <= CURRENT_TIMESTAMP
+        )
+        INSERT INTO notification_dedup (user_id, dedup_key, queue_id, expires_at)
+        VALUES ($1, $2, $3, CURRENT_TIMESTAMP + $4 * INTERVAL '1 second')
+        ON CONFLICT (user_id, dedup_key) DO UPDATE
+        SET queue_id = EXCLUDED.queue_id,
+            expires_at = EXCLUDED.expires_at,
+            suppressed = CASE WHEN notification_dedup.expires_at <= CURRENT_TIMESTAMP
+                              THEN 0 ELSE notification_dedup.suppressed END
+        WHERE notification_dedup.expires_at <= CURRENT_TIMESTAMP
+           OR notification_dedup.queue_id = EXCLUDED.queue_id
+        RETURNING COALESCE((SELECT suppressed FROM previous), 0)
+        "#
+    )
+    .bind(user_id)
+    .bind(dedup_key)
+    .bind(queue_id)
+    .bind(window.as_secs_f64())
+    .fetch_optional(pool)
+    .await?;
+
+    if claimed.is_none() {
+        sqlx::query("UPDATE notification_dedup SET suppressed = suppressed + 1 WHERE user_id = $1 AND dedup_key = $2")
+            .bind(user_id)
+            .bind(dedup_key)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(claimed.map(|(repeats,)| repeats))
+}
+
+/// Cuenta un envío por el canal en la ventana fija actual. Si ya se llegó
+/// al límite lo cuenta como omitido y devuelve `false`. Al empezar una
+/// ventana nueva los omitidos de la anterior pasan a `overflow`, que es lo
+/// que se resume después.
+pub async fn try_acquire(
+    pool: &PgPool,
+    user_id: i32,
+    channel: &str,
+    limit: u32,
+    window: Duration,
+) -> Result<bool, sqlx::Error> {
+    let (allowed,): (bool,) = sqlx::query_as(
+        r#"
+        INSERT INTO notification_rate_limits AS r (user_id, channel, window_start, sent, allowed)
+        VALUES ($1, $2, CURRENT_TIMESTAMP, 1, true)
+        ON CONFLICT (user_id, channel) DO UPDATE
+        SET overflow = CASE WHEN r.window_start + $4 * INTERVAL '1 second' <= CURRENT_TIMESTAMP
+                            THEN r.overflow + r.suppressed ELSE r.overflow END,
+            suppressed = CASE WHEN r.window_start + $4 * INTERVAL '1 second' <= CURRENT_TIMESTAMP THEN 0
+                              WHEN r.sent >= $3 THEN r.suppressed + 1
+                              ELSE r.suppressed END,
+            sent = CASE WHEN r.window_start + $4 * INTERVAL '1 second' <= CURRENT_TIMESTAMP THEN 1
+                        WHEN r.sent >= $3 THEN r.sent
+                        ELSE r.sent + 1 END,
+            allowed = r.window_start + $4 * INTERVAL '1 second' <= CURRENT_TIMESTAMP OR r.sent < $3,
+            window_start = CASE WHEN r.window_start + $4 * INTERVAL '1 second' <= CURRENT_TIMESTAMP
+                                THEN CURRENT_TIMESTAMP ELSE r.window_start END
+        RETURNING allowed
+        "#
+    )
+    .bind(user_id)
+    .bind(channel)
+    .bind(limit as i32)
+    .bind(window.as_secs_f64())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(allowed)
+}
+
+/// Notificaciones omitidas por límite de envío que hay que resumir.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Overflow {
+    pub user_id: i32,
+    pub channel: String,
+    pub count: i32,
+}
+
+/// Toma los omitidos de las ventanas ya cerradas (hasta `limit` pares
+/// usuario/canal) y los pone a cero.
+pub async fn take_overflow(pool: &PgPool, window: Duration, limit: i64) -> Result<Vec<Overflow>, sqlx::Error> {
+    sqlx::query_as::<_, Overflow>(
+        r#"
+        WITH due AS (
+            SELECT user_id, channel,
+                   window_start + $1 * INTERVAL '1 second' <= CURRENT_TIMESTAMP AS closed,
+                   overflow + CASE WHEN window_start + $1 * INTERVAL '1 second' <= CURRENT_TIMESTAMP
+                                   THEN suppressed ELSE 0 END AS count
+            FROM notification_rate_limits
+            WHERE overflow > 0
+               OR (suppressed > 0 AND window_start + $1 * INTERVAL '1 second' <= CURRENT_TIMESTAMP)
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE notification_rate_limits r
+        SET overflow = 0,
+            suppressed = CASE WHEN due.closed THEN 0 ELSE r.suppressed END
+        FROM due
+        WHERE r.user_id = due.user_id AND r.channel = due.channel
+        RETURNING r.user_id, r.channel, due.count
+        "#
+    )
+    .bind(window.as_secs_f64())
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
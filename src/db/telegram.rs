@@ -0,0 +1,129 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Guarda un código de vinculación nuevo, descartando los anteriores del
+/// usuario.
+pub async fn create_link_code(
+    pool: &PgPool,
+    user_id: i32,
+    code: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM telegram_link_codes WHERE user_id = $1 OR expires_at <= CURRENT_TIMESTAMP")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO telegram_link_codes (code, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        "#
+    )
+    .bind(code)
+    .bind(user_id)
+    .bind(expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Consume el código y vincula el chat al usuario que lo generó. Si el chat
+/// estaba vinculado a otra cuenta, se desvincula de ella. Devuelve el
+/// usuario o `None` si el código no existe o expiró.
+pub async fn consume_link_code(pool: &PgPool, code: &str, telegram_id: &str) -> Result<Option<i32>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let user_id: Option<i32> = sqlx::query_scalar(
+        r#"
+        DELETE FROM telegram_link_codes
+        WHERE code = $1 AND expires_at > CURRENT_TIMESTAMP
+        RETURNING user_id
+        "#
+    )
+    .bind(code)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+
+    sqlx::query("UPDATE users SET telegram_id = NULL WHERE telegram_id = $1 AND id <> $2")
+        .bind(telegram_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE users SET telegram_id = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2")
+        .bind(telegram_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Some(user_id))
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TelegramLink {
+    pub telegram_id: String,
+    pub muted_until: Option<DateTime<Utc>>,
+}
+
+impl TelegramLink {
+    pub fn is_muted(&self) -> bool {
+        self.muted_until.map_or(false, |until| until > Utc::now())
+    }
+}
+
+/// Chat vinculado al usuario, si lo tiene.
+pub async fn get_telegram_link(pool: &PgPool, user_id: i32) -> Result<Option<TelegramLink>, sqlx::Error> {
+    sqlx::query_as::<_, TelegramLink>(
+        r#"
+        SELECT telegram_id, telegram_muted_until AS muted_until
+        FROM users
+        WHERE id = $1 AND telegram_id IS NOT NULL
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Silencia las notificaciones por Telegram hasta `until`; `None` las
+/// reactiva.
+pub async fn set_muted_until(pool: &PgPool, user_id: i32, until: Option<DateTime<Utc>>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET telegram_muted_until = $1 WHERE id = $2")
+        .bind(until)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn find_user_by_telegram_id(pool: &PgPool, telegram_id: &str) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM users WHERE telegram_id = $1 AND COALESCE(is_active, true)")
+        .bind(telegram_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn unlink_telegram(pool: &PgPool, user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET telegram_id = NULL, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND telegram_id IS NOT NULL
+        "#
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -1,4 +1,4 @@
-use crate::models::users::CreateUserRequest;
+use crate::{i18n::Locale, models::users::CreateUserRequest};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -91,4 +91,81 @@ pub async fn authenticate_user(
     } else {
         Err(sqlx::Error::RowNotFound)
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct UserAuthStatus {
+    pub is_active: bool,
+    pub tokens_revoked_at: Option<DateTime<Utc>>,
+}
+
+impl UserAuthStatus {
+    /// Un token queda revocado si se emitió antes de la última revocación.
+    pub fn is_token_revoked(&self, issued_at: i64) -> bool {
+        match self.tokens_revoked_at {
+            Some(revoked_at) => issued_at < revoked_at.timestamp(),
+            None => false,
+        }
+    }
+}
+
+pub async fn get_auth_status(pool: &PgPool, user_id: i32) -> Result<Option<UserAuthStatus>, sqlx::Error> {
+    sqlx::query_as::<_, UserAuthStatus>(
+        r#"
+        SELECT COALESCE(is_active, true) AS is_active, tokens_revoked_at
+        FROM users
+        WHERE id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Desactiva al usuario y revoca todos los tokens emitidos hasta ahora.
+pub async fn deactivate_user(pool: &PgPool, user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET is_active = false,
+            tokens_revoked_at = CURRENT_TIMESTAMP,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        "#
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Idioma elegido por el usuario; `None` si no eligió ninguno.
+pub async fn get_user_locale(pool: &PgPool, user_id: i32) -> Result<Option<Locale>, sqlx::Error> {
+    let locale: Option<(Option<String>,)> = sqlx::query_as("SELECT locale FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(locale
+        .and_then(|(locale,)| locale)
+        .and_then(|locale| Locale::from_tag(&locale)))
+}
+
+/// `None` vuelve a usar el `Accept-Language` de cada petición.
+pub async fn set_user_locale(pool: &PgPool, user_id: i32, locale: Option<Locale>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET locale = $2,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        "#
+    )
+    .bind(user_id)
+    .bind(locale.map(|locale| locale.as_str()))
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
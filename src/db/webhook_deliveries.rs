@@ -0,0 +1,219 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::notifications::{WebhookConfig, WebhookDelivery};
+
+/// Resultado de un intento, tal como se guarda en `webhook_deliveries`.
+#[derive(Debug, Clone)]
+pub struct DeliveryAttempt {
+    pub delivery_id: Uuid,
+    pub webhook_id: Uuid,
+    pub user_id: i32,
+    pub attempt: i32,
+    pub status: &'static str,
+    pub status_code: Option<i32>,
+    pub latency_ms: Option<i32>,
+    pub response_snippet: Option<String>,
+    pub error: Option<String>,
+    pub payload: String,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+const DELIVERY_COLUMNS: &str = r#"
+    id, delivery_id, webhook_id, user_id, attempt, status, status_code, latency_ms,
+    response_snippet, error, payload, next_attempt_at, created_at
+"#;
+
+pub async fn insert_attempt(pool: &PgPool, attempt: &DeliveryAttempt) -> Result<WebhookDelivery, sqlx::Error> {
+    let query = format!(
+        r#"
+        INSERT INTO webhook_deliveries (
+            id, delivery_id, webhook_id, user_id, attempt, status, status_code,
+            latency_ms, response_snippet, error, payload, next_attempt_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING {}
+        "#,
+        DELIVERY_COLUMNS
+    );
+
+    sqlx::query_as::<_, WebhookDelivery>(&query)
+        .bind(Uuid::new_v4())
+        .bind(attempt.delivery_id)
+        .bind(attempt.webhook_id)
+        .bind(attempt.user_id)
+        .bind(attempt.attempt)
+        .bind(attempt.status)
+        .bind(attempt.status_code)
+        .bind(attempt.latency_ms)
+        .bind(&attempt.response_snippet)
+        .bind(&attempt.error)
+        .bind(&attempt.payload)
+        .bind(attempt.next_attempt_at)
+        .fetch_one(pool)
+        .await
+}
+
+/// Toma los reintentos vencidos y los marca como `retried`, así dos workers
+/// no procesan el mismo.
+pub async fn claim_due_retries(pool: &PgPool, limit: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    let query = format!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'retried'
+        WHERE id IN (
+            SELECT id
+            FROM webhook_deliveries
+            WHERE status = 'retry_scheduled' AND next_attempt_at <= CURRENT_TIMESTAMP
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {}
+        "#,
+        DELIVERY_COLUMNS
+    );
+
+    sqlx::query_as::<_, WebhookDelivery>(&query)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+/// Devuelve a la espera un reintento tomado con `claim_due_retries` que no
+/// se pudo procesar, para que lo tome la próxima pasada.
+pub async fn release_retry(pool: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'retry_scheduled'
+        WHERE id = $1 AND status = 'retried'
+        "#
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_deliveries(
+    pool: &PgPool,
+    user_id: i32,
+    webhook_id: Uuid,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT {}
+        FROM webhook_deliveries
+        WHERE user_id = $1 AND webhook_id = $2
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+        DELIVERY_COLUMNS
+    );
+
+    sqlx::query_as::<_, WebhookDelivery>(&query)
+        .bind(user_id)
+        .bind(webhook_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+}
+
+/// Último intento de una entrega, del que se toma el payload al reenviar.
+pub async fn latest_attempt(
+    pool: &PgPool,
+    user_id: i32,
+    webhook_id: Uuid,
+    delivery_id: Uuid,
+) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT {}
+        FROM webhook_deliveries
+        WHERE user_id = $1 AND webhook_id = $2 AND delivery_id = $3
+        ORDER BY attempt DESC
+        LIMIT 1
+        "#,
+        DELIVERY_COLUMNS
+    );
+
+    sqlx::query_as::<_, WebhookDelivery>(&query)
+        .bind(user_id)
+        .bind(webhook_id)
+        .bind(delivery_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Primer intento de una entrega; si existe, el evento ya se le envió a
+/// ese webhook y no hay que repetirlo.
+pub async fn first_attempt(pool: &PgPool, delivery_id: Uuid) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT {}
+        FROM webhook_deliveries
+        WHERE delivery_id = $1 AND attempt = 1
+        LIMIT 1
+        "#,
+        DELIVERY_COLUMNS
+    );
+
+    sqlx::query_as::<_, WebhookDelivery>(&query)
+        .bind(delivery_id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn get_webhook(pool: &PgPool, webhook_id: Uuid) -> Result<Option<WebhookConfig>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookConfig>(
+        r#"
+        SELECT id, user_id, kind, url, secret, notification_types, enabled,
+               previous_secret, previous_secret_expires_at, api_version, created_at, updated_at
+        FROM webhooks
+        WHERE id = $1
+        "#
+    )
+    .bind(webhook_id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn reset_failures(pool: &PgPool, webhook_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE webhooks
+        SET consecutive_failures = 0
+        WHERE id = $1 AND consecutive_failures <> 0
+        "#
+    )
+    .bind(webhook_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Suma un fallo consecutivo y deshabilita el webhook al llegar a
+/// `disable_after`. Devuelve `true` si este fallo lo deshabilitó.
+pub async fn record_failure(pool: &PgPool, webhook_id: Uuid, disable_after: i32) -> Result<bool, sqlx::Error> {
+    let row: Option<(bool,)> = sqlx::query_as(
+        r#"
+        UPDATE webhooks
+        SET consecutive_failures = consecutive_failures + 1,
+            enabled = CASE WHEN consecutive_failures + 1 >= $2 THEN false ELSE enabled END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        RETURNING (enabled = false AND consecutive_failures = $2)
+        "#
+    )
+    .bind(webhook_id)
+    .bind(disable_after)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|(disabled,)| disabled).unwrap_or(false))
+}
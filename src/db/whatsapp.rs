@@ -0,0 +1,288 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PhoneVerification {
+    pub phone_number: String,
+    pub code_hash: String,
+    pub attempts: i32,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl PhoneVerification {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+/// Guarda el código pendiente del usuario, reemplazando el anterior. Los
+/// intentos fallidos se conservan si el número es el mismo y el código
+/// anterior sigue vigente: reenviar no da intentos nuevos.
+pub async fn create_phone_verification(
+    pool: &PgPool,
+    user_id: i32,
+    phone_number: &str,
+    code_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO phone_verifications (user_id, phone_number, code_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id) DO UPDATE
+        SET phone_number = EXCLUDED.phone_number,
+            code_hash = EXCLUDED.code_hash,
+            attempts = CASE
+                WHEN phone_verifications.phone_number = EXCLUDED.phone_number
+                    AND phone_verifications.expires_at > CURRENT_TIMESTAMP
+                THEN phone_verifications.attempts
+                ELSE 0
+            END,
+            expires_at = EXCLUDED.expires_at,
+            created_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(user_id)
+    .bind(phone_number)
+    .bind(code_hash)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Códigos enviados en las últimas 24 horas al usuario y al número, en ese
+/// orden.
+pub async fn count_recent_verification_sends(
+    pool: &PgPool,
+    user_id: i32,
+    phone_number: &str,
+) -> Result<(i64, i64), sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE user_id = $1),
+               COUNT(*) FILTER (WHERE phone_number = $2)
+        FROM phone_verification_sends
+        WHERE sent_at > CURRENT_TIMESTAMP - INTERVAL '24 hours'
+        "#
+    )
+    .bind(user_id)
+    .bind(phone_number)
+    .fetch_one(pool)
+    .await
+}
+
+/// Registra un envío de código y descarta los que ya no cuentan para el
+/// límite diario.
+pub async fn record_verification_send(pool: &PgPool, user_id: i32, phone_number: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM phone_verification_sends WHERE sent_at <= CURRENT_TIMESTAMP - INTERVAL '24 hours'")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("INSERT INTO phone_verification_sends (user_id, phone_number) VALUES ($1, $2)")
+        .bind(user_id)
+        .bind(phone_number)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_phone_verification(pool: &PgPool, user_id: i32) -> Result<Option<PhoneVerification>, sqlx::Error> {
+    sqlx::query_as::<_, PhoneVerification>(
+        r#"
+        SELECT phone_number, code_hash, attempts, expires_at, created_at
+        FROM phone_verifications
+        WHERE user_id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Consume un intento y devuelve el hash del código a comparar. `None` si
+/// ya se agotaron los `max_attempts` (o no hay código pendiente).
+pub async fn consume_verification_attempt(
+    pool: &PgPool,
+    user_id: i32,
+    max_attempts: i32,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        UPDATE phone_verifications
+        SET attempts = attempts + 1
+        WHERE user_id = $1 AND attempts < $2
+        RETURNING code_hash
+        "#
+    )
+    .bind(user_id)
+    .bind(max_attempts)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Marca el número como verificado y descarta el código. Si otro usuario
+/// tenía ese número verificado, lo pierde.
+pub async fn confirm_phone_number(pool: &PgPool, user_id: i32, phone_number: &str) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM phone_verifications WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE personal_data
+        SET phone_number = NULL, phone_verified_at = NULL, whatsapp_last_inbound_at = NULL
+        WHERE phone_number = $1 AND user_id <> $2
+        "#
+    )
+    .bind(phone_number)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // personal_data puede no existir todavía: se crea con el email de la cuenta
+    sqlx::query(
+        r#"
+        INSERT INTO personal_data (user_id, email, phone_number, phone_verified_at)
+        SELECT id, email, $2, CURRENT_TIMESTAMP FROM users WHERE id = $1
+        ON CONFLICT (user_id) DO UPDATE
+        SET phone_number = EXCLUDED.phone_number,
+            phone_verified_at = EXCLUDED.phone_verified_at,
+            whatsapp_last_inbound_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(user_id)
+    .bind(phone_number)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn remove_phone_number(pool: &PgPool, user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE personal_data
+        SET phone_number = NULL, phone_verified_at = NULL, whatsapp_last_inbound_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND phone_number IS NOT NULL
+        "#
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WhatsAppRecipient {
+    pub phone_number: String,
+    /// Último mensaje del usuario: abre la ventana de sesión de 24 horas.
+    pub last_inbound_at: Option<DateTime<Utc>>,
+}
+
+/// Número verificado del usuario, si lo tiene.
+pub async fn get_recipient(pool: &PgPool, user_id: i32) -> Result<Option<WhatsAppRecipient>, sqlx::Error> {
+    sqlx::query_as::<_, WhatsAppRecipient>(
+        r#"
+        SELECT phone_number, whatsapp_last_inbound_at AS last_inbound_at
+        FROM personal_data
+        WHERE user_id = $1 AND phone_number IS NOT NULL AND phone_verified_at IS NOT NULL
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn record_inbound_message(pool: &PgPool, phone_number: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE personal_data
+        SET whatsapp_last_inbound_at = CURRENT_TIMESTAMP
+        WHERE phone_number = $1 AND phone_verified_at IS NOT NULL
+        "#
+    )
+    .bind(phone_number)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessagePurpose {
+    Notification,
+    Verification,
+}
+
+impl MessagePurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessagePurpose::Notification => "notification",
+            MessagePurpose::Verification => "verification",
+        }
+    }
+}
+
+/// Registra un mensaje aceptado por la API para asociarle después los
+/// cambios de estado. `event_id` identifica la notificación en el historial.
+pub async fn record_message(
+    pool: &PgPool,
+    message_id: &str,
+    user_id: i32,
+    event_id: Option<i64>,
+    purpose: MessagePurpose,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO whatsapp_messages (message_id, user_id, event_id, purpose)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (message_id) DO NOTHING
+        "#
+    )
+    .bind(message_id)
+    .bind(user_id)
+    .bind(event_id)
+    .bind(purpose.as_str())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Actualiza el estado de un mensaje. Los webhooks pueden llegar
+/// desordenados, así que un estado sólo reemplaza a uno anterior en
+/// `accepted → sent → delivered → read`; `failed` siempre se aplica.
+/// Devuelve `false` si el mensaje no existe o el estado quedó obsoleto.
+pub async fn update_message_status(
+    pool: &PgPool,
+    message_id: &str,
+    status: &str,
+    error: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE whatsapp_messages
+        SET status = $2, error = COALESCE($3, error), updated_at = CURRENT_TIMESTAMP
+        WHERE message_id = $1
+          AND array_position(ARRAY['accepted', 'sent', 'delivered', 'read', 'failed'], status)
+            < array_position(ARRAY['accepted', 'sent', 'delivered', 'read', 'failed'], $2::text)
+        "#
+    )
+    .bind(message_id)
+    .bind(status)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -1,63 +1,119 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
+use serde::Deserialize;
 use serde_json::json;
+use tracing::error;
 
 use crate::{
     db::{
-        users::get_all_users,
+        users::{self, get_all_users},
         asset_pairs::get_all_asset_pairs_admin,
-        price_alerts::list_all_price_alerts,
+        price_alerts::get_all_price_alerts_admin,
     },
     endpoints::AppState,
+    i18n::Locale,
+    notifications::bus::publish_disconnect,
 };
 
 pub async fn get_users(
     State(state): State<AppState>,
+    locale: Locale,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     match get_all_users(&state.pool).await {
         Ok(users) => Ok(Json(json!({
             "status": "success",
-            "message": "Users retrieved successfully",
+            "message": locale.t("users.listed"),
             "data": users
         }))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error retrieving users: {}", e),
+            locale.t_with("users.list_error", &[("error", &e)]),
         )),
     }
 }
 
 pub async fn get_all_asset_pairs(
     State(state): State<AppState>,
+    locale: Locale,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     match get_all_asset_pairs_admin(&state.pool).await {
         Ok(asset_pairs) => Ok(Json(json!({
             "status": "success",
-            "message": "Asset pairs retrieved successfully",
+            "message": locale.t("asset_pairs.listed"),
             "data": asset_pairs
         }))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error retrieving asset pairs: {}", e),
+            locale.t_with("asset_pairs.list_error", &[("error", &e)]),
         )),
     }
 }
 
 pub async fn get_all_alerts(
     State(state): State<AppState>,
+    locale: Locale,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    match list_all_price_alerts(&state.pool).await {
+    match get_all_price_alerts_admin(&state.pool).await {
         Ok(alerts) => Ok(Json(json!({
             "status": "success",
-            "message": "Price alerts retrieved successfully",
+            "message": locale.t("price_alerts.listed"),
             "data": alerts
         }))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error retrieving price alerts: {}", e),
+            locale.t_with("price_alerts.list_error", &[("error", &e)]),
         )),
     }
 }
+
+pub async fn deactivate_user(
+    State(state): State<AppState>,
+    locale: Locale,
+    Path(id): Path<i32>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    match users::deactivate_user(&state.pool, id).await {
+        Ok(true) => {
+            // Cerrar sus conexiones en todas las instancias; si el bus falla
+            // al menos se cierran las de esta
+            if let Err(e) = publish_disconnect(state.bus.as_ref(), id, "Usuario desactivado").await {
+                error!("Error publicando la desconexión del usuario {}: {}", id, e);
+                state
+                    .ws_server
+                    .disconnect_user(id, "Usuario desactivado")
+                    .await;
+            }
+
+            Ok(Json(json!({
+                "status": "success",
+                "message": locale.t("users.deactivated"),
+            })))
+        }
+        Ok(false) => Err((StatusCode::NOT_FOUND, locale.t("users.not_found"))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale.t_with("users.deactivate_error", &[("error", &e)]),
+        )),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConnectionsQuery {
+    pub user_id: Option<i32>,
+}
+
+pub async fn get_connections(
+    State(state): State<AppState>,
+    locale: Locale,
+    Query(query): Query<ConnectionsQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let connections = state.ws_server.list_connections(query.user_id).await;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": locale.t("admin.connections_listed"),
+        "data": connections
+    })))
+}
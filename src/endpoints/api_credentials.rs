@@ -3,10 +3,12 @@ use axum::{
     Json,
     http::StatusCode,
 };
+use tracing::error;
 use crate::{
     db::api_credentials::{self, ApiCredential, CreateApiCredentialRequest},
     auth::jwt::Claims,
     endpoints::AppState,
+    i18n::Locale,
 };
 
 pub async fn create(
@@ -23,27 +25,35 @@ pub async fn create(
 pub async fn list(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
 ) -> Result<Json<Vec<ApiCredential>>, (StatusCode, String)> {
     let user_id: i32 = claims.user_id;  // Ya es i32
 
     api_credentials::list_by_user(&state.pool, user_id)
         .await
         .map(Json)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        .map_err(|e| {
+            error!("Error listando credenciales del usuario {}: {}", user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, locale.t("api_credentials.list_error"))
+        })
 }
 
 pub async fn get_one(
     _claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Path(id): Path<i64>,
 ) -> Result<Json<ApiCredential>, (StatusCode, String)> {
     let id: i32 = id.try_into()
-        .map_err(|_| (StatusCode::BAD_REQUEST, "ID inválido".to_string()))?;
+        .map_err(|_| (StatusCode::BAD_REQUEST, locale.t("api_credentials.invalid_id")))?;
 
     api_credentials::get_by_id(&state.pool, id)
         .await
         .map(Json)
-        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))
+        .map_err(|e| {
+            error!("Error obteniendo la credencial {}: {}", id, e);
+            (StatusCode::NOT_FOUND, locale.t("api_credentials.not_found"))
+        })
 }
 
 pub async fn update(
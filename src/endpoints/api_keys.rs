@@ -10,6 +10,7 @@ use crate::{
     app_state::AppState,
     auth::jwt::Claims,
     db::api_keys::{self, CreateApiKeyRequest, UpdateApiKeyRequest},
+    i18n::Locale,
 };
 
 pub fn api_keys_router() -> Router<AppState> {
@@ -24,12 +25,13 @@ pub fn api_keys_router() -> Router<AppState> {
 pub async fn create_api_key(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Json(req): Json<CreateApiKeyRequest>,
 ) -> Result<Json<JsonValue>, (StatusCode, String)> {
     match api_keys::create_api_key(&app_state.pool, claims.user_id, req).await {
         Ok(api_key) => Ok(Json(json!({
             "status": "success",
-            "message": "API key creada exitosamente",
+            "message": locale.t("api_keys.created"),
             "data": {
                 "id": api_key.id,
                 "name": api_key.name,
@@ -42,7 +44,7 @@ pub async fn create_api_key(
         Err(e) => {
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error al crear la API key: {}", e),
+                locale.t_with("api_keys.create_error", &[("error", &e)]),
             ))
         }
     }
@@ -51,12 +53,13 @@ pub async fn create_api_key(
 pub async fn get_api_key(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Path(id): Path<i32>,
 ) -> Result<Json<JsonValue>, (StatusCode, String)> {
     match api_keys::get_api_key(&app_state.pool, claims.user_id, id).await {
         Ok(Some(api_key)) => Ok(Json(json!({
             "status": "success",
-            "message": "API key encontrada",
+            "message": locale.t("api_keys.found"),
             "data": {
                 "id": api_key.id,
                 "name": api_key.name,
@@ -66,10 +69,10 @@ pub async fn get_api_key(
                 "updated_at": api_key.updated_at
             }
         }))),
-        Ok(None) => Err((StatusCode::NOT_FOUND, "API key no encontrada".to_string())),
+        Ok(None) => Err((StatusCode::NOT_FOUND, locale.t("api_keys.not_found"))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error al obtener la API key: {}", e),
+            locale.t_with("api_keys.fetch_error", &[("error", &e)]),
         )),
     }
 }
@@ -77,11 +80,12 @@ pub async fn get_api_key(
 pub async fn list_api_keys(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
 ) -> Result<Json<JsonValue>, (StatusCode, String)> {
     match api_keys::list_api_keys(&app_state.pool, claims.user_id).await {
         Ok(api_keys) => Ok(Json(json!({
             "status": "success",
-            "message": "API keys encontradas",
+            "message": locale.t("api_keys.listed"),
             "data": api_keys.iter().map(|key| {
                 json!({
                     "id": key.id,
@@ -95,7 +99,7 @@ pub async fn list_api_keys(
         }))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error al listar las API keys: {}", e),
+            locale.t_with("api_keys.list_error", &[("error", &e)]),
         )),
     }
 }
@@ -103,13 +107,14 @@ pub async fn list_api_keys(
 pub async fn update_api_key(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Path(id): Path<i32>,
     Json(req): Json<UpdateApiKeyRequest>,
 ) -> Result<Json<JsonValue>, (StatusCode, String)> {
     match api_keys::update_api_key(&app_state.pool, claims.user_id, id, &req).await {
         Ok(api_key) => Ok(Json(json!({
             "status": "success",
-            "message": "API key actualizada exitosamente",
+            "message": locale.t("api_keys.updated"),
             "data": {
                 "id": api_key.id,
                 "name": api_key.name,
@@ -122,7 +127,7 @@ pub async fn update_api_key(
         Err(e) => {
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error al actualizar la API key: {}", e),
+                locale.t_with("api_keys.update_error", &[("error", &e)]),
             ))
         }
     }
@@ -131,17 +136,18 @@ pub async fn update_api_key(
 pub async fn delete_api_key(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Path(id): Path<i32>,
 ) -> Result<Json<JsonValue>, (StatusCode, String)> {
     match api_keys::delete_api_key(&app_state.pool, id, claims.user_id).await {
         Ok(true) => Ok(Json(json!({
             "status": "success",
-            "message": "API key eliminada exitosamente"
+            "message": locale.t("api_keys.deleted")
         }))),
-        Ok(false) => Err((StatusCode::NOT_FOUND, "API key no encontrada".to_string())),
+        Ok(false) => Err((StatusCode::NOT_FOUND, locale.t("api_keys.not_found"))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error al eliminar la API key: {}", e),
+            locale.t_with("api_keys.delete_error", &[("error", &e)]),
         )),
     }
 }
@@ -4,6 +4,7 @@ use axum::{
     Json,
 };
 use serde_json::json;
+use tracing::error;
 
 use crate::{
     auth::jwt::Claims,
@@ -14,24 +15,104 @@ use crate::{
         update_asset_pair as db_update_asset_pair,
         delete_asset_pair as db_delete_asset_pair,
     },
+    db::instruments::{find_instrument, list_similar_instruments},
     endpoints::AppState,
-    models::asset_pairs::{AssetPair, CreateAssetPairRequest},
+    i18n::Locale,
+    models::{
+        api_keys::is_valid_exchange,
+        asset_pairs::{AssetPair, CreateAssetPairRequest},
+        instruments::{normalize_pair, suggest_symbols},
+    },
 };
 
+/// Normaliza el par recibido y verifica que exista en el catálogo de
+/// instrumentos del exchange. Si no existe, el error incluye sugerencias.
+async fn validate_asset_pair(
+    state: &AppState,
+    locale: Locale,
+    req: &mut CreateAssetPairRequest,
+) -> Result<(), (StatusCode, String)> {
+    let exchange = req.exchange.trim().to_lowercase();
+    if !is_valid_exchange(&exchange) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            locale.t_with("exchanges.unsupported", &[("exchange", &req.exchange)]),
+        ));
+    }
+
+    let (base, quote) = normalize_pair(&req.base_asset, &req.quote_asset).ok_or((
+        StatusCode::BAD_REQUEST,
+        locale.t_with(
+            "asset_pairs.invalid",
+            &[("base", &req.base_asset), ("quote", &req.quote_asset)],
+        ),
+    ))?;
+
+    let instrument = find_instrument(&state.pool, &exchange, &base, &quote)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t_with("asset_pairs.validate_error", &[("error", &e)]),
+            )
+        })?;
+
+    match instrument {
+        Some(instrument) if instrument.status == "trading" => {
+            req.exchange = exchange;
+            req.base_asset = instrument.base_asset;
+            req.quote_asset = instrument.quote_asset;
+            Ok(())
+        }
+        Some(instrument) => Err((
+            StatusCode::BAD_REQUEST,
+            locale.t_with(
+                "asset_pairs.not_trading",
+                &[("symbol", &instrument.symbol), ("exchange", &exchange), ("status", &instrument.status)],
+            ),
+        )),
+        None => {
+            // Sin sugerencias el error sigue siendo útil, no vale la pena un 500
+            let candidates = list_similar_instruments(&state.pool, &exchange, &base, &quote)
+                .await
+                .unwrap_or_else(|e| {
+                    error!("Error buscando instrumentos similares: {}", e);
+                    Vec::new()
+                });
+            let suggestions = suggest_symbols(&candidates, &base, &quote, 5);
+
+            let args: [(&str, &dyn std::fmt::Display); 3] = [("base", &base), ("quote", &quote), ("exchange", &exchange)];
+            let message = if suggestions.is_empty() {
+                locale.t_with("asset_pairs.unknown", &args)
+            } else {
+                let suggestions = suggestions.join(", ");
+                locale.t_with(
+                    "asset_pairs.unknown_with_suggestions",
+                    &[args.as_slice(), &[("suggestions", &suggestions)]].concat(),
+                )
+            };
+            Err((StatusCode::BAD_REQUEST, message))
+        }
+    }
+}
+
 pub async fn create_asset_pair(
     claims: Claims,
     State(state): State<AppState>,
-    Json(req): Json<CreateAssetPairRequest>,
+    locale: Locale,
+    Json(mut req): Json<CreateAssetPairRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    validate_asset_pair(&state, locale, &mut req).await?;
+
     match db_create_asset_pair(&state.pool, claims.user_id, &req).await {
         Ok(asset_pair) => Ok(Json(json!({
             "status": "success",
-            "message": "Asset pair created successfully",
+            "message": locale.t("asset_pairs.created"),
             "data": asset_pair
         }))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error creating asset pair: {}", e),
+            locale.t_with("asset_pairs.create_error", &[("error", &e)]),
         )),
     }
 }
@@ -39,16 +120,17 @@ pub async fn create_asset_pair(
 pub async fn list_asset_pairs(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     match db_list_asset_pairs(&state.pool, claims.user_id).await {
         Ok(asset_pairs) => Ok(Json(json!({
             "status": "success",
-            "message": "Asset pairs retrieved successfully",
+            "message": locale.t("asset_pairs.listed"),
             "data": asset_pairs
         }))),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Error retrieving asset pairs: {}", e),
+            locale.t_with("asset_pairs.list_error", &[("error", &e)]),
         )),
     }
 }
@@ -56,21 +138,22 @@ pub async fn list_asset_pairs(
 pub async fn get_asset_pair(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Path(id): Path<i32>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     match db_get_asset_pair(&state.pool, id, claims.user_id).await {
         Ok(asset_pair) => Ok(Json(json!({
             "status": "success",
-            "message": "Asset pair retrieved successfully",
+            "message": locale.t("asset_pairs.found"),
             "data": asset_pair
         }))),
         Err(e) => {
             if e.to_string().contains("no rows") {
-                Err((StatusCode::NOT_FOUND, "Asset pair not found".to_string()))
+                Err((StatusCode::NOT_FOUND, locale.t("asset_pairs.not_found")))
             } else {
                 Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error retrieving asset pair: {}", e),
+                    locale.t_with("asset_pairs.fetch_error", &[("error", &e)]),
                 ))
             }
         }
@@ -80,22 +163,25 @@ pub async fn get_asset_pair(
 pub async fn update_asset_pair(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Path(id): Path<i32>,
-    Json(req): Json<CreateAssetPairRequest>,
+    Json(mut req): Json<CreateAssetPairRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    validate_asset_pair(&state, locale, &mut req).await?;
+
     match db_update_asset_pair(&state.pool, id, claims.user_id, &req).await {
         Ok(asset_pair) => Ok(Json(json!({
             "status": "success",
-            "message": "Asset pair updated successfully",
+            "message": locale.t("asset_pairs.updated"),
             "data": asset_pair
         }))),
         Err(e) => {
             if e.to_string().contains("no rows") {
-                Err((StatusCode::NOT_FOUND, "Asset pair not found".to_string()))
+                Err((StatusCode::NOT_FOUND, locale.t("asset_pairs.not_found")))
             } else {
                 Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error updating asset pair: {}", e),
+                    locale.t_with("asset_pairs.update_error", &[("error", &e)]),
                 ))
             }
         }
@@ -105,20 +191,21 @@ pub async fn update_asset_pair(
 pub async fn delete_asset_pair(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Path(id): Path<i32>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     match db_delete_asset_pair(&state.pool, id, claims.user_id).await {
         Ok(_) => Ok(Json(json!({
             "status": "success",
-            "message": "Asset pair deleted successfully",
+            "message": locale.t("asset_pairs.deleted"),
         }))),
         Err(e) => {
             if e.to_string().contains("no rows") {
-                Err((StatusCode::NOT_FOUND, "Asset pair not found".to_string()))
+                Err((StatusCode::NOT_FOUND, locale.t("asset_pairs.not_found")))
             } else {
                 Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Error deleting asset pair: {}", e),
+                    locale.t_with("asset_pairs.delete_error", &[("error", &e)]),
                 ))
             }
         }
@@ -10,6 +10,7 @@ use crate::{
     app_state::AppState,
     auth::jwt,
     db::users::{self, LoginRequest},
+    i18n::Locale,
     models::users::CreateUserRequest,
 };
 
@@ -21,6 +22,7 @@ pub fn auth_router() -> Router<AppState> {
 
 pub async fn login(
     State(app_state): State<AppState>,
+    locale: Locale,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
     match users::authenticate_user(&app_state.pool, &req.username, &req.password).await {
@@ -30,13 +32,13 @@ pub async fn login(
                 error!("Error creating token: {}", e);
                 (
                     axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    "Error interno del servidor".to_string(),
+                    locale.t("errors.internal"),
                 )
             })?;
 
             Ok(Json(json!({
                 "status": "success",
-                "message": "Login exitoso",
+                "message": locale.t("auth.login_success"),
                 "data": {
                     "id": user.id,
                     "username": user.username,
@@ -49,7 +51,7 @@ pub async fn login(
             error!("Error authenticating user: {}", e);
             Err((
                 axum::http::StatusCode::UNAUTHORIZED,
-                "Credenciales inválidas".to_string(),
+                locale.t("auth.invalid_credentials"),
             ))
         }
     }
@@ -57,6 +59,7 @@ pub async fn login(
 
 pub async fn register(
     State(app_state): State<AppState>,
+    locale: Locale,
     Json(req): Json<CreateUserRequest>,
 ) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
     let _password_hash = bcrypt::hash(req.password.as_bytes(), bcrypt::DEFAULT_COST)
@@ -64,14 +67,14 @@ pub async fn register(
             error!("Error hashing password: {}", e);
             (
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Error interno del servidor".to_string(),
+                locale.t("errors.internal"),
             )
         })?;
 
     match users::create_user(&app_state.pool, &req).await {
         Ok(user) => Ok(Json(json!({
             "status": "success",
-            "message": "Usuario creado exitosamente",
+            "message": locale.t("users.created"),
             "data": {
                 "id": user.id,
                 "username": user.username,
@@ -82,7 +85,7 @@ pub async fn register(
             error!("Error creating user: {}", e);
             Err((
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Error interno del servidor".to_string(),
+                locale.t("errors.internal"),
             ))
         }
     }
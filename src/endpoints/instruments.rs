@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+
+use crate::{
+    api::exchange_info::{self, ExchangeInfoError, InstrumentSource},
+    db::instruments,
+    endpoints::AppState,
+    i18n::Locale,
+    models::{
+        api_keys::is_valid_exchange,
+        instruments::{InstrumentQuery, SyncInstrumentsRequest},
+    },
+};
+
+pub async fn list_instruments(
+    State(state): State<AppState>,
+    locale: Locale,
+    Query(query): Query<InstrumentQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let exchange = query.exchange.map(|e| e.to_lowercase());
+    let quote_asset = query.quote_asset.map(|q| q.to_uppercase());
+
+    match instruments::list_instruments(&state.pool, exchange.as_deref(), quote_asset.as_deref()).await {
+        Ok(instruments) => Ok(Json(json!({
+            "status": "success",
+            "message": locale.t("instruments.listed"),
+            "data": instruments
+        }))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale.t_with("instruments.list_error", &[("error", &e)]),
+        )),
+    }
+}
+
+pub async fn sync_instruments(
+    State(state): State<AppState>,
+    locale: Locale,
+    Json(req): Json<SyncInstrumentsRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let exchange = req.exchange.to_lowercase();
+    if !is_valid_exchange(&exchange) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            locale.t_with("exchanges.unsupported", &[("exchange", &req.exchange)]),
+        ));
+    }
+
+    let source = InstrumentSource::from_env();
+    match exchange_info::sync_instruments(&state.pool, &exchange, &source).await {
+        Ok(count) => Ok(Json(json!({
+            "status": "success",
+            "message": locale.t("instruments.synchronized"),
+            "data": { "exchange": exchange, "instruments": count }
+        }))),
+        Err(e @ (ExchangeInfoError::Http(_) | ExchangeInfoError::EmptyCatalog(_))) => Err((
+            StatusCode::BAD_GATEWAY,
+            locale.t_with("instruments.exchange_error", &[("error", &e)]),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale.t_with("instruments.sync_error", &[("error", &e)]),
+        )),
+    }
+}
@@ -0,0 +1,159 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde_json::json;
+
+use crate::{
+    api::klines::{self, KlinesError},
+    db::{candles, instruments::find_instrument},
+    endpoints::AppState,
+    i18n::Locale,
+    models::{
+        candles::{parse_candles_csv, BackfillRequest, CandleImportQuery, CandlePage, CandleQuery, Timeframe},
+        instruments::{split_symbol, Instrument},
+    },
+};
+
+const DEFAULT_CANDLE_LIMIT: i64 = 500;
+const MAX_CANDLE_LIMIT: i64 = 1000;
+
+async fn resolve_instrument(
+    state: &AppState,
+    locale: Locale,
+    exchange: &str,
+    symbol: &str,
+) -> Result<Instrument, (StatusCode, String)> {
+    let exchange = exchange.to_lowercase();
+    let (base, quote) = split_symbol(symbol)
+        .ok_or((StatusCode::BAD_REQUEST, locale.t_with("market.invalid_symbol", &[("symbol", &symbol)])))?;
+
+    find_instrument(&state.pool, &exchange, &base, &quote)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t_with("instruments.fetch_error", &[("error", &e)]),
+            )
+        })?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            locale.t_with(
+                "instruments.unknown",
+                &[("base", &base), ("quote", &quote), ("exchange", &exchange)],
+            ),
+        ))
+}
+
+fn parse_timeframe(locale: Locale, timeframe: &str) -> Result<Timeframe, (StatusCode, String)> {
+    timeframe.parse::<Timeframe>().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            locale.t_with("candles.invalid_timeframe", &[("timeframe", &timeframe)]),
+        )
+    })
+}
+
+pub async fn get_candles(
+    State(state): State<AppState>,
+    locale: Locale,
+    Query(query): Query<CandleQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let timeframe = parse_timeframe(locale, &query.timeframe)?;
+    let instrument = resolve_instrument(&state, locale, &query.exchange, &query.symbol).await?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_CANDLE_LIMIT).clamp(1, MAX_CANDLE_LIMIT);
+    let end = query.end.unwrap_or_else(Utc::now);
+    let start = query
+        .start
+        .unwrap_or_else(|| end - timeframe.duration() * limit as i32);
+
+    if start >= end {
+        return Err((StatusCode::BAD_REQUEST, locale.t("candles.invalid_range")));
+    }
+
+    let candles = candles::load_candles(&state.pool, instrument.id, timeframe, start, end, limit)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t_with("candles.fetch_error", &[("error", &e)]),
+            )
+        })?;
+
+    let next_start = if candles.len() as i64 == limit {
+        candles
+            .last()
+            .map(|c| c.open_time + timeframe.duration())
+            .filter(|next| *next < end)
+    } else {
+        None
+    };
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": locale.t("candles.listed"),
+        "data": CandlePage { candles, next_start }
+    })))
+}
+
+pub async fn import_candles(
+    State(state): State<AppState>,
+    locale: Locale,
+    Query(query): Query<CandleImportQuery>,
+    body: String,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let timeframe = parse_timeframe(locale, &query.timeframe)?;
+    let instrument = resolve_instrument(&state, locale, &query.exchange, &query.symbol).await?;
+
+    let parsed = parse_candles_csv(instrument.id, timeframe, &body, locale)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    match candles::upsert_candles(&state.pool, &parsed).await {
+        Ok(count) => Ok(Json(json!({
+            "status": "success",
+            "message": locale.t("candles.imported"),
+            "data": { "imported": count }
+        }))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale.t_with("candles.import_error", &[("error", &e)]),
+        )),
+    }
+}
+
+pub async fn backfill_candles(
+    State(state): State<AppState>,
+    locale: Locale,
+    Json(req): Json<BackfillRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if req.start >= req.end {
+        return Err((StatusCode::BAD_REQUEST, locale.t("candles.invalid_range")));
+    }
+    if req.end - req.start > chrono::Duration::days(klines::MAX_BACKFILL_DAYS) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            locale.t_with("candles.backfill_range_too_large", &[("days", &klines::MAX_BACKFILL_DAYS)]),
+        ));
+    }
+
+    let instrument = resolve_instrument(&state, locale, &req.exchange, &req.symbol).await?;
+
+    match klines::backfill_minute_candles(&state.pool, &instrument, req.start, req.end).await {
+        Ok(count) => Ok(Json(json!({
+            "status": "success",
+            "message": locale.t("candles.backfilled"),
+            "data": { "inserted": count }
+        }))),
+        Err(KlinesError::Http(e)) => Err((
+            StatusCode::BAD_GATEWAY,
+            locale.t_with("candles.exchange_error", &[("error", &e)]),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale.t_with("candles.backfill_error", &[("error", &e)]),
+        )),
+    }
+}
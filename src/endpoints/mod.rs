@@ -1,16 +1,13 @@
-use axum::Router;
-
-use crate::app_state::AppState;
+pub use crate::app_state::AppState;
 
+pub mod admin;
 pub mod api_keys;
+pub mod asset_pairs;
 pub mod auth;
+pub mod instruments;
+pub mod market;
+pub mod price_alerts;
 pub mod users;
 pub mod notifications;
-
-pub fn create_router(state: AppState) -> Router {
-    Router::new()
-        .merge(api_keys::api_keys_router())
-        .merge(auth::auth_router())
-        .merge(users::users_router())
-        .with_state(state)
-}
\ No newline at end of file
+pub mod telegram;
+pub mod whatsapp;
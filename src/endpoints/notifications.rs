@@ -1,130 +1,228 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{header::USER_AGENT, HeaderMap, StatusCode},
+    response::Html,
     Json, Router,
-    routing::{get, put, post, delete},
+    routing::{get, put, post, patch, delete},
 };
 use serde::Deserialize;
+use serde_json::json;
 use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     auth::jwt::Claims,
-    db::notifications,
-    models::notifications::{CreateWebhookRequest, NotificationPreference, WebhookConfig},
+    db::{notifications, push_subscriptions, webhook_deliveries},
+    models::notifications::{
+        CreateNotificationRuleRequest, CreatePushSubscriptionRequest, CreateWebhookRequest, NotificationPreference,
+        NotificationRule, PushSubscription, UpdatePreferencesRequest, UpdateWebhookRequest, WebhookConfig,
+        WebhookDelivery,
+    },
     app_state::AppState,
+    i18n::Locale,
+    notifications::{
+        email::verify_unsubscribe_token_from_env,
+        events::{self, EventDescriptor},
+        dispatcher::ChannelKind,
+        models::{NotificationListQuery, NotificationPage},
+        sse::sse_handler,
+        web_push::{parse_auth_secret, parse_p256dh, VapidKeys},
+        webhook::{generate_webhook_secret, validate_event_patterns, validate_webhook_url},
+        NotificationType,
+    },
 };
 
+const DEFAULT_NOTIFICATIONS_LIMIT: i64 = 50;
+const MAX_NOTIFICATIONS_LIMIT: i64 = 200;
+const DEFAULT_DELIVERIES_LIMIT: i64 = 50;
+/// Horas durante las que se sigue firmando con el secreto anterior.
+const DEFAULT_SECRET_ROTATION_GRACE_HOURS: i64 = 24;
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveriesQuery {
+    pub limit: Option<i64>,
+}
+
 #[derive(Debug, Deserialize)]
-pub struct UpdatePreferencesRequest {
-    pub email_enabled: Option<bool>,
-    pub telegram_enabled: Option<bool>,
-    pub whatsapp_enabled: Option<bool>,
-    pub price_alerts_enabled: Option<bool>,
-    pub system_alerts_enabled: Option<bool>,
+pub struct UnsubscribeQuery {
+    pub token: String,
 }
 
 pub async fn get_preferences(
     State(state): State<AppState>,
     claims: Claims,
+    locale: Locale,
 ) -> Result<Json<NotificationPreference>, (StatusCode, String)> {
-    match notifications::get_notification_preferences(&state.pool, claims.user_id).await {
-        Ok(prefs) => Ok(Json(prefs)),
+    let prefs = match notifications::get_notification_preferences(&state.pool, claims.user_id).await {
+        // Si no existen preferencias, crear unas por defecto
         Err(sqlx::Error::RowNotFound) => {
-            // Si no existen preferencias, crear unas por defecto
-            let now = chrono::Utc::now();
-            let prefs = sqlx::query_as::<_, NotificationPreference>(
-                r#"
-                INSERT INTO notification_preferences (
-                    user_id,
-                    email_enabled,
-                    telegram_enabled,
-                    whatsapp_enabled,
-                    price_alerts_enabled,
-                    system_alerts_enabled,
-                    created_at,
-                    updated_at
-                )
-                VALUES ($1, true, true, false, true, true, $2, $2)
-                RETURNING user_id, email_enabled, telegram_enabled, whatsapp_enabled,
-                         price_alerts_enabled, system_alerts_enabled, created_at, updated_at
-                "#
-            )
-            .bind(claims.user_id)
-            .bind(now)
-            .fetch_one(&state.pool)
-            .await
-            .map_err(|e| {
-                error!("Error al crear preferencias por defecto: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Error al crear preferencias por defecto".to_string(),
-                )
-            })?;
-
-            Ok(Json(prefs))
+            notifications::create_default_preferences(&state.pool, claims.user_id)
+                .await
+                .map_err(|e| {
+                    error!("Error al crear preferencias por defecto: {}", e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        locale.t("preferences.create_error"),
+                    )
+                })?
         }
+        Ok(prefs) => prefs,
         Err(e) => {
             error!("Error al obtener preferencias: {}", e);
-            Err((
+            return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Error al obtener preferencias".to_string(),
-            ))
+                locale.t("preferences.fetch_error"),
+            ));
         }
-    }
+    };
+
+    Ok(Json(prefs))
 }
 
 pub async fn update_preferences(
     State(state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Json(request): Json<UpdatePreferencesRequest>,
 ) -> Result<Json<NotificationPreference>, (StatusCode, String)> {
-    let updated = sqlx::query_as::<_, NotificationPreference>(
-        r#"
-        UPDATE notification_preferences
-        SET 
-            email_enabled = COALESCE($1, email_enabled),
-            telegram_enabled = COALESCE($2, telegram_enabled),
-            whatsapp_enabled = COALESCE($3, whatsapp_enabled),
-            price_alerts_enabled = COALESCE($4, price_alerts_enabled),
-            system_alerts_enabled = COALESCE($5, system_alerts_enabled),
-            updated_at = CURRENT_TIMESTAMP
-        WHERE user_id = $6
-        RETURNING user_id, email_enabled, telegram_enabled, whatsapp_enabled,
-                 price_alerts_enabled, system_alerts_enabled, created_at, updated_at
-        "#
+    request.validate(locale).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let updated = notifications::update_notification_preferences(&state.pool, claims.user_id, &request)
+        .await
+        .map_err(|e| {
+            error!("Error al actualizar preferencias: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("preferences.update_error"),
+            )
+        })?;
+
+    Ok(Json(updated))
+}
+
+pub async fn list_rules(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<Vec<NotificationRule>>, (StatusCode, String)> {
+    match notifications::list_notification_rules(&state.pool, claims.user_id).await {
+        Ok(rules) => Ok(Json(rules)),
+        Err(e) => {
+            error!("Error al listar reglas de notificación: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("rules.list_error"),
+            ))
+        }
+    }
+}
+
+pub async fn upsert_rule(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Json(request): Json<CreateNotificationRuleRequest>,
+) -> Result<Json<NotificationRule>, (StatusCode, String)> {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, message);
+
+    let notification_type = match request.notification_type.as_deref() {
+        Some(name) => Some(
+            NotificationType::from_name(name)
+                .ok_or_else(|| bad_request(locale.t_with("rules.unknown_type", &[("type", &name)])))?
+                .as_str(),
+        ),
+        None => None,
+    };
+    let channel = match request.channel.as_deref() {
+        Some(name) => Some(
+            ChannelKind::from_name(name)
+                .ok_or_else(|| bad_request(locale.t_with("rules.unknown_channel", &[("channel", &name)])))?
+                .as_str(),
+        ),
+        None => None,
+    };
+    let asset = request.asset.as_deref().map(|a| a.trim().to_uppercase());
+    if let Some(asset) = &asset {
+        if asset.is_empty() || asset.len() > 20 {
+            return Err(bad_request(locale.t("rules.invalid_asset")));
+        }
+    }
+
+    match notifications::upsert_notification_rule(
+        &state.pool,
+        claims.user_id,
+        notification_type,
+        channel,
+        asset.as_deref(),
+        request.enabled,
     )
-    .bind(request.email_enabled)
-    .bind(request.telegram_enabled)
-    .bind(request.whatsapp_enabled)
-    .bind(request.price_alerts_enabled)
-    .bind(request.system_alerts_enabled)
-    .bind(claims.user_id)
-    .fetch_one(&state.pool)
     .await
-    .map_err(|e| {
-        error!("Error al actualizar preferencias: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Error al actualizar preferencias".to_string(),
-        )
-    })?;
+    {
+        Ok(rule) => Ok(Json(rule)),
+        Err(e) => {
+            error!("Error al guardar regla de notificación: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("rules.save_error"),
+            ))
+        }
+    }
+}
 
-    Ok(Json(updated))
+pub async fn delete_rule(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(rule_id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    match notifications::delete_notification_rule(&state.pool, claims.user_id, rule_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, locale.t("rules.not_found"))),
+        Err(e) => {
+            error!("Error al eliminar regla de notificación: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("rules.delete_error"),
+            ))
+        }
+    }
+}
+
+fn validate_api_version(version: i32, locale: Locale) -> Result<(), String> {
+    if events::is_supported_version(version) {
+        Ok(())
+    } else {
+        let supported: Vec<String> = events::SUPPORTED_VERSIONS.iter().map(|v| v.to_string()).collect();
+        Err(locale.t_with(
+            "webhooks.unsupported_version",
+            &[("version", &version), ("supported", &supported.join(", "))],
+        ))
+    }
+}
+
+/// Catálogo de eventos de webhooks con el JSON Schema de cada payload.
+pub async fn list_webhook_events() -> Json<Vec<EventDescriptor>> {
+    Json(events::catalog())
 }
 
 pub async fn create_webhook(
     State(state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Json(request): Json<CreateWebhookRequest>,
 ) -> Result<Json<WebhookConfig>, (StatusCode, String)> {
+    validate_webhook_url(request.kind, &request.url, locale)
+        .and_then(|_| validate_event_patterns(&request.notification_types, locale))
+        .and_then(|_| request.api_version.map_or(Ok(()), |version| validate_api_version(version, locale)))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     match notifications::create_webhook(&state.pool, claims.user_id, &request).await {
         Ok(webhook) => Ok(Json(webhook)),
         Err(e) => {
             error!("Error al crear webhook: {}", e);
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Error al crear webhook".to_string(),
+                locale.t("webhooks.create_error"),
             ))
         }
     }
@@ -133,6 +231,7 @@ pub async fn create_webhook(
 pub async fn list_webhooks(
     State(state): State<AppState>,
     claims: Claims,
+    locale: Locale,
 ) -> Result<Json<Vec<WebhookConfig>>, (StatusCode, String)> {
     match notifications::list_webhooks(&state.pool, claims.user_id).await {
         Ok(webhooks) => Ok(Json(webhooks)),
@@ -140,7 +239,94 @@ pub async fn list_webhooks(
             error!("Error al listar webhooks: {}", e);
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Error al listar webhooks".to_string(),
+                locale.t("webhooks.list_error"),
+            ))
+        }
+    }
+}
+
+pub async fn update_webhook(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(webhook_id): Path<Uuid>,
+    Json(request): Json<UpdateWebhookRequest>,
+) -> Result<Json<WebhookConfig>, (StatusCode, String)> {
+    if let Some(url) = &request.url {
+        let kind = match webhook_deliveries::get_webhook(&state.pool, webhook_id).await {
+            Ok(Some(webhook)) if webhook.user_id == claims.user_id => webhook.kind,
+            Ok(_) => return Err((StatusCode::NOT_FOUND, locale.t("webhooks.not_found"))),
+            Err(e) => {
+                error!("Error al obtener webhook: {}", e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    locale.t("webhooks.update_error"),
+                ));
+            }
+        };
+        validate_webhook_url(kind, url, locale).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    if let Some(types) = &request.notification_types {
+        validate_event_patterns(types, locale).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    if let Some(version) = request.api_version {
+        validate_api_version(version, locale).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    match notifications::update_webhook(&state.pool, claims.user_id, webhook_id, &request).await {
+        Ok(Some(webhook)) => Ok(Json(webhook)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, locale.t("webhooks.not_found"))),
+        Err(e) => {
+            error!("Error al actualizar webhook: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("webhooks.update_error"),
+            ))
+        }
+    }
+}
+
+pub async fn enable_webhook(
+    state: State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    webhook_id: Path<Uuid>,
+) -> Result<Json<WebhookConfig>, (StatusCode, String)> {
+    let request = UpdateWebhookRequest {
+        enabled: Some(true),
+        ..Default::default()
+    };
+    update_webhook(state, claims, locale, webhook_id, Json(request)).await
+}
+
+pub async fn disable_webhook(
+    state: State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    webhook_id: Path<Uuid>,
+) -> Result<Json<WebhookConfig>, (StatusCode, String)> {
+    let request = UpdateWebhookRequest {
+        enabled: Some(false),
+        ..Default::default()
+    };
+    update_webhook(state, claims, locale, webhook_id, Json(request)).await
+}
+
+/// Envía un evento `ping` al webhook y devuelve el resultado del intento.
+pub async fn test_webhook(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<Json<WebhookDelivery>, (StatusCode, String)> {
+    match state.webhooks.ping(claims.user_id, webhook_id).await {
+        Ok(Some(delivery)) => Ok(Json(delivery)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, locale.t("webhooks.not_found"))),
+        Err(e) => {
+            error!("Error al probar webhook: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("webhooks.test_error"),
             ))
         }
     }
@@ -149,18 +335,338 @@ pub async fn list_webhooks(
 pub async fn delete_webhook(
     State(state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Path(webhook_id): Path<Uuid>,
 ) -> Result<StatusCode, (StatusCode, String)> {
     match notifications::delete_webhook(&state.pool, claims.user_id, webhook_id).await {
         Ok(_) => Ok(StatusCode::NO_CONTENT),
         Err(sqlx::Error::RowNotFound) => {
-            Err((StatusCode::NOT_FOUND, "Webhook no encontrado".to_string()))
+            Err((StatusCode::NOT_FOUND, locale.t("webhooks.not_found")))
         }
         Err(e) => {
             error!("Error al eliminar webhook: {}", e);
             Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                "Error interno del servidor".to_string(),
+                locale.t("errors.internal"),
+            ))
+        }
+    }
+}
+
+/// Genera un secreto nuevo. El anterior sigue firmando durante el período
+/// de gracia para que el receptor pueda actualizarse sin perder entregas.
+pub async fn rotate_webhook_secret(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<Json<WebhookConfig>, (StatusCode, String)> {
+    let grace_hours = std::env::var("WEBHOOK_SECRET_ROTATION_GRACE_HOURS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &i64| *v >= 0)
+        .unwrap_or(DEFAULT_SECRET_ROTATION_GRACE_HOURS);
+    let previous_expires_at = chrono::Utc::now() + chrono::Duration::hours(grace_hours);
+
+    match notifications::rotate_webhook_secret(
+        &state.pool,
+        claims.user_id,
+        webhook_id,
+        &generate_webhook_secret(),
+        previous_expires_at,
+    )
+    .await
+    {
+        Ok(Some(webhook)) => Ok(Json(webhook)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, locale.t("webhooks.not_found"))),
+        Err(e) => {
+            error!("Error al rotar el secreto del webhook: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("webhooks.rotate_secret_error"),
+            ))
+        }
+    }
+}
+
+pub async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(webhook_id): Path<Uuid>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, (StatusCode, String)> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_DELIVERIES_LIMIT)
+        .clamp(1, MAX_NOTIFICATIONS_LIMIT);
+
+    match webhook_deliveries::list_deliveries(&state.pool, claims.user_id, webhook_id, limit).await {
+        Ok(deliveries) => Ok(Json(deliveries)),
+        Err(e) => {
+            error!("Error al listar entregas del webhook: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("webhooks.list_deliveries_error"),
+            ))
+        }
+    }
+}
+
+pub async fn redeliver_webhook(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<WebhookDelivery>, (StatusCode, String)> {
+    match state.webhooks.redeliver(claims.user_id, webhook_id, delivery_id).await {
+        Ok(Some(delivery)) => Ok(Json(delivery)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, locale.t("webhooks.delivery_not_found"))),
+        Err(e) => {
+            error!("Error al reenviar webhook: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("webhooks.redeliver_error"),
+            ))
+        }
+    }
+}
+
+pub async fn list_notifications(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Query(query): Query<NotificationListQuery>,
+) -> Result<Json<NotificationPage>, (StatusCode, String)> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_NOTIFICATIONS_LIMIT)
+        .clamp(1, MAX_NOTIFICATIONS_LIMIT);
+
+    match notifications::list_notifications(&state.pool, claims.user_id, &query, limit).await {
+        Ok(notifications) => {
+            let next_cursor = if notifications.len() as i64 == limit {
+                notifications.last().map(|n| n.event_id)
+            } else {
+                None
+            };
+            Ok(Json(NotificationPage {
+                notifications,
+                next_cursor,
+            }))
+        }
+        Err(e) => {
+            error!("Error al listar notificaciones: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("notifications.list_error"),
+            ))
+        }
+    }
+}
+
+pub async fn unread_count(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    match notifications::count_unread_notifications(&state.pool, claims.user_id).await {
+        Ok(unread) => Ok(Json(json!({ "unread": unread }))),
+        Err(e) => {
+            error!("Error al contar notificaciones no leídas: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("notifications.count_error"),
+            ))
+        }
+    }
+}
+
+pub async fn mark_read(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(event_id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    match notifications::mark_notifications_read(&state.pool, claims.user_id, &[event_id]).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            error!("Error al marcar notificación como leída: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("notifications.mark_read_error"),
+            ))
+        }
+    }
+}
+
+pub async fn mark_all_read(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    match notifications::mark_all_notifications_read(&state.pool, claims.user_id).await {
+        Ok(updated) => Ok(Json(json!({ "updated": updated }))),
+        Err(e) => {
+            error!("Error al marcar notificaciones como leídas: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("notifications.mark_all_read_error"),
+            ))
+        }
+    }
+}
+
+pub async fn delete_notification(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(event_id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    match notifications::delete_notification(&state.pool, claims.user_id, event_id).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(sqlx::Error::RowNotFound) => {
+            Err((StatusCode::NOT_FOUND, locale.t("notifications.not_found")))
+        }
+        Err(e) => {
+            error!("Error al eliminar notificación: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("errors.internal"),
+            ))
+        }
+    }
+}
+
+/// Clave pública VAPID que el navegador pasa como `applicationServerKey`
+/// al suscribirse.
+pub async fn vapid_public_key(locale: Locale) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let keys = VapidKeys::from_env()
+        .ok_or((StatusCode::NOT_FOUND, locale.t("push.not_configured")))?;
+    Ok(Json(json!({ "public_key": keys.public_key() })))
+}
+
+pub async fn create_push_subscription(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    headers: HeaderMap,
+    Json(request): Json<CreatePushSubscriptionRequest>,
+) -> Result<Json<PushSubscription>, (StatusCode, String)> {
+    match reqwest::Url::parse(&request.endpoint) {
+        Ok(url) if url.scheme() == "https" => {}
+        _ => return Err((StatusCode::BAD_REQUEST, locale.t("push.invalid_endpoint"))),
+    }
+    parse_p256dh(&request.keys.p256dh)
+        .and(parse_auth_secret(&request.keys.auth))
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                locale.t_with("push.invalid_keys", &[("error", &e)]),
+            )
+        })?;
+
+    let user_agent = headers.get(USER_AGENT).and_then(|v| v.to_str().ok());
+    match push_subscriptions::upsert_subscription(&state.pool, claims.user_id, &request, user_agent).await {
+        Ok(subscription) => Ok(Json(subscription)),
+        Err(e) => {
+            error!("Error al registrar suscripción de push: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("push.subscribe_error"),
+            ))
+        }
+    }
+}
+
+pub async fn list_push_subscriptions(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<Vec<PushSubscription>>, (StatusCode, String)> {
+    match push_subscriptions::list_subscriptions(&state.pool, claims.user_id).await {
+        Ok(subscriptions) => Ok(Json(subscriptions)),
+        Err(e) => {
+            error!("Error al listar suscripciones de push: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("push.list_error"),
+            ))
+        }
+    }
+}
+
+pub async fn delete_push_subscription(
+    State(state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Path(subscription_id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    match push_subscriptions::delete_subscription(&state.pool, claims.user_id, subscription_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err((StatusCode::NOT_FOUND, locale.t("push.not_found"))),
+        Err(e) => {
+            error!("Error al eliminar suscripción de push: {}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("push.delete_error"),
+            ))
+        }
+    }
+}
+
+fn unsubscribe_page(locale: Locale, body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html><html lang=\"{}\"><head><meta charset=\"utf-8\"><title>{}</title></head>\
+         <body style=\"font-family:Arial,Helvetica,sans-serif;max-width:480px;margin:48px auto;\">{}</body></html>",
+        locale.as_str(),
+        locale.t("unsubscribe.page_title"),
+        body
+    ))
+}
+
+/// Página de confirmación enlazada desde el pie de los emails. No da de baja
+/// por sí sola para que los escáneres de enlaces no la disparen.
+pub async fn unsubscribe_form(
+    locale: Locale,
+    Query(query): Query<UnsubscribeQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    if verify_unsubscribe_token_from_env(&query.token).is_none() {
+        return Err((StatusCode::BAD_REQUEST, locale.t("unsubscribe.invalid_link")));
+    }
+
+    Ok(unsubscribe_page(
+        locale,
+        &format!(
+            "<h1>{}</h1><p>{}</p><form method=\"post\" action=\"?token={}\"><button type=\"submit\">{}</button></form>",
+            locale.t("unsubscribe.confirm_title"),
+            locale.t("unsubscribe.confirm_body"),
+            query.token,
+            locale.t("unsubscribe.confirm_button"),
+        ),
+    ))
+}
+
+/// Baja con un clic (RFC 8058): la usan tanto el formulario como los
+/// clientes de correo a partir de `List-Unsubscribe-Post`.
+pub async fn unsubscribe(
+    State(state): State<AppState>,
+    locale: Locale,
+    Query(query): Query<UnsubscribeQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let user_id = verify_unsubscribe_token_from_env(&query.token)
+        .ok_or((StatusCode::BAD_REQUEST, locale.t("unsubscribe.invalid_link")))?;
+
+    match notifications::disable_email_notifications(&state.pool, user_id).await {
+        Ok(()) => Ok(unsubscribe_page(
+            locale,
+            &format!("<h1>{}</h1><p>{}</p>", locale.t("unsubscribe.done_title"), locale.t("unsubscribe.done_body")),
+        )),
+        Err(e) => {
+            error!("Error al desactivar el email del usuario {}: {}", user_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("preferences.update_error"),
             ))
         }
     }
@@ -168,7 +674,31 @@ pub async fn delete_webhook(
 
 pub fn notifications_router() -> Router<AppState> {
     Router::new()
+        // El stream se autentica por sí mismo: acepta el token en la query
+        .route("/stream", get(sse_handler))
+        .route("/", get(list_notifications))
+        .route("/unread-count", get(unread_count))
+        .route("/read-all", post(mark_all_read))
+        .route("/:event_id", delete(delete_notification))
+        .route("/:event_id/read", post(mark_read))
         .route("/preferences", get(get_preferences).put(update_preferences))
+        .route("/preferences/rules", get(list_rules).post(upsert_rule))
+        .route("/preferences/rules/:id", delete(delete_rule))
+        // Sin sesión: se autentica con el token firmado del enlace
+        .route("/unsubscribe", get(unsubscribe_form).post(unsubscribe))
+        .route("/push/vapid-public-key", get(vapid_public_key))
+        .route("/push/subscriptions", get(list_push_subscriptions).post(create_push_subscription))
+        .route("/push/subscriptions/:id", delete(delete_push_subscription))
         .route("/webhooks", get(list_webhooks).post(create_webhook))
-        .route("/webhooks/:id", delete(delete_webhook))
+        .route("/webhooks/events", get(list_webhook_events))
+        .route("/webhooks/:id", patch(update_webhook).delete(delete_webhook))
+        .route("/webhooks/:id/enable", post(enable_webhook))
+        .route("/webhooks/:id/disable", post(disable_webhook))
+        .route("/webhooks/:id/test", post(test_webhook))
+        .route("/webhooks/:id/rotate-secret", post(rotate_webhook_secret))
+        .route("/webhooks/:id/deliveries", get(list_webhook_deliveries))
+        .route(
+            "/webhooks/:id/deliveries/:delivery_id/redeliver",
+            post(redeliver_webhook),
+        )
 }
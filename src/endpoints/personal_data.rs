@@ -5,20 +5,26 @@ use axum::{
 };
 use sqlx::SqlitePool;
 use std::sync::Arc;
+use tracing::error;
 use crate::{
     models::user::Claims,
     db::personal_data::{PersonalData, CreatePersonalDataRequest},
+    i18n::Locale,
 };
 
 // Crear datos personales
 pub async fn create_personal_data(
     claims: Claims,
     State(pool): State<Arc<SqlitePool>>,
+    locale: Locale,
     Json(data): Json<CreatePersonalDataRequest>,
 ) -> Result<Json<PersonalData>, (StatusCode, String)> {
     let personal_data = crate::db::personal_data::create(&pool, claims.user_id, &data)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| {
+            error!("Error guardando datos personales del usuario {}: {}", claims.user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, locale.t("personal_data.create_error"))
+        })?;
     
     Ok(Json(personal_data))
 }
@@ -27,10 +33,14 @@ pub async fn create_personal_data(
 pub async fn get_personal_data(
     claims: Claims,
     State(pool): State<Arc<SqlitePool>>,
+    locale: Locale,
 ) -> Result<Json<PersonalData>, (StatusCode, String)> {
     let data = crate::db::personal_data::get_by_user_id(&pool, claims.user_id)
         .await
-        .map_err(|e| (StatusCode::NOT_FOUND, e.to_string()))?;
+        .map_err(|e| {
+            error!("Error obteniendo datos personales del usuario {}: {}", claims.user_id, e);
+            (StatusCode::NOT_FOUND, locale.t("personal_data.not_found"))
+        })?;
     
     Ok(Json(data))
 }
@@ -39,11 +49,15 @@ pub async fn get_personal_data(
 pub async fn update_personal_data(
     claims: Claims,
     State(pool): State<Arc<SqlitePool>>,
+    locale: Locale,
     Json(data): Json<CreatePersonalDataRequest>,
 ) -> Result<Json<PersonalData>, (StatusCode, String)> {
     let updated = crate::db::personal_data::update(&pool, claims.user_id, &data)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| {
+            error!("Error actualizando datos personales del usuario {}: {}", claims.user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, locale.t("personal_data.update_error"))
+        })?;
     
     Ok(Json(updated))
 }
@@ -52,10 +66,14 @@ pub async fn update_personal_data(
 pub async fn delete_personal_data(
     claims: Claims,
     State(pool): State<Arc<SqlitePool>>,
+    locale: Locale,
 ) -> Result<StatusCode, (StatusCode, String)> {
     crate::db::personal_data::delete(&pool, claims.user_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| {
+            error!("Error eliminando datos personales del usuario {}: {}", claims.user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, locale.t("personal_data.delete_error"))
+        })?;
     
     Ok(StatusCode::NO_CONTENT)
 } 
\ No newline at end of file
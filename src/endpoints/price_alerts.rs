@@ -3,21 +3,22 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use serde_json::json;
+use tracing::error;
 
 use crate::{
     auth::jwt::Claims,
     models::price_alerts::{PriceAlert, CreatePriceAlertRequest},
     endpoints::AppState,
+    i18n::Locale,
     models::ApiResponse,
-    notifications::webhook,
+    notifications::events::{PriceAlertCreated, WebhookEvent},
     db::price_alerts,
-    utils::serde::serialize_bigdecimal,
 };
 
 pub async fn create_price_alert(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Json(request): Json<CreatePriceAlertRequest>,
 ) -> Result<Json<ApiResponse<PriceAlert>>, (StatusCode, String)> {
     let alert = price_alerts::create_price_alert(&state.pool, claims.user_id, &request)
@@ -25,46 +26,51 @@ pub async fn create_price_alert(
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error creating price alert: {}", e),
+                locale.t_with("price_alerts.create_error", &[("error", &e)]),
             )
         })?;
 
     // Enviar notificación a través de webhooks
-    let notification_data = json!({
-        "alert_id": alert.id,
-        "target_price": serialize_bigdecimal(&alert.target_price, serde_json::value::Serializer).unwrap(),
-        "condition": alert.condition,
-    });
+    let event = WebhookEvent::new(&PriceAlertCreated::from(&alert)).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            locale.t_with("webhooks.event_error", &[("error", &e)]),
+        )
+    })?;
 
     // No esperamos a que se envíen los webhooks para responder
+    let webhooks = state.webhooks.clone();
+    let user_id = claims.user_id;
     tokio::spawn(async move {
-        if let Err(e) = webhook::send_event_to_webhooks(&state.pool, "price_alert.created", notification_data).await {
-            eprintln!("Error sending webhooks: {:?}", e);
+        if let Err(e) = webhooks.send_event(user_id, &event).await {
+            error!("Error enviando webhooks de la alerta: {}", e);
         }
     });
 
-    Ok(Json(ApiResponse::success(alert)))
+    Ok(Json(ApiResponse::success(alert, locale)))
 }
 
 pub async fn get_price_alerts(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
 ) -> Result<Json<ApiResponse<Vec<PriceAlert>>>, (StatusCode, String)> {
     let alerts = price_alerts::list_price_alerts(&state.pool, claims.user_id)
         .await
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error fetching price alerts: {}", e),
+                locale.t_with("price_alerts.list_error", &[("error", &e)]),
             )
         })?;
 
-    Ok(Json(ApiResponse::success(alerts)))
+    Ok(Json(ApiResponse::success(alerts, locale)))
 }
 
 pub async fn get_price_alert(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Path(id): Path<i32>,
 ) -> Result<Json<ApiResponse<PriceAlert>>, (StatusCode, String)> {
     let alert = price_alerts::get_price_alert(&state.pool, id)
@@ -72,24 +78,25 @@ pub async fn get_price_alert(
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error fetching price alert: {}", e),
+                locale.t_with("price_alerts.fetch_error", &[("error", &e)]),
             )
         })?
-        .ok_or((StatusCode::NOT_FOUND, "Price alert not found".to_string()))?;
+        .ok_or((StatusCode::NOT_FOUND, locale.t("price_alerts.not_found")))?;
 
     if alert.user_id != claims.user_id {
         return Err((
             StatusCode::FORBIDDEN,
-            "Not authorized to access this price alert".to_string(),
+            locale.t("price_alerts.forbidden"),
         ));
     }
 
-    Ok(Json(ApiResponse::success(alert)))
+    Ok(Json(ApiResponse::success(alert, locale)))
 }
 
 pub async fn update_price_alert(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Path(id): Path<i32>,
     Json(request): Json<CreatePriceAlertRequest>,
 ) -> Result<Json<ApiResponse<PriceAlert>>, (StatusCode, String)> {
@@ -98,17 +105,18 @@ pub async fn update_price_alert(
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error updating price alert: {}", e),
+                locale.t_with("price_alerts.update_error", &[("error", &e)]),
             )
         })?
-        .ok_or((StatusCode::NOT_FOUND, "Price alert not found".to_string()))?;
+        .ok_or((StatusCode::NOT_FOUND, locale.t("price_alerts.not_found")))?;
 
-    Ok(Json(ApiResponse::success(alert)))
+    Ok(Json(ApiResponse::success(alert, locale)))
 }
 
 pub async fn delete_price_alert(
     claims: Claims,
     State(state): State<AppState>,
+    locale: Locale,
     Path(id): Path<i32>,
 ) -> Result<Json<ApiResponse<()>>, (StatusCode, String)> {
     let deleted = price_alerts::delete_price_alert(&state.pool, id, claims.user_id)
@@ -116,13 +124,13 @@ pub async fn delete_price_alert(
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error deleting price alert: {}", e),
+                locale.t_with("price_alerts.delete_error", &[("error", &e)]),
             )
         })?;
 
     if !deleted {
-        return Err((StatusCode::NOT_FOUND, "Price alert not found".to_string()));
+        return Err((StatusCode::NOT_FOUND, locale.t("price_alerts.not_found")));
     }
 
-    Ok(Json(ApiResponse::success(())))
+    Ok(Json(ApiResponse::success((), locale)))
 }
\ No newline at end of file
@@ -0,0 +1,32 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+
+use crate::{app_state::AppState, notifications::telegram::Update};
+
+/// Header con el `secret_token` registrado en `setWebhook`.
+const SECRET_TOKEN_HEADER: &str = "x-telegram-bot-api-secret-token";
+
+/// Recibe las actualizaciones del bot cuando Telegram las entrega por
+/// webhook. Sólo está activo con `TELEGRAM_WEBHOOK_SECRET`; sin él, el
+/// dispatcher las lee con long polling.
+pub async fn telegram_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(update): Json<Update>,
+) -> StatusCode {
+    let (Some(bot), Ok(secret)) = (state.telegram.clone(), std::env::var("TELEGRAM_WEBHOOK_SECRET")) else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    let provided = headers.get(SECRET_TOKEN_HEADER).and_then(|v| v.to_str().ok());
+    if provided != Some(secret.as_str()) {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    // Responder enseguida: Telegram reintenta si tardamos
+    tokio::spawn(async move { bot.handle_update(update).await });
+    StatusCode::OK
+}
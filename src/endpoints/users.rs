@@ -1,41 +1,62 @@
 use crate::{
     app_state::AppState,
     auth::jwt::Claims,
-    db::{personal_data, users},
-    models::personal_data::UpdatePersonalDataRequest,
+    db::{personal_data, telegram, users, whatsapp},
+    i18n::Locale,
+    models::{
+        personal_data::{StartPhoneVerificationRequest, UpdatePersonalDataRequest, VerifyPhoneRequest},
+        users::UpdateLocaleRequest,
+    },
+    notifications::{
+        telegram::{generate_link_code, LinkCode, LINK_CODE_TTL_MINUTES},
+        whatsapp::{
+            generate_verification_code, hash_verification_code, normalize_phone_number,
+            VERIFICATION_CODE_TTL_MINUTES, VERIFICATION_MAX_ATTEMPTS, VERIFICATION_MAX_SENDS_PER_DAY,
+        },
+    },
 };
 use axum::{
     extract::State,
-    routing::{get, post},
+    http::HeaderMap,
+    routing::{delete, get, post},
     Json, Router,
 };
 use serde_json::{json, Value as JsonValue};
 use tracing::error;
 
+/// Segundos mínimos entre dos envíos de código de verificación.
+const PHONE_CODE_RESEND_SECS: i64 = 60;
+
 pub fn users_router() -> Router<AppState> {
     Router::new()
         .route("/users/me", get(get_current_user))
         .route("/users/me/personal-data", get(get_personal_data))
         .route("/users/me/personal-data", post(update_personal_data))
+        .route("/users/me/locale", get(get_locale).put(update_locale))
+        .route("/users/me/telegram/link", post(create_telegram_link))
+        .route("/users/me/telegram", delete(unlink_telegram))
+        .route("/users/me/phone", post(start_phone_verification).delete(remove_phone))
+        .route("/users/me/phone/verify", post(verify_phone))
 }
 
 async fn get_current_user(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
 ) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
     let user = users::get_user(&app_state.pool, claims.user_id)
         .await
         .map_err(|e| {
             (
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Error al obtener el usuario: {}", e),
+                locale.t_with("users.fetch_error", &[("error", &e)]),
             )
         })?;
 
     match user {
         Some(user) => Ok(Json(json!({
             "status": "success",
-            "message": "Usuario encontrado",
+            "message": locale.t("users.found"),
             "data": {
                 "id": user.id,
                 "username": user.username,
@@ -44,25 +65,26 @@ async fn get_current_user(
                 "updated_at": user.updated_at
             }
         }))),
-        None => Err((axum::http::StatusCode::NOT_FOUND, "Usuario no encontrado".to_string())),
+        None => Err((axum::http::StatusCode::NOT_FOUND, locale.t("users.not_found"))),
     }
 }
 
 async fn get_personal_data(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
 ) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
     match personal_data::get_personal_data(&app_state.pool, claims.user_id).await {
         Ok(data) => Ok(Json(json!({
             "status": "success",
-            "message": "Datos personales encontrados",
+            "message": locale.t("personal_data.found"),
             "data": data
         }))),
         Err(e) => {
             error!("Error getting personal data: {}", e);
             Err((
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Error interno del servidor".to_string(),
+                locale.t("errors.internal"),
             ))
         }
     }
@@ -71,20 +93,297 @@ async fn get_personal_data(
 async fn update_personal_data(
     State(app_state): State<AppState>,
     claims: Claims,
+    locale: Locale,
     Json(req): Json<UpdatePersonalDataRequest>,
 ) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
     match personal_data::update_personal_data(&app_state.pool, claims.user_id, &req).await {
         Ok(data) => Ok(Json(json!({
             "status": "success",
-            "message": "Datos personales actualizados",
+            "message": locale.t("personal_data.updated"),
             "data": data
         }))),
         Err(e) => {
             error!("Error updating personal data: {}", e);
             Err((
                 axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                "Error interno del servidor".to_string(),
+                locale.t("errors.internal"),
+            ))
+        }
+    }
+}
+/// Genera un código de un solo uso que el usuario envía al bot para
+/// demostrar que el chat de Telegram es suyo.
+async fn create_telegram_link(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
+    let code = generate_link_code();
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(LINK_CODE_TTL_MINUTES);
+
+    if let Err(e) = telegram::create_link_code(&app_state.pool, claims.user_id, &code, expires_at).await {
+        error!("Error creating telegram link code: {}", e);
+        return Err((
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+            locale.t("errors.internal"),
+        ));
+    }
+
+    let deep_link = std::env::var("TELEGRAM_BOT_USERNAME")
+        .ok()
+        .filter(|bot| !bot.is_empty())
+        .map(|bot| format!("https://t.me/{}?start={}", bot.trim_start_matches('@'), code));
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": locale.t("telegram.link_code_created"),
+        "data": LinkCode { code, expires_at, deep_link }
+    })))
+}
+
+async fn unlink_telegram(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
+    match telegram::unlink_telegram(&app_state.pool, claims.user_id).await {
+        Ok(true) => Ok(Json(json!({
+            "status": "success",
+            "message": locale.t("telegram.unlinked"),
+            "data": null
+        }))),
+        Ok(false) => Err((
+            axum::http::StatusCode::NOT_FOUND,
+            locale.t("telegram.not_linked"),
+        )),
+        Err(e) => {
+            error!("Error unlinking telegram: {}", e);
+            Err((
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                locale.t("errors.internal"),
             ))
         }
     }
-}
\ No newline at end of file
+}
+
+/// Idioma guardado (`null` si se usa `Accept-Language`) y el que se
+/// aplica a esta petición.
+async fn get_locale(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
+    let saved = users::get_user_locale(&app_state.pool, claims.user_id)
+        .await
+        .map_err(|e| {
+            error!("Error cargando el idioma del usuario: {}", e);
+            internal_error(locale)
+        })?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": locale.t("response.success"),
+        "data": { "locale": saved, "effective": locale }
+    })))
+}
+
+async fn update_locale(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    headers: HeaderMap,
+    Json(req): Json<UpdateLocaleRequest>,
+) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
+    match users::set_user_locale(&app_state.pool, claims.user_id, req.locale).await {
+        Ok(true) => {
+            // La respuesta ya sale en el idioma nuevo
+            let effective = req.locale.unwrap_or_else(|| Locale::from_headers(&headers));
+            Ok(Json(json!({
+                "status": "success",
+                "message": effective.t("users.locale_updated"),
+                "data": { "locale": req.locale, "effective": effective }
+            })))
+        }
+        Ok(false) => Err((axum::http::StatusCode::NOT_FOUND, locale.t("users.not_found"))),
+        Err(e) => {
+            error!("Error actualizando el idioma del usuario: {}", e);
+            Err(internal_error(locale))
+        }
+    }
+}
+
+fn internal_error(locale: Locale) -> (axum::http::StatusCode, String) {
+    (
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        locale.t("errors.internal"),
+    )
+}
+
+/// Envía un código por WhatsApp al número indicado. El número sólo se
+/// guarda como verificado al confirmar el código.
+async fn start_phone_verification(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Json(req): Json<StartPhoneVerificationRequest>,
+) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
+    let Some(client) = app_state.whatsapp.as_ref() else {
+        return Err((
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            locale.t("whatsapp.not_configured"),
+        ));
+    };
+
+    let phone_number = normalize_phone_number(&req.phone_number).ok_or((
+        axum::http::StatusCode::BAD_REQUEST,
+        locale.t("whatsapp.invalid_phone_number"),
+    ))?;
+
+    let pending = whatsapp::get_phone_verification(&app_state.pool, claims.user_id)
+        .await
+        .map_err(|e| {
+            error!("Error cargando la verificación de teléfono: {}", e);
+            internal_error(locale)
+        })?;
+    if let Some(created_at) = pending.and_then(|p| p.created_at) {
+        if chrono::Utc::now() - created_at < chrono::Duration::seconds(PHONE_CODE_RESEND_SECS) {
+            return Err((
+                axum::http::StatusCode::TOO_MANY_REQUESTS,
+                locale.t("whatsapp.code_resend_too_soon"),
+            ));
+        }
+    }
+
+    let (user_sends, phone_sends) =
+        whatsapp::count_recent_verification_sends(&app_state.pool, claims.user_id, &phone_number)
+            .await
+            .map_err(|e| {
+                error!("Error contando los códigos enviados: {}", e);
+                internal_error(locale)
+            })?;
+    if user_sends.max(phone_sends) >= VERIFICATION_MAX_SENDS_PER_DAY {
+        return Err((
+            axum::http::StatusCode::TOO_MANY_REQUESTS,
+            locale.t("whatsapp.code_daily_limit"),
+        ));
+    }
+
+    let code = generate_verification_code();
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(VERIFICATION_CODE_TTL_MINUTES);
+    whatsapp::create_phone_verification(
+        &app_state.pool,
+        claims.user_id,
+        &phone_number,
+        &hash_verification_code(&code),
+        expires_at,
+    )
+    .await
+    .map_err(|e| {
+        error!("Error creando la verificación de teléfono: {}", e);
+        internal_error(locale)
+    })?;
+
+    // Cuenta aunque el envío falle: cada intento llega a la API de WhatsApp
+    if let Err(e) = whatsapp::record_verification_send(&app_state.pool, claims.user_id, &phone_number).await {
+        error!("Error registrando el envío del código: {}", e);
+        return Err(internal_error(locale));
+    }
+
+    match client.send_verification_code(&phone_number, &code).await {
+        Ok(message_id) => {
+            if let Err(e) = whatsapp::record_message(
+                &app_state.pool,
+                &message_id,
+                claims.user_id,
+                None,
+                whatsapp::MessagePurpose::Verification,
+            )
+            .await
+            {
+                error!("Error registrando mensaje de WhatsApp: {}", e);
+            }
+        }
+        Err(e) => {
+            error!("Error enviando código de verificación por WhatsApp: {}", e);
+            return Err((
+                axum::http::StatusCode::BAD_GATEWAY,
+                locale.t("whatsapp.code_send_error"),
+            ));
+        }
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": locale.t("whatsapp.code_sent"),
+        "data": { "expires_at": expires_at }
+    })))
+}
+
+async fn verify_phone(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+    Json(req): Json<VerifyPhoneRequest>,
+) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
+    let verification = whatsapp::get_phone_verification(&app_state.pool, claims.user_id)
+        .await
+        .map_err(|e| {
+            error!("Error cargando la verificación de teléfono: {}", e);
+            internal_error(locale)
+        })?
+        .filter(|v| !v.is_expired())
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            locale.t("whatsapp.code_not_found"),
+        ))?;
+
+    // Contar el intento y leer el código en la misma sentencia, para que
+    // peticiones concurrentes no superen el límite
+    let code_hash = whatsapp::consume_verification_attempt(&app_state.pool, claims.user_id, VERIFICATION_MAX_ATTEMPTS)
+        .await
+        .map_err(|e| {
+            error!("Error registrando intento de verificación: {}", e);
+            internal_error(locale)
+        })?
+        .ok_or((
+            axum::http::StatusCode::TOO_MANY_REQUESTS,
+            locale.t("whatsapp.code_too_many_attempts"),
+        ))?;
+
+    if hash_verification_code(&req.code) != code_hash {
+        return Err((axum::http::StatusCode::BAD_REQUEST, locale.t("whatsapp.code_incorrect")));
+    }
+
+    if let Err(e) = whatsapp::confirm_phone_number(&app_state.pool, claims.user_id, &verification.phone_number).await {
+        error!("Error confirmando número de teléfono: {}", e);
+        return Err(internal_error(locale));
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": locale.t("whatsapp.phone_verified"),
+        "data": { "phone_number": format!("+{}", verification.phone_number) }
+    })))
+}
+
+async fn remove_phone(
+    State(app_state): State<AppState>,
+    claims: Claims,
+    locale: Locale,
+) -> Result<Json<JsonValue>, (axum::http::StatusCode, String)> {
+    match whatsapp::remove_phone_number(&app_state.pool, claims.user_id).await {
+        Ok(true) => Ok(Json(json!({
+            "status": "success",
+            "message": locale.t("whatsapp.phone_removed"),
+            "data": null
+        }))),
+        Ok(false) => Err((
+            axum::http::StatusCode::NOT_FOUND,
+            locale.t("whatsapp.phone_not_found"),
+        )),
+        Err(e) => {
+            error!("Error eliminando número de teléfono: {}", e);
+            Err(internal_error(locale))
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    app_state::AppState,
+    notifications::whatsapp::{handle_webhook, verify_webhook_signature, WebhookPayload, SIGNATURE_HEADER},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyQuery {
+    #[serde(rename = "hub.mode")]
+    pub mode: Option<String>,
+    #[serde(rename = "hub.verify_token")]
+    pub verify_token: Option<String>,
+    #[serde(rename = "hub.challenge")]
+    pub challenge: Option<String>,
+}
+
+/// Verificación de la suscripción: Meta envía el token configurado en la
+/// app y espera recibir `hub.challenge` de vuelta.
+pub async fn verify_whatsapp_webhook(Query(query): Query<VerifyQuery>) -> Result<String, StatusCode> {
+    let expected = std::env::var("WHATSAPP_WEBHOOK_VERIFY_TOKEN").map_err(|_| StatusCode::NOT_FOUND)?;
+
+    match (query.mode.as_deref(), query.verify_token, query.challenge) {
+        (Some("subscribe"), Some(token), Some(challenge)) if token == expected => Ok(challenge),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+/// Mensajes entrantes y cambios de estado de los mensajes enviados. Sólo
+/// está activo con `WHATSAPP_APP_SECRET`, con el que se firma el cuerpo.
+pub async fn whatsapp_webhook(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let Ok(app_secret) = std::env::var("WHATSAPP_APP_SECRET") else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    let signature = headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if !verify_webhook_signature(&app_secret, &body, signature) {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    match serde_json::from_slice::<WebhookPayload>(&body) {
+        Ok(payload) => {
+            handle_webhook(&state.pool, payload).await;
+            StatusCode::OK
+        }
+        Err(e) => {
+            warn!("Webhook de WhatsApp inválido: {}", e);
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::{collections::HashMap, convert::Infallible, fmt::Display};
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header::ACCEPT_LANGUAGE, request::Parts, HeaderMap},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{app_state::AppState, auth::jwt::Claims, db::users};
+
+lazy_static! {
+    static ref CATALOGS: HashMap<Locale, HashMap<String, String>> = Locale::ALL
+        .into_iter()
+        .map(|locale| {
+            let catalog = serde_json::from_str(locale.catalog_source()).expect("catálogo de mensajes inválido");
+            (locale, catalog)
+        })
+        .collect();
+}
+
+/// Idioma de los mensajes de la API y de las notificaciones. Sin
+/// preferencia ni `Accept-Language` se usa español.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Es,
+    En,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::Es, Locale::En];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::Es => "es",
+            Locale::En => "en",
+        }
+    }
+
+    /// Etiqueta de idioma (`es`, `en-US`, `es_AR`...); sólo cuenta el
+    /// idioma principal.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?.trim();
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.as_str().eq_ignore_ascii_case(primary))
+    }
+
+    /// El idioma soportado con mayor `q` en un `Accept-Language`.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut ranges: Vec<(f32, &str)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let q = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+                Some((q, tag))
+            })
+            .filter(|(q, _)| *q > 0.0)
+            .collect();
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.into_iter().find_map(|(_, tag)| Self::from_tag(tag))
+    }
+
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::from_accept_language)
+            .unwrap_or_default()
+    }
+
+    fn catalog_source(&self) -> &'static str {
+        match self {
+            Locale::Es => include_str!("../../locales/es.json"),
+            Locale::En => include_str!("../../locales/en.json"),
+        }
+    }
+
+    /// Mensaje `key` del catálogo. Si falta se usa el de español y, si
+    /// tampoco está, la clave misma.
+    pub fn t(&self, key: &str) -> String {
+        catalog(*self)
+            .get(key)
+            .or_else(|| catalog(Locale::default()).get(key))
+            .cloned()
+            .unwrap_or_else(|| {
+                warn!("Mensaje sin traducir: {}", key);
+                key.to_string()
+            })
+    }
+
+    /// Como `t`, reemplazando cada `{nombre}` por su argumento.
+    pub fn t_with(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        args.iter().fold(self.t(key), |message, (name, value)| {
+            message.replace(&format!("{{{}}}", name), &value.to_string())
+        })
+    }
+}
+
+pub fn catalog(locale: Locale) -> &'static HashMap<String, String> {
+    &CATALOGS[&locale]
+}
+
+/// El idioma guardado por el usuario autenticado o, si no eligió ninguno
+/// (o la petición es anónima), el de `Accept-Language`.
+#[async_trait]
+impl<S> FromRequestParts<S> for Locale
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Ok(claims) = Claims::from_request_parts(parts, state).await {
+            let app_state = AppState::from_ref(state);
+            if let Ok(Some(locale)) = users::get_user_locale(&app_state.pool, claims.user_id).await {
+                return Ok(locale);
+            }
+        }
+
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,89 @@
+use std::{collections::BTreeSet, path::Path};
+
+use super::*;
+
+fn placeholders(message: &str) -> BTreeSet<&str> {
+    message
+        .split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+        .collect()
+}
+
+#[test]
+fn test_catalogs_have_the_same_keys_and_placeholders() {
+    let spanish = catalog(Locale::Es);
+    for locale in Locale::ALL {
+        let other = catalog(locale);
+        let missing: Vec<_> = spanish.keys().filter(|key| !other.contains_key(*key)).collect();
+        let extra: Vec<_> = other.keys().filter(|key| !spanish.contains_key(*key)).collect();
+        assert!(missing.is_empty(), "faltan en {}: {:?}", locale.as_str(), missing);
+        assert!(extra.is_empty(), "faltan en es: {:?}", extra);
+
+        for (key, message) in other {
+            assert_eq!(
+                placeholders(message),
+                placeholders(&spanish[key]),
+                "argumentos distintos en {} para {}",
+                locale.as_str(),
+                key
+            );
+        }
+    }
+}
+
+/// Toda clave que se usa con `t("...")` o `t_with("...", ...)` en el código
+/// o en las plantillas tiene que estar en los catálogos.
+#[test]
+fn test_every_used_key_is_in_the_catalogs() {
+    fn collect(dir: &Path, keys: &mut BTreeSet<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                collect(&path, keys);
+                continue;
+            }
+            // Este archivo prueba claves inexistentes a propósito
+            if path.ends_with("i18n/tests.rs") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap_or_default();
+            for call in [".t(", " t(", ".t_with("] {
+                for (start, _) in source.match_indices(call) {
+                    let args = source[start + call.len()..].trim_start();
+                    let key = args.strip_prefix('"').and_then(|rest| rest.split_once('"'));
+                    if let Some((key, _)) = key.filter(|(key, _)| key.contains('.')) {
+                        keys.insert(key.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut keys = BTreeSet::new();
+    collect(&root.join("src"), &mut keys);
+    collect(&root.join("templates"), &mut keys);
+
+    assert!(keys.contains("response.success"));
+    for locale in Locale::ALL {
+        let missing: Vec<_> = keys.iter().filter(|key| !catalog(locale).contains_key(*key)).collect();
+        assert!(missing.is_empty(), "faltan en {}: {:?}", locale.as_str(), missing);
+    }
+}
+
+#[test]
+fn test_accept_language_and_lookup() {
+    assert_eq!(Locale::from_accept_language("en-US,en;q=0.9,es;q=0.8"), Some(Locale::En));
+    assert_eq!(Locale::from_accept_language("fr-FR, es;q=0.5, en;q=0.7"), Some(Locale::En));
+    assert_eq!(Locale::from_accept_language("es_AR"), Some(Locale::Es));
+    assert_eq!(Locale::from_accept_language("fr, de;q=0.5"), None);
+    assert_eq!(Locale::from_accept_language("en;q=0, es"), Some(Locale::Es));
+
+    assert_eq!(Locale::En.t("auth.invalid_credentials"), "Invalid credentials");
+    assert_eq!(
+        Locale::Es.t_with("candles.csv_number", &[("line", &3), ("value", &"x")]),
+        "Línea 3: número inválido 'x'"
+    );
+    assert_eq!(Locale::En.t("no.existe"), "no.existe");
+}
@@ -24,16 +24,31 @@ use endpoints::{
         get_users,
         get_all_asset_pairs,
         get_all_alerts,
+        deactivate_user,
+        get_connections,
     },
+    instruments::{list_instruments, sync_instruments},
+    market::{get_candles, import_candles, backfill_candles},
+    notifications::notifications_router,
+    telegram::telegram_webhook,
+    users::users_router,
+    whatsapp::{verify_whatsapp_webhook, whatsapp_webhook},
 };
 use tower_http::cors::{Any, CorsLayer};
 use crate::auth::{middleware::auth, admin::require_admin};
+use tracing::{error, info};
+use crate::notifications::{dispatcher::build_dispatcher, ticker::run_ticker_feed, websocket::ws_handler};
 
+pub mod api;
+pub mod app_state;
 pub mod auth;
 pub mod config;
 pub mod db;
 pub mod endpoints;
+pub mod i18n;
 pub mod models;
+pub mod notifications;
+pub mod utils;
 
 pub async fn create_router(pool: sqlx::PgPool) -> Router {
     let cors = CorsLayer::new()
@@ -41,12 +56,51 @@ pub async fn create_router(pool: sqlx::PgPool) -> Router {
         .allow_headers(Any)
         .allow_origin(Any);
 
-    let app_state = AppState { pool };
+    let app_state = AppState::new(pool);
+
+    // Entregar a los sockets locales lo que se publique en el bus
+    app_state.ws_server.spawn_bus_listener(app_state.bus.clone());
+
+    // Sin Redis la cola y el bus sólo existen en este proceso y el binario
+    // `dispatcher` no los vería, así que las notificaciones se reparten aquí
+    if !app_state.queue.is_shared() {
+        info!("Cola de notificaciones en memoria, dispatcher en el mismo proceso");
+        let dispatcher = build_dispatcher(app_state.pool.clone(), app_state.queue.clone(), app_state.bus.clone());
+        tokio::spawn(dispatcher.run(std::future::pending()));
+    }
+
+    // Catálogo de instrumentos al arrancar: sin él no se pueden validar pares
+    // ni alertas hasta que un admin llame a /admin/instruments/sync
+    let pool = app_state.pool.clone();
+    tokio::spawn(async move {
+        let source = api::exchange_info::InstrumentSource::from_env();
+        for exchange in models::api_keys::SUPPORTED_EXCHANGES {
+            match api::exchange_info::sync_instruments(&pool, exchange, &source).await {
+                Ok(count) => info!("Catálogo de {} sincronizado: {} instrumentos", exchange, count),
+                Err(e) => error!("Error sincronizando el catálogo de {}: {}", exchange, e),
+            }
+        }
+    });
+
+    // Tickers en tiempo real para los clientes WebSocket
+    app_state
+        .ws_server
+        .spawn_ticker_fanout(std::time::Duration::from_millis(500));
+    tokio::spawn(run_ticker_feed(
+        app_state.ws_server.clone(),
+        std::time::Duration::from_secs(2),
+    ));
 
     // Rutas públicas
     let public_routes = Router::new()
         .route("/users/register", post(register))
-        .route("/users/login", post(login));
+        .route("/users/login", post(login))
+        // El WebSocket se autentica con el JWT en la query o en el primer mensaje
+        .route("/ws", get(ws_handler))
+        // Telegram se autentica con el secret token del webhook
+        .route("/telegram/webhook", post(telegram_webhook))
+        // WhatsApp firma el cuerpo con el app secret
+        .route("/whatsapp/webhook", get(verify_whatsapp_webhook).post(whatsapp_webhook));
 
     // Rutas protegidas
     let protected_routes = Router::new()
@@ -70,13 +124,22 @@ pub async fn create_router(pool: sqlx::PgPool) -> Router {
                 .put(update_price_alert)
                 .delete(delete_price_alert),
         )
+        .route("/instruments", get(list_instruments))
+        .route("/market/candles", get(get_candles))
+        // Perfil, idioma, vinculación de Telegram y verificación de teléfono
+        .merge(users_router())
         .layer(middleware::from_fn(auth));
 
     // Rutas de administrador
     let admin_routes = Router::new()
         .route("/admin/users", get(get_users))
+        .route("/admin/users/:id/deactivate", post(deactivate_user))
+        .route("/admin/connections", get(get_connections))
         .route("/admin/asset-pairs", get(get_all_asset_pairs))
         .route("/admin/price-alerts", get(get_all_alerts))
+        .route("/admin/instruments/sync", post(sync_instruments))
+        .route("/admin/candles/import", post(import_candles))
+        .route("/admin/candles/backfill", post(backfill_candles))
         .layer(middleware::from_fn_with_state(
             app_state.clone(),
             require_admin,
@@ -88,6 +151,8 @@ pub async fn create_router(pool: sqlx::PgPool) -> Router {
         .merge(public_routes)
         .merge(protected_routes)
         .merge(admin_routes)
+        // Cada handler de notificaciones extrae los Claims del token
+        .nest("/notifications", notifications_router())
         .layer(cors)
         .with_state(app_state)
 }
\ No newline at end of file
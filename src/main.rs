@@ -1,6 +1,6 @@
 use std::net::SocketAddr;
 use dotenv::dotenv;
-use my_rust_api::{create_router, db::init::{init_pool, init_database}};
+use my_rust_api::{create_router, db::{candles, init::{init_pool, init_database}}};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -25,6 +25,18 @@ async fn main() {
     // Initialize database schema
     init_database(&pool).await.expect("Failed to initialize database");
 
+    // Purge old candles according to each timeframe's retention policy
+    let retention_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            if let Err(e) = candles::apply_retention(&retention_pool).await {
+                tracing::error!("Error applying candle retention: {}", e);
+            }
+        }
+    });
+
     // Create router
     let app = create_router(pool).await;
 
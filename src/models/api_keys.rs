@@ -29,9 +29,11 @@ pub struct UpdateApiKeyRequest {
     pub is_active: Option<bool>,
 }
 
+pub const SUPPORTED_EXCHANGES: [&str; 3] = ["binance", "kucoin", "bybit"];
+
 // Validaciones
 pub fn is_valid_exchange(exchange: &str) -> bool {
-    matches!(exchange.to_lowercase().as_str(), "binance" | "kucoin" | "bybit")
+    SUPPORTED_EXCHANGES.contains(&exchange.to_lowercase().as_str())
 }
 
 pub fn validate_permissions(permissions: &[String]) -> bool {
@@ -0,0 +1,273 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use sqlx::FromRow;
+
+use crate::{
+    i18n::Locale,
+    utils::serde::{deserialize_bigdecimal, serialize_bigdecimal},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Timeframe {
+    #[serde(rename = "1m")]
+    M1,
+    #[serde(rename = "5m")]
+    M5,
+    #[serde(rename = "15m")]
+    M15,
+    #[serde(rename = "1h")]
+    H1,
+    #[serde(rename = "4h")]
+    H4,
+    #[serde(rename = "1d")]
+    D1,
+}
+
+impl Timeframe {
+    pub const ALL: [Timeframe; 6] = [
+        Timeframe::M1,
+        Timeframe::M5,
+        Timeframe::M15,
+        Timeframe::H1,
+        Timeframe::H4,
+        Timeframe::D1,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Timeframe::M1 => "1m",
+            Timeframe::M5 => "5m",
+            Timeframe::M15 => "15m",
+            Timeframe::H1 => "1h",
+            Timeframe::H4 => "4h",
+            Timeframe::D1 => "1d",
+        }
+    }
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Timeframe::M1 => 60,
+            Timeframe::M5 => 5 * 60,
+            Timeframe::M15 => 15 * 60,
+            Timeframe::H1 => 60 * 60,
+            Timeframe::H4 => 4 * 60 * 60,
+            Timeframe::D1 => 24 * 60 * 60,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(self.seconds())
+    }
+
+    /// Inicio de la vela que contiene `time` (velas alineadas a UTC).
+    pub fn bucket_start(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let seconds = self.seconds();
+        let ts = time.timestamp();
+        Utc.timestamp_opt(ts - ts.rem_euclid(seconds), 0).unwrap()
+    }
+
+    /// Días que se conservan las velas de este timeframe. `None` significa
+    /// que no se borran nunca. Se puede sobreescribir con
+    /// `CANDLES_RETENTION_<TF>_DAYS` (por ejemplo `CANDLES_RETENTION_1M_DAYS`).
+    pub fn retention_days(&self) -> Option<i64> {
+        let env_key = format!("CANDLES_RETENTION_{}_DAYS", self.as_str().to_uppercase());
+        if let Some(days) = std::env::var(env_key).ok().and_then(|v| v.parse::<i64>().ok()) {
+            return (days > 0).then_some(days);
+        }
+
+        match self {
+            Timeframe::M1 => Some(30),
+            Timeframe::M5 => Some(90),
+            Timeframe::M15 => Some(180),
+            Timeframe::H1 => Some(730),
+            Timeframe::H4 | Timeframe::D1 => None,
+        }
+    }
+}
+
+impl fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Timeframe {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Timeframe::ALL
+            .iter()
+            .find(|tf| tf.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Timeframe inválido: {}", s))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Candle {
+    pub instrument_id: i32,
+    pub timeframe: String,
+    pub open_time: DateTime<Utc>,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub open: BigDecimal,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub high: BigDecimal,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub low: BigDecimal,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub close: BigDecimal,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub volume: BigDecimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CandleQuery {
+    pub exchange: String,
+    pub symbol: String,
+    pub timeframe: String,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CandleImportQuery {
+    pub exchange: String,
+    pub symbol: String,
+    pub timeframe: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BackfillRequest {
+    pub exchange: String,
+    pub symbol: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CandlePage {
+    pub candles: Vec<Candle>,
+    /// Valor de `start` para pedir la página siguiente, si la hay.
+    pub next_start: Option<DateTime<Utc>>,
+}
+
+/// Agrega velas de un timeframe menor (normalmente 1m) en velas de
+/// `target`. Las velas de entrada deben estar ordenadas por `open_time`.
+pub fn aggregate_candles(candles: &[Candle], target: Timeframe) -> Vec<Candle> {
+    let mut aggregated: Vec<Candle> = Vec::new();
+
+    for candle in candles {
+        let bucket = target.bucket_start(candle.open_time);
+
+        match aggregated.last_mut() {
+            Some(current) if current.open_time == bucket => {
+                if candle.high > current.high {
+                    current.high = candle.high.clone();
+                }
+                if candle.low < current.low {
+                    current.low = candle.low.clone();
+                }
+                current.close = candle.close.clone();
+                current.volume = &current.volume + &candle.volume;
+            }
+            _ => aggregated.push(Candle {
+                instrument_id: candle.instrument_id,
+                timeframe: target.as_str().to_string(),
+                open_time: bucket,
+                open: candle.open.clone(),
+                high: candle.high.clone(),
+                low: candle.low.clone(),
+                close: candle.close.clone(),
+                volume: candle.volume.clone(),
+            }),
+        }
+    }
+
+    aggregated
+}
+
+/// Devuelve los rangos `[desde, hasta)` sin velas entre `start` y `end`.
+/// Las velas deben estar ordenadas por `open_time`.
+pub fn find_gaps(
+    candles: &[Candle],
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let step = timeframe.duration();
+    let mut gaps = Vec::new();
+    let mut expected = timeframe.bucket_start(start);
+
+    for candle in candles {
+        if candle.open_time < expected {
+            continue;
+        }
+        if candle.open_time >= end {
+            break;
+        }
+        if candle.open_time > expected {
+            gaps.push((expected, candle.open_time));
+        }
+        expected = candle.open_time + step;
+    }
+
+    if expected < end {
+        gaps.push((expected, end));
+    }
+
+    gaps
+}
+
+/// Parsea un CSV con cabecera `open_time,open,high,low,close,volume`. El
+/// `open_time` puede ser RFC 3339 o un timestamp en milisegundos.
+pub fn parse_candles_csv(
+    instrument_id: i32,
+    timeframe: Timeframe,
+    content: &str,
+    locale: Locale,
+) -> Result<Vec<Candle>, String> {
+    let mut candles = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (index == 0 && line.to_lowercase().starts_with("open_time")) {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() < 6 {
+            return Err(locale.t_with("candles.csv_columns", &[("line", &(index + 1))]));
+        }
+
+        let open_time = match fields[0].parse::<i64>() {
+            Ok(millis) => Utc.timestamp_millis_opt(millis).single(),
+            Err(_) => DateTime::parse_from_rfc3339(fields[0])
+                .ok()
+                .map(|dt| dt.with_timezone(&Utc)),
+        }
+        .ok_or_else(|| locale.t_with("candles.csv_open_time", &[("line", &(index + 1))]))?;
+
+        let number = |value: &str| {
+            BigDecimal::from_str(value)
+                .map_err(|_| locale.t_with("candles.csv_number", &[("line", &(index + 1)), ("value", &value)]))
+        };
+
+        candles.push(Candle {
+            instrument_id,
+            timeframe: timeframe.as_str().to_string(),
+            open_time: timeframe.bucket_start(open_time),
+            open: number(fields[1])?,
+            high: number(fields[2])?,
+            low: number(fields[3])?,
+            close: number(fields[4])?,
+            volume: number(fields[5])?,
+        });
+    }
+
+    candles.sort_by_key(|c| c.open_time);
+    Ok(candles)
+}
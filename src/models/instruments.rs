@@ -0,0 +1,170 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use sqlx::FromRow;
+
+use crate::utils::serde::{
+    deserialize_bigdecimal, deserialize_option_bigdecimal, serialize_bigdecimal,
+    serialize_option_bigdecimal,
+};
+
+/// Quote assets conocidos, ordenados de mayor a menor longitud para poder
+/// separar símbolos concatenados como `BTCUSDT` o `ETHFDUSD`.
+const KNOWN_QUOTE_ASSETS: &[&str] = &[
+    "FDUSD", "USDT", "USDC", "BUSD", "TUSD", "USDP", "EUR", "USD", "TRY", "DAI", "BTC", "ETH",
+    "BNB",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Instrument {
+    pub id: i32,
+    pub exchange: String,
+    pub symbol: String,
+    pub exchange_symbol: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub tick_size: BigDecimal,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub lot_size: BigDecimal,
+    #[serde(serialize_with = "serialize_option_bigdecimal", deserialize_with = "deserialize_option_bigdecimal")]
+    pub min_notional: Option<BigDecimal>,
+    pub status: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Instrumento tal como lo devuelve el endpoint de exchange-info (o un
+/// fixture), antes de guardarlo en la base de datos.
+#[derive(Debug, Clone)]
+pub struct NewInstrument {
+    pub exchange: String,
+    pub exchange_symbol: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub tick_size: BigDecimal,
+    pub lot_size: BigDecimal,
+    pub min_notional: Option<BigDecimal>,
+    pub status: String,
+}
+
+impl NewInstrument {
+    pub fn symbol(&self) -> String {
+        canonical_symbol(&self.base_asset, &self.quote_asset)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InstrumentQuery {
+    pub exchange: Option<String>,
+    pub quote_asset: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncInstrumentsRequest {
+    pub exchange: String,
+}
+
+/// Formato canónico usado en la API: `BASE/QUOTE` en mayúsculas.
+pub fn canonical_symbol(base: &str, quote: &str) -> String {
+    format!("{}/{}", base.trim().to_uppercase(), quote.trim().to_uppercase())
+}
+
+/// Convierte un par al formato nativo de cada exchange.
+pub fn exchange_symbol(exchange: &str, base: &str, quote: &str) -> String {
+    let base = base.trim().to_uppercase();
+    let quote = quote.trim().to_uppercase();
+    match exchange.to_lowercase().as_str() {
+        "kucoin" => format!("{}-{}", base, quote),
+        _ => format!("{}{}", base, quote),
+    }
+}
+
+/// Separa un símbolo en `(base, quote)` aceptando `BTCUSDT`, `BTC-USDT`,
+/// `BTC_USDT` y `BTC/USDT` (sin distinguir mayúsculas).
+pub fn split_symbol(symbol: &str) -> Option<(String, String)> {
+    let symbol = symbol.trim().to_uppercase();
+
+    if let Some((base, quote)) = symbol.split_once(|c| c == '/' || c == '-' || c == '_') {
+        if base.is_empty() || quote.is_empty() {
+            return None;
+        }
+        return Some((base.to_string(), quote.to_string()));
+    }
+
+    KNOWN_QUOTE_ASSETS
+        .iter()
+        .filter(|quote| symbol.len() > quote.len() && symbol.ends_with(*quote))
+        .map(|quote| {
+            let base = &symbol[..symbol.len() - quote.len()];
+            (base.to_string(), quote.to_string())
+        })
+        .next()
+}
+
+/// Normaliza un par recibido como `base_asset`/`quote_asset`. Si el cliente
+/// envía el símbolo completo en `base_asset` y deja `quote_asset` vacío, se
+/// intenta separarlo.
+pub fn normalize_pair(base_asset: &str, quote_asset: &str) -> Option<(String, String)> {
+    let base = base_asset.trim();
+    let quote = quote_asset.trim();
+
+    if quote.is_empty() {
+        return split_symbol(base);
+    }
+    if base.is_empty() {
+        return None;
+    }
+
+    Some((base.to_uppercase(), quote.to_uppercase()))
+}
+
+/// Devuelve hasta `limit` símbolos del catálogo parecidos a `base/quote`,
+/// priorizando los que comparten base o quote.
+pub fn suggest_symbols(
+    instruments: &[Instrument],
+    base: &str,
+    quote: &str,
+    limit: usize,
+) -> Vec<String> {
+    let wanted = format!("{}{}", base, quote);
+
+    let mut scored: Vec<(usize, &Instrument)> = instruments
+        .iter()
+        .filter_map(|instrument| {
+            let candidate = format!("{}{}", instrument.base_asset, instrument.quote_asset);
+            let mut score = edit_distance(&wanted, &candidate);
+            if instrument.base_asset == base {
+                score = score.saturating_sub(2);
+            }
+            if instrument.quote_asset == quote {
+                score = score.saturating_sub(1);
+            }
+            (score <= 3).then_some((score, instrument))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.symbol.cmp(&b.1.symbol)));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, instrument)| instrument.symbol.clone())
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
@@ -4,9 +4,13 @@ pub mod asset_pairs;
 pub mod price_alerts;
 pub mod personal_data;
 pub mod api_keys;
+pub mod instruments;
+pub mod candles;
 
 use serde::Serialize;
 
+use crate::i18n::Locale;
+
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T>
 where
@@ -21,19 +25,23 @@ impl<T> ApiResponse<T>
 where
     T: Serialize,
 {
-    pub fn success(data: T) -> Self {
+    pub fn success(data: T, locale: Locale) -> Self {
         Self {
             status: "success".to_string(),
-            message: "Operation successful".to_string(),
+            message: locale.t("response.success"),
             data: Some(data),
         }
     }
 
-    pub fn error(message: String) -> Self {
+    /// `key` es la clave del mensaje en el catálogo.
+    pub fn error(key: &str, locale: Locale) -> Self {
         Self {
             status: "error".to_string(),
-            message,
+            message: locale.t(key),
             data: None,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests;
@@ -1,36 +1,328 @@
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
 use sqlx::FromRow;
 use serde_json::Value as JsonValue;
+use sqlx::types::Json;
+use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+use crate::{
+    i18n::Locale,
+    notifications::{dispatcher::ChannelKind, NotificationPriority, NotificationType},
+};
+
+/// Interruptores generales por canal y por tipo de notificación. Las
+/// reglas (`NotificationRule`) los ajustan por combinación de tipo, canal y
+/// activo.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct NotificationPreference {
     pub user_id: i32,
     pub email_enabled: bool,
     pub telegram_enabled: bool,
     pub whatsapp_enabled: bool,
+    pub web_push_enabled: bool,
     pub price_alerts_enabled: bool,
+    pub market_sentiment_enabled: bool,
+    pub strategy_updates_enabled: bool,
+    pub trade_execution_enabled: bool,
     pub system_alerts_enabled: bool,
+    /// Prioridad mínima que tiene que tener una notificación para salir por
+    /// cada canal; los canales ausentes aceptan todas.
+    pub min_priority: Json<HashMap<ChannelKind, NotificationPriority>>,
+    /// Zona horaria IANA (`Europe/Madrid`) para las horas de silencio y los
+    /// resúmenes diarios.
+    pub timezone: String,
+    /// Horas de silencio en hora local; el intervalo puede cruzar la
+    /// medianoche (22:00–07:00).
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+    /// Canales que agrupan las notificaciones en resúmenes en lugar de
+    /// enviarlas al momento.
+    pub digest: Json<HashMap<ChannelKind, DigestMode>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl NotificationPreference {
+    /// Valores por defecto de la tabla, para usuarios sin preferencias guardadas.
+    pub fn defaults(user_id: i32) -> Self {
+        let now = Utc::now();
+        Self {
+            user_id,
+            email_enabled: true,
+            telegram_enabled: true,
+            whatsapp_enabled: false,
+            web_push_enabled: true,
+            price_alerts_enabled: true,
+            market_sentiment_enabled: true,
+            strategy_updates_enabled: true,
+            trade_execution_enabled: true,
+            system_alerts_enabled: true,
+            min_priority: Json(HashMap::new()),
+            timezone: "UTC".to_string(),
+            quiet_hours_start: None,
+            quiet_hours_end: None,
+            digest: Json(HashMap::new()),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Interruptor general del canal. El WebSocket (bandeja in-app) y los
+    /// webhooks no tienen: los webhooks se filtran por su propia
+    /// configuración.
+    pub fn channel_enabled(&self, channel: ChannelKind) -> bool {
+        match channel {
+            ChannelKind::WebSocket | ChannelKind::Webhook => true,
+            ChannelKind::Email => self.email_enabled,
+            ChannelKind::Telegram => self.telegram_enabled,
+            ChannelKind::WhatsApp => self.whatsapp_enabled,
+            ChannelKind::WebPush => self.web_push_enabled,
+        }
+    }
+
+    pub fn type_enabled(&self, notification_type: &NotificationType) -> bool {
+        match notification_type {
+            NotificationType::PriceAlert => self.price_alerts_enabled,
+            NotificationType::MarketSentiment => self.market_sentiment_enabled,
+            NotificationType::StrategyUpdate => self.strategy_updates_enabled,
+            NotificationType::TradeExecution => self.trade_execution_enabled,
+            NotificationType::SystemAlert => self.system_alerts_enabled,
+        }
+    }
+
+    pub fn min_priority_for(&self, channel: ChannelKind) -> NotificationPriority {
+        self.min_priority.get(&channel).copied().unwrap_or(NotificationPriority::Low)
+    }
+
+    /// La zona guardada, o UTC si no es válida.
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    pub fn quiet_hours(&self) -> Option<(NaiveTime, NaiveTime)> {
+        self.quiet_hours_start.zip(self.quiet_hours_end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestMode {
+    /// Al comienzo de cada hora.
+    Hourly,
+    /// Una vez al día, a las 8:00 hora local.
+    Daily,
+}
+
+/// Horas de silencio; ambas a `null` las desactiva.
+#[derive(Debug, Deserialize)]
+pub struct QuietHours {
+    pub start: Option<NaiveTime>,
+    pub end: Option<NaiveTime>,
+}
+
+/// Campos a modificar de las preferencias; los ausentes se mantienen.
+/// `min_priority` reemplaza el mapa completo.
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdatePreferencesRequest {
+    pub email_enabled: Option<bool>,
+    pub telegram_enabled: Option<bool>,
+    pub whatsapp_enabled: Option<bool>,
+    pub web_push_enabled: Option<bool>,
+    pub price_alerts_enabled: Option<bool>,
+    pub market_sentiment_enabled: Option<bool>,
+    pub strategy_updates_enabled: Option<bool>,
+    pub trade_execution_enabled: Option<bool>,
+    pub system_alerts_enabled: Option<bool>,
+    pub min_priority: Option<HashMap<ChannelKind, NotificationPriority>>,
+    pub timezone: Option<String>,
+    pub quiet_hours: Option<QuietHours>,
+    /// Reemplaza el mapa completo; `{}` vuelve al envío inmediato.
+    pub digest: Option<HashMap<ChannelKind, DigestMode>>,
+}
+
+impl UpdatePreferencesRequest {
+    pub fn validate(&self, locale: Locale) -> Result<(), String> {
+        if let Some(timezone) = &self.timezone {
+            timezone
+                .parse::<Tz>()
+                .map_err(|_| locale.t_with("preferences.unknown_timezone", &[("timezone", timezone)]))?;
+        }
+        if let Some(quiet_hours) = &self.quiet_hours {
+            match (quiet_hours.start, quiet_hours.end) {
+                (Some(start), Some(end)) if start == end => {
+                    return Err(locale.t("preferences.quiet_hours_empty"));
+                }
+                (Some(_), None) | (None, Some(_)) => {
+                    return Err(locale.t("preferences.quiet_hours_incomplete"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Excepción al interruptor del tipo de notificación; los de canal se
+/// respetan siempre. Los campos vacíos valen para cualquier tipo, canal o
+/// activo; si varias reglas coinciden gana la más específica (el activo
+/// pesa más que el tipo y éste más que el canal).
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NotificationRule {
+    pub id: i32,
+    pub user_id: i32,
+    pub notification_type: Option<String>,
+    pub channel: Option<String>,
+    pub asset: Option<String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NotificationRule {
+    pub fn matches(&self, channel: ChannelKind, notification_type: &NotificationType, asset: Option<&str>) -> bool {
+        self.notification_type.as_deref().map_or(true, |t| t == notification_type.as_str())
+            && self.channel.as_deref().map_or(true, |c| c == channel.as_str())
+            && self.asset.as_deref().map_or(true, |a| Some(a) == asset)
+    }
+
+    pub fn specificity(&self) -> u8 {
+        (self.asset.is_some() as u8) << 2
+            | (self.notification_type.is_some() as u8) << 1
+            | self.channel.is_some() as u8
+    }
+}
+
+/// Crea la regla o, si ya hay una para la misma combinación, cambia su
+/// `enabled`. Por ejemplo `{"asset": "DOGE", "enabled": false}` silencia
+/// todo lo relativo a DOGE.
+#[derive(Debug, Deserialize)]
+pub struct CreateNotificationRuleRequest {
+    pub notification_type: Option<String>,
+    pub channel: Option<String>,
+    pub asset: Option<String>,
+    pub enabled: bool,
+}
+
+/// Formato del receptor: `generic` recibe el sobre de eventos firmado;
+/// `discord` y `slack` son incoming webhooks de esas plataformas y reciben
+/// un mensaje nativo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    #[default]
+    Generic,
+    Discord,
+    Slack,
+}
+
+impl WebhookKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookKind::Generic => "generic",
+            WebhookKind::Discord => "discord",
+            WebhookKind::Slack => "slack",
+        }
+    }
+}
+
+impl From<String> for WebhookKind {
+    fn from(kind: String) -> Self {
+        match kind.as_str() {
+            "discord" => WebhookKind::Discord,
+            "slack" => WebhookKind::Slack,
+            _ => WebhookKind::Generic,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct WebhookConfig {
     pub id: uuid::Uuid,
     pub user_id: i32,
+    #[sqlx(try_from = "String")]
+    pub kind: WebhookKind,
     pub url: String,
     pub secret: String,
     pub enabled: bool,
     #[serde(rename = "notification_types")]
     pub notification_types: JsonValue,
+    /// Secreto anterior a la última rotación; mientras no expire se firma
+    /// también con él.
+    #[serde(skip_serializing)]
+    pub previous_secret: Option<String>,
+    pub previous_secret_expires_at: Option<DateTime<Utc>>,
+    /// Versión del sobre de eventos que recibe el webhook.
+    pub api_version: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateWebhookRequest {
+    #[serde(default)]
+    pub kind: WebhookKind,
     pub url: String,
-    pub secret: String,
+    /// Si no se envía, el servidor genera uno.
+    pub secret: Option<String>,
+    /// Tipos de evento suscritos; admite comodines como `price_alert.*`.
     pub notification_types: Vec<String>,
+    /// Por defecto, la última versión del catálogo de eventos.
+    pub api_version: Option<i32>,
+}
+
+/// Campos a modificar de un webhook; los ausentes se mantienen.
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub notification_types: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    pub api_version: Option<i32>,
+}
+
+/// Un intento de entrega de un webhook. Los reintentos de una misma
+/// notificación comparten `delivery_id`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: uuid::Uuid,
+    pub delivery_id: uuid::Uuid,
+    pub webhook_id: uuid::Uuid,
+    pub user_id: i32,
+    pub attempt: i32,
+    /// `succeeded`, `failed` (definitivo) o `retry_scheduled`.
+    pub status: String,
+    pub status_code: Option<i32>,
+    pub latency_ms: Option<i32>,
+    pub response_snippet: Option<String>,
+    pub error: Option<String>,
+    #[serde(skip_serializing)]
+    pub payload: String,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Suscripción de Web Push de un navegador.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PushSubscription {
+    pub id: i32,
+    pub user_id: i32,
+    pub endpoint: String,
+    #[serde(skip_serializing)]
+    pub p256dh: String,
+    #[serde(skip_serializing)]
+    pub auth: String,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PushSubscriptionKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// Mismo formato que `PushSubscription.toJSON()` en el navegador.
+#[derive(Debug, Deserialize)]
+pub struct CreatePushSubscriptionRequest {
+    pub endpoint: String,
+    pub keys: PushSubscriptionKeys,
 }
@@ -20,3 +20,14 @@ pub struct CreatePersonalDataRequest {
 pub struct UpdatePersonalDataRequest {
     pub email: String,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StartPhoneVerificationRequest {
+    /// En formato internacional, p. ej. `+54 9 11 1234-5678`.
+    pub phone_number: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyPhoneRequest {
+    pub code: String,
+}
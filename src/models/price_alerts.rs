@@ -1,7 +1,9 @@
 use chrono::{DateTime, Utc};
 use sqlx::types::BigDecimal;
 use serde::{Deserialize, Serialize};
-use crate::utils::serde::{serialize_bigdecimal, deserialize_bigdecimal};
+use crate::utils::serde::{
+    deserialize_bigdecimal, deserialize_option_bigdecimal, serialize_bigdecimal, serialize_option_bigdecimal,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreatePriceAlertRequest {
@@ -9,6 +11,12 @@ pub struct CreatePriceAlertRequest {
     pub target_price: BigDecimal,
     pub condition: String,
     pub asset: String,
+    #[serde(
+        default,
+        serialize_with = "serialize_option_bigdecimal",
+        deserialize_with = "deserialize_option_bigdecimal"
+    )]
+    pub trigger_price: Option<BigDecimal>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,6 +27,10 @@ pub struct PriceAlert {
     pub target_price: BigDecimal,
     pub condition: String,
     pub asset: String,
+    #[serde(serialize_with = "serialize_option_bigdecimal", deserialize_with = "deserialize_option_bigdecimal")]
+    pub trigger_price: Option<BigDecimal>,
+    /// Mientras esté en el futuro la alerta no se evalúa.
+    pub snoozed_until: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use chrono::{TimeZone, Utc};
+use sqlx::types::BigDecimal;
+
+use super::candles::{aggregate_candles, find_gaps, Candle, Timeframe};
+use super::instruments::{canonical_symbol, exchange_symbol, normalize_pair, split_symbol};
+
+#[test]
+fn test_split_symbol_formats() {
+    let expected = Some(("BTC".to_string(), "USDT".to_string()));
+
+    assert_eq!(split_symbol("BTCUSDT"), expected);
+    assert_eq!(split_symbol("btc-usdt"), expected);
+    assert_eq!(split_symbol("BTC/USDT"), expected);
+    assert_eq!(split_symbol("BTC_USDT"), expected);
+    assert_eq!(split_symbol("ETHFDUSD"), Some(("ETH".to_string(), "FDUSD".to_string())));
+    assert_eq!(split_symbol("USDT"), None);
+    assert_eq!(split_symbol("BTC/"), None);
+}
+
+#[test]
+fn test_normalize_pair() {
+    assert_eq!(
+        normalize_pair(" btc ", "usdt"),
+        Some(("BTC".to_string(), "USDT".to_string()))
+    );
+    assert_eq!(
+        normalize_pair("ETH-BTC", ""),
+        Some(("ETH".to_string(), "BTC".to_string()))
+    );
+    assert_eq!(normalize_pair("", "USDT"), None);
+}
+
+#[test]
+fn test_exchange_symbol_format() {
+    assert_eq!(canonical_symbol("btc", "usdt"), "BTC/USDT");
+    assert_eq!(exchange_symbol("binance", "btc", "usdt"), "BTCUSDT");
+    assert_eq!(exchange_symbol("kucoin", "btc", "usdt"), "BTC-USDT");
+}
+
+fn minute_candle(minute: i64, open: &str, high: &str, low: &str, close: &str, volume: &str) -> Candle {
+    Candle {
+        instrument_id: 1,
+        timeframe: "1m".to_string(),
+        open_time: Utc.timestamp_opt(minute * 60, 0).unwrap(),
+        open: BigDecimal::from_str(open).unwrap(),
+        high: BigDecimal::from_str(high).unwrap(),
+        low: BigDecimal::from_str(low).unwrap(),
+        close: BigDecimal::from_str(close).unwrap(),
+        volume: BigDecimal::from_str(volume).unwrap(),
+    }
+}
+
+#[test]
+fn test_aggregate_candles_to_5m() {
+    let candles = vec![
+        minute_candle(0, "10", "12", "9", "11", "1"),
+        minute_candle(1, "11", "15", "10", "14", "2"),
+        minute_candle(4, "14", "14", "8", "9", "3"),
+        minute_candle(5, "9", "10", "9", "10", "4"),
+    ];
+
+    let aggregated = aggregate_candles(&candles, Timeframe::M5);
+
+    assert_eq!(aggregated.len(), 2);
+    assert_eq!(aggregated[0].open, BigDecimal::from_str("10").unwrap());
+    assert_eq!(aggregated[0].high, BigDecimal::from_str("15").unwrap());
+    assert_eq!(aggregated[0].low, BigDecimal::from_str("8").unwrap());
+    assert_eq!(aggregated[0].close, BigDecimal::from_str("9").unwrap());
+    assert_eq!(aggregated[0].volume, BigDecimal::from_str("6").unwrap());
+    assert_eq!(aggregated[1].open_time, Utc.timestamp_opt(300, 0).unwrap());
+}
+
+#[test]
+fn test_find_gaps() {
+    let candles = vec![
+        minute_candle(0, "1", "1", "1", "1", "1"),
+        minute_candle(1, "1", "1", "1", "1", "1"),
+        minute_candle(4, "1", "1", "1", "1", "1"),
+    ];
+    let start = Utc.timestamp_opt(0, 0).unwrap();
+    let end = Utc.timestamp_opt(6 * 60, 0).unwrap();
+
+    let gaps = find_gaps(&candles, Timeframe::M1, start, end);
+
+    assert_eq!(
+        gaps,
+        vec![
+            (Utc.timestamp_opt(120, 0).unwrap(), Utc.timestamp_opt(240, 0).unwrap()),
+            (Utc.timestamp_opt(300, 0).unwrap(), end),
+        ]
+    );
+}
@@ -1,3 +1,4 @@
+use crate::i18n::Locale;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use sqlx::FromRow;
@@ -39,3 +40,9 @@ pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
+
+/// `null` borra la preferencia y vuelve a usar `Accept-Language`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateLocaleRequest {
+    pub locale: Option<Locale>,
+}
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use redis::{aio::ConnectionManager, Client, RedisError};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, OnceCell};
+use tracing::{info, warn};
+
+use super::{queue::RedisNotificationQueue, Notification};
+
+/// Canal de Redis por el que se reparten las notificaciones entre instancias.
+pub const NOTIFICATIONS_CHANNEL: &str = "notifications:broadcast";
+
+const IN_MEMORY_CAPACITY: usize = 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BusError {
+    #[error("Error de Redis: {0}")]
+    Redis(#[from] RedisError),
+    #[error("Error serializando notificación: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Lo que viaja por el bus: una notificación para los sockets del usuario
+/// o la orden de cerrarlos (por ejemplo al desactivarlo).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BusMessage {
+    Notification(Notification),
+    Disconnect { user_id: i32, reason: String },
+}
+
+/// Bus que reparte cada mensaje publicado a todas las instancias de la
+/// API, para que cada una lo aplique a los sockets que tiene abiertos.
+#[async_trait]
+pub trait NotificationBus: Send + Sync {
+    async fn publish(&self, message: &BusMessage) -> Result<(), BusError>;
+
+    /// Stream con los mensajes publicados desde que se suscribe.
+    async fn subscribe(&self) -> Result<BoxStream<'static, BusMessage>, BusError>;
+}
+
+/// Bus sobre Redis pub/sub, para despliegues con varias instancias.
+pub struct RedisBus {
+    client: Client,
+    channel: String,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl RedisBus {
+    /// Reutiliza el cliente de la cola de notificaciones.
+    pub fn from_queue(queue: &RedisNotificationQueue) -> Self {
+        Self {
+            client: queue.client().clone(),
+            channel: NOTIFICATIONS_CHANNEL.to_string(),
+            connection: OnceCell::new(),
+        }
+    }
+
+    /// Conexión compartida para publicar; se abre en el primer uso y se
+    /// reconecta sola si Redis se cae. Las suscripciones necesitan una
+    /// conexión propia.
+    async fn get_connection(&self) -> Result<ConnectionManager, RedisError> {
+        self.connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl NotificationBus for RedisBus {
+    async fn publish(&self, message: &BusMessage) -> Result<(), BusError> {
+        let payload = serde_json::to_string(message)?;
+        let mut conn = self.get_connection().await?;
+
+        redis::cmd("PUBLISH")
+            .arg(&self.channel)
+            .arg(payload)
+            .query_async::<_, i64>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, BusMessage>, BusError> {
+        let mut pubsub = self.client.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(&self.channel).await?;
+        info!("Suscrito al canal de notificaciones {}", self.channel);
+
+        let stream = pubsub.into_on_message().filter_map(|msg| async move {
+            let payload: String = match msg.get_payload() {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Mensaje inválido en el bus de notificaciones: {}", e);
+                    return None;
+                }
+            };
+
+            match serde_json::from_str::<BusMessage>(&payload) {
+                Ok(message) => Some(message),
+                Err(e) => {
+                    warn!("Error deserializando mensaje del bus: {}", e);
+                    None
+                }
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+}
+
+/// Bus en memoria para una sola instancia y para los tests.
+pub struct InMemoryBus {
+    sender: broadcast::Sender<BusMessage>,
+}
+
+impl InMemoryBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(IN_MEMORY_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl Default for InMemoryBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl NotificationBus for InMemoryBus {
+    async fn publish(&self, message: &BusMessage) -> Result<(), BusError> {
+        // Sin suscriptores no hay a quién entregar; no es un error.
+        let _ = self.sender.send(message.clone());
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, BusMessage>, BusError> {
+        let receiver = self.sender.subscribe();
+
+        let stream = stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(message) => return Some((message, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Bus en memoria atrasado, {} mensajes descartados", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+}
+
+/// Pide a todas las instancias que cierren las conexiones del usuario.
+pub async fn publish_disconnect(bus: &dyn NotificationBus, user_id: i32, reason: &str) -> Result<(), BusError> {
+    bus.publish(&BusMessage::Disconnect {
+        user_id,
+        reason: reason.to_string(),
+    })
+    .await
+}
+
+/// Usa Redis si `REDIS_URL` está configurado y el bus en memoria si no.
+pub fn bus_from_env() -> Arc<dyn NotificationBus> {
+    match std::env::var("REDIS_URL") {
+        Ok(url) => match RedisNotificationQueue::new(&url) {
+            Ok(queue) => return Arc::new(RedisBus::from_queue(&queue)),
+            Err(e) => warn!("REDIS_URL inválido, usando bus en memoria: {}", e),
+        },
+        Err(_) => info!("REDIS_URL no configurado, usando bus de notificaciones en memoria"),
+    }
+
+    Arc::new(InMemoryBus::new())
+}
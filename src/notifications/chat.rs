@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde_json::{json, Value as JsonValue};
+
+use super::events::{self, WebhookEvent};
+
+/// Gris para los eventos que no son de ningún tipo de notificación.
+const DEFAULT_COLOR: u32 = 0x6B7280;
+/// Campos que ya se muestran como título y texto del mensaje.
+const HIDDEN_FIELDS: [&str; 3] = ["title", "message", "notification_id"];
+
+// Límites de Discord para embeds
+const DISCORD_TITLE_LEN: usize = 256;
+const DISCORD_DESCRIPTION_LEN: usize = 4096;
+const DISCORD_MAX_FIELDS: usize = 25;
+const DISCORD_FIELD_NAME_LEN: usize = 256;
+const DISCORD_FIELD_VALUE_LEN: usize = 1024;
+
+// Límites de Slack para Block Kit
+const SLACK_HEADER_LEN: usize = 150;
+const SLACK_TEXT_LEN: usize = 3000;
+const SLACK_MAX_FIELDS: usize = 10;
+const SLACK_FIELD_LEN: usize = 2000;
+
+/// Contenido común a los formatos de Discord y Slack.
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub title: String,
+    pub message: Option<String>,
+    pub color: u32,
+    pub fields: Vec<(String, String)>,
+    pub event_type: &'static str,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ChatMessage {
+    /// Título y texto salen de la notificación si el evento la trae; si no,
+    /// el título es la descripción del evento. El resto de los datos se
+    /// muestran como campos, aplanando un nivel de objetos anidados.
+    pub fn from_event(event: &WebhookEvent) -> Self {
+        let data = event.latest_data();
+        let text = |key: &str| data.get(key).and_then(JsonValue::as_str).map(str::to_string);
+
+        let mut fields = Vec::new();
+        if let Some(map) = data.as_object() {
+            for (key, value) in map.iter().filter(|(key, _)| !HIDDEN_FIELDS.contains(&key.as_str())) {
+                match value {
+                    JsonValue::Null => {}
+                    // Objetos como `details` de los avisos del sistema
+                    JsonValue::Object(nested) => fields.extend(
+                        nested
+                            .iter()
+                            .filter(|(_, value)| !value.is_null())
+                            .map(|(key, value)| (field_name(key), field_value(value))),
+                    ),
+                    value => fields.push((field_name(key), field_value(value))),
+                }
+            }
+        }
+
+        Self {
+            title: text("title").unwrap_or_else(|| event.description.to_string()),
+            message: text("message"),
+            color: events::notification_type_for(event.event_type).map_or(DEFAULT_COLOR, |t| t.color()),
+            fields,
+            event_type: event.event_type,
+            timestamp: event.created_at,
+        }
+    }
+}
+
+/// `target_price` → `Target price`.
+fn field_name(key: &str) -> String {
+    let name = key.replace('_', " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+fn field_value(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Cuerpo para un incoming webhook de Discord: un embed con el color del
+/// tipo de notificación.
+pub fn discord_payload(message: &ChatMessage) -> JsonValue {
+    let fields: Vec<JsonValue> = message
+        .fields
+        .iter()
+        .take(DISCORD_MAX_FIELDS)
+        .map(|(name, value)| {
+            json!({
+                "name": truncate(name, DISCORD_FIELD_NAME_LEN),
+                "value": truncate(value, DISCORD_FIELD_VALUE_LEN),
+                "inline": true,
+            })
+        })
+        .collect();
+
+    let mut embed = json!({
+        "title": truncate(&message.title, DISCORD_TITLE_LEN),
+        "color": message.color,
+        "fields": fields,
+        "footer": { "text": message.event_type },
+        "timestamp": message.timestamp.to_rfc3339(),
+    });
+    if let Some(text) = &message.message {
+        embed["description"] = json!(truncate(text, DISCORD_DESCRIPTION_LEN));
+    }
+
+    json!({ "embeds": [embed] })
+}
+
+/// Escapa `&`, `<` y `>`, que Slack interpreta en `mrkdwn`.
+pub fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Cuerpo para un incoming webhook de Slack. Los bloques van dentro de un
+/// attachment porque es la única forma de mostrar el color; `text` es lo
+/// que aparece en las notificaciones del cliente.
+pub fn slack_payload(message: &ChatMessage) -> JsonValue {
+    let mut blocks = vec![json!({
+        "type": "header",
+        "text": { "type": "plain_text", "text": truncate(&message.title, SLACK_HEADER_LEN) },
+    })];
+
+    if let Some(text) = &message.message {
+        blocks.push(json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": truncate(&escape_slack(text), SLACK_TEXT_LEN) },
+        }));
+    }
+
+    for chunk in message.fields.chunks(SLACK_MAX_FIELDS) {
+        let fields: Vec<JsonValue> = chunk
+            .iter()
+            .map(|(name, value)| {
+                let text = format!("*{}*\n{}", escape_slack(name), escape_slack(value));
+                json!({ "type": "mrkdwn", "text": truncate(&text, SLACK_FIELD_LEN) })
+            })
+            .collect();
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+
+    blocks.push(json!({
+        "type": "context",
+        "elements": [{ "type": "mrkdwn", "text": message.event_type }],
+    }));
+
+    let fallback = match &message.message {
+        Some(text) => format!("{}: {}", message.title, text),
+        None => message.title.clone(),
+    };
+
+    json!({
+        "text": escape_slack(&fallback),
+        "attachments": [{
+            "color": format!("#{:06x}", message.color),
+            "blocks": blocks,
+        }],
+    })
+}
+
+/// Espera pedida en una respuesta 429: el header `Retry-After` (Slack y
+/// Discord) o `retry_after` en el cuerpo (Discord), en segundos.
+pub fn retry_after(headers: &HeaderMap, body: &str) -> Option<Duration> {
+    let from_header = headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok());
+    let from_body = || {
+        serde_json::from_str::<JsonValue>(body)
+            .ok()?
+            .get("retry_after")?
+            .as_f64()
+    };
+
+    from_header
+        .or_else(from_body)
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
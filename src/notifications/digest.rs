@@ -0,0 +1,53 @@
+use serde_json::json;
+
+use super::Notification;
+use crate::i18n::Locale;
+
+/// Notificaciones listadas en el texto del resumen; el resto sólo se cuenta.
+const MAX_DIGEST_LINES: usize = 20;
+
+/// Agrupa las notificaciones retenidas de un usuario en un único mensaje.
+/// Con una sola se envía tal cual. El resumen toma el tipo de la de mayor
+/// prioridad y lleva el detalle de todas en `metadata.notifications`.
+pub fn build_digest(notifications: &[Notification], locale: Locale) -> Option<Notification> {
+    let (first, rest) = notifications.split_first()?;
+    if rest.is_empty() {
+        return Some(first.clone());
+    }
+
+    let main = notifications
+        .iter()
+        .max_by_key(|notification| notification.priority)
+        .unwrap_or(first);
+
+    let mut lines: Vec<String> = notifications
+        .iter()
+        .take(MAX_DIGEST_LINES)
+        .map(|notification| format!("• {}: {}", notification.title, notification.message))
+        .collect();
+    if notifications.len() > MAX_DIGEST_LINES {
+        lines.push(locale.t_with("digest.more", &[("count", &(notifications.len() - MAX_DIGEST_LINES))]));
+    }
+
+    let items: Vec<serde_json::Value> = notifications
+        .iter()
+        .map(|notification| {
+            json!({
+                "event_id": notification.event_id,
+                "type": notification.notification_type.as_str(),
+                "title": notification.title,
+                "message": notification.message,
+                "created_at": notification.created_at,
+            })
+        })
+        .collect();
+
+    Some(Notification::new(
+        first.user_id,
+        main.notification_type.clone(),
+        locale.t_with("digest.title", &[("count", &notifications.len())]),
+        lines.join("\n"),
+        json!({ "digest": true, "count": notifications.len(), "notifications": items }),
+    )
+    .with_priority(main.priority))
+}
@@ -0,0 +1,653 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::{error, info, warn};
+
+use super::{
+    bus::{BusMessage, NotificationBus},
+    digest::build_digest,
+    email::{mailer_from_env, EmailChannel},
+    limits::LimitsConfig,
+    preferences::UserPreferences,
+    queue::{NotificationQueue, QueuedNotification},
+    telegram::{TelegramChannel, TelegramClient},
+    telegram_bot::TelegramBot,
+    web_push::{WebPushChannel, WebPushClient},
+    webhook::WebhookChannel,
+    whatsapp::{WhatsAppChannel, WhatsAppClient},
+    Notification, NotificationPriority, NotificationType,
+};
+use crate::{
+    db::{
+        held_notifications::{self, HeldNotification},
+        notifications::{self, DeliveryStatus},
+        rate_limits::{self, Overflow},
+        users,
+    },
+    i18n::Locale,
+};
+
+const DEFAULT_CONCURRENCY: usize = 16;
+/// Espera entre sondeos cuando todas las colas están vacías.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Cada cuánto se buscan notificaciones retenidas que ya hay que enviar.
+const HELD_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Pares usuario/canal que se resumen en cada pasada.
+const HELD_BATCH_SIZE: i64 = 100;
+/// Espera antes de reintentar un resumen que falló.
+const HELD_RETRY_DELAY_SECS: i64 = 60;
+/// Cada cuánto se reintentan las entregas de webhooks que fallaron.
+const WEBHOOK_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+    #[serde(rename = "websocket")]
+    WebSocket,
+    Webhook,
+    Email,
+    Telegram,
+    #[serde(rename = "whatsapp")]
+    WhatsApp,
+    WebPush,
+}
+
+impl ChannelKind {
+    pub const ALL: [ChannelKind; 6] = [
+        ChannelKind::WebSocket,
+        ChannelKind::Webhook,
+        ChannelKind::Email,
+        ChannelKind::Telegram,
+        ChannelKind::WhatsApp,
+        ChannelKind::WebPush,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChannelKind::WebSocket => "websocket",
+            ChannelKind::Webhook => "webhook",
+            ChannelKind::Email => "email",
+            ChannelKind::Telegram => "telegram",
+            ChannelKind::WhatsApp => "whatsapp",
+            ChannelKind::WebPush => "web_push",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == name)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChannelError {
+    /// El usuario no tiene destino configurado para el canal (sin webhooks,
+    /// sin chat de Telegram vinculado, ...). No se reintenta.
+    #[error("Sin destino configurado: {0}")]
+    NotConfigured(String),
+    #[error("Error de entrega: {0}")]
+    Failed(String),
+}
+
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    fn kind(&self) -> ChannelKind;
+
+    async fn send(&self, notification: &Notification) -> Result<(), ChannelError>;
+}
+
+/// Entrega en vivo (WebSocket/SSE): publica la notificación en el bus para
+/// que cada instancia de la API la entregue. La bandeja la guarda el
+/// dispatcher antes de repartir, así no depende de este canal.
+pub struct WebSocketChannel {
+    bus: Arc<dyn NotificationBus>,
+}
+
+impl WebSocketChannel {
+    pub fn new(bus: Arc<dyn NotificationBus>) -> Self {
+        Self { bus }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WebSocketChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::WebSocket
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), ChannelError> {
+        self.bus
+            .publish(&BusMessage::Notification(notification.clone()))
+            .await
+            .map_err(|e| ChannelError::Failed(e.to_string()))
+    }
+}
+
+/// Consume las colas de notificaciones y las reparte entre los canales que
+/// el usuario tiene habilitados, registrando el resultado de cada uno.
+pub struct Dispatcher {
+    pool: PgPool,
+    queue: Arc<dyn NotificationQueue>,
+    channels: HashMap<ChannelKind, Arc<dyn NotificationChannel>>,
+    concurrency: usize,
+    consumer: String,
+    limits: LimitsConfig,
+}
+
+impl Dispatcher {
+    pub fn new(pool: PgPool, queue: Arc<dyn NotificationQueue>) -> Self {
+        let concurrency = std::env::var("DISPATCHER_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(DEFAULT_CONCURRENCY);
+
+        Self {
+            pool,
+            queue,
+            channels: HashMap::new(),
+            concurrency,
+            consumer: format!("dispatcher-{}", std::process::id()),
+            limits: LimitsConfig::from_env(),
+        }
+    }
+
+    pub fn with_channel(mut self, channel: Arc<dyn NotificationChannel>) -> Self {
+        self.channels.insert(channel.kind(), channel);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_consumer(mut self, consumer: impl Into<String>) -> Self {
+        self.consumer = consumer.into();
+        self
+    }
+
+    pub fn with_limits(mut self, limits: LimitsConfig) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Procesa notificaciones hasta que `shutdown` se resuelve; después deja
+    /// de leer de las colas y espera a que terminen las entregas en curso.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) {
+        let dispatcher = Arc::new(self);
+        let semaphore = Arc::new(Semaphore::new(dispatcher.concurrency));
+        let mut in_flight = JoinSet::new();
+
+        info!(
+            "Dispatcher {} iniciado (concurrencia {}, canales: {:?})",
+            dispatcher.consumer,
+            dispatcher.concurrency,
+            dispatcher.channels.keys().map(ChannelKind::as_str).collect::<Vec<_>>()
+        );
+
+        tokio::pin!(shutdown);
+        let mut next_held_poll = Instant::now();
+
+        'poll: loop {
+            let mut popped_any = false;
+
+            if Instant::now() >= next_held_poll {
+                next_held_poll = Instant::now() + HELD_POLL_INTERVAL;
+                let dispatcher = dispatcher.clone();
+                in_flight.spawn(async move { dispatcher.flush_held().await });
+            }
+
+            for notification_type in NotificationType::ALL {
+                let permit = tokio::select! {
+                    permit = semaphore.clone().acquire_owned() => permit.expect("semáforo cerrado"),
+                    _ = &mut shutdown => break 'poll,
+                };
+
+                let queued = match dispatcher.queue.pop(notification_type.clone(), &dispatcher.consumer).await {
+                    Ok(Some(queued)) => queued,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        error!("Error leyendo la cola {:?}: {}", notification_type, e);
+                        continue;
+                    }
+                };
+
+                popped_any = true;
+                let dispatcher = dispatcher.clone();
+                in_flight.spawn(async move {
+                    dispatcher.dispatch(notification_type, queued).await;
+                    drop(permit);
+                });
+            }
+
+            // Liberar los resultados de las tareas terminadas
+            while in_flight.try_join_next().is_some() {}
+
+            if !popped_any {
+                tokio::select! {
+                    _ = tokio::time::sleep(IDLE_POLL_INTERVAL) => {}
+                    _ = &mut shutdown => break 'poll,
+                }
+            }
+        }
+
+        info!("Dispatcher detenido, esperando {} entregas en curso", in_flight.len());
+        while in_flight.join_next().await.is_some() {}
+        info!("Dispatcher finalizado");
+    }
+
+    /// Entrega una notificación y la confirma en la cola. Si algún canal
+    /// falla no se confirma, así se reintenta; los canales que ya la
+    /// entregaron en un intento anterior se saltean. Las duplicadas dentro
+    /// de la ventana de deduplicación se descartan.
+    ///
+    /// Antes de repartir se guarda en la bandeja (asignando su `event_id`),
+    /// sin importar las preferencias de cada canal: lo que no pase por el
+    /// historial no se puede reenviar con `resume`.
+    async fn dispatch(&self, notification_type: NotificationType, mut queued: QueuedNotification) {
+        let dedup_key = queued.notification.dedup_key();
+        match rate_limits::claim_dedup_key(
+            &self.pool,
+            queued.notification.user_id,
+            &dedup_key,
+            &queued.id,
+            self.limits.dedup_window,
+        )
+        .await
+        {
+            Ok(Some(0)) => {}
+            Ok(Some(repeats)) => self.add_repeats(&mut queued.notification, repeats).await,
+            Ok(None) => {
+                info!("Notificación {} descartada: duplicada (clave {})", queued.id, dedup_key);
+                self.ack(notification_type, &queued.id).await;
+                return;
+            }
+            Err(e) => {
+                error!("Error comprobando duplicados de {}: {}", queued.id, e);
+                return;
+            }
+        }
+
+        match notifications::store_notification(&self.pool, &queued.notification, &queued.id).await {
+            Ok(stored) => {
+                queued.notification.id = Some(stored.id);
+                queued.notification.event_id = Some(stored.event_id);
+            }
+            Err(e) => {
+                error!("Error guardando {} en la bandeja: {}", queued.id, e);
+                return;
+            }
+        }
+        let notification = &queued.notification;
+
+        let preferences = match UserPreferences::load(&self.pool, notification.user_id).await {
+            Ok(preferences) => preferences,
+            Err(e) => {
+                error!("Error cargando preferencias del usuario {}: {}", notification.user_id, e);
+                return;
+            }
+        };
+
+        if !ChannelKind::ALL.iter().any(|kind| preferences.allows(*kind, notification)) {
+            info!(
+                "Notificación {} descartada: tipo {:?} deshabilitado en todos los canales por el usuario {}",
+                queued.id, notification.notification_type, notification.user_id
+            );
+            self.ack(notification_type, &queued.id).await;
+            return;
+        }
+
+        let delivered = notifications::delivered_channels(&self.pool, &queued.id)
+            .await
+            .unwrap_or_default();
+
+        let mut retry = false;
+        for kind in ChannelKind::ALL {
+            if delivered.iter().any(|channel| channel == kind.as_str()) {
+                continue;
+            }
+
+            let (status, detail) = self
+                .deliver(kind, &queued.id, notification, &preferences, queued.attempts)
+                .await;
+            retry |= status == DeliveryStatus::Failed;
+
+            if let Err(e) = notifications::record_delivery(
+                &self.pool,
+                &queued.id,
+                notification.user_id,
+                kind.as_str(),
+                status,
+                queued.attempts as i32,
+                detail.as_deref(),
+            )
+            .await
+            {
+                error!("Error registrando entrega de {}: {}", queued.id, e);
+            }
+        }
+
+        if !retry {
+            self.ack(notification_type, &queued.id).await;
+        }
+    }
+
+    /// Entrega por un canal respetando las preferencias del usuario: canal
+    /// deshabilitado, horas de silencio (se retiene) y límite de envíos.
+    async fn deliver(
+        &self,
+        kind: ChannelKind,
+        queue_id: &str,
+        notification: &Notification,
+        preferences: &UserPreferences,
+        attempts: u32,
+    ) -> (DeliveryStatus, Option<String>) {
+        if !preferences.allows(kind, notification) {
+            return (DeliveryStatus::Skipped, None);
+        }
+        let Some(channel) = self.channels.get(&kind) else {
+            return (DeliveryStatus::Unavailable, None);
+        };
+
+        if let Some(deliver_after) = preferences.hold_until(kind, notification, Utc::now()) {
+            return match held_notifications::hold_notification(
+                &self.pool,
+                queue_id,
+                kind.as_str(),
+                notification,
+                deliver_after,
+            )
+            .await
+            {
+                Ok(()) => (DeliveryStatus::Held, Some(format!("hasta {}", deliver_after.to_rfc3339()))),
+                Err(e) => {
+                    error!("Error reteniendo {} para {}: {}", queue_id, kind.as_str(), e);
+                    (DeliveryStatus::Failed, Some(e.to_string()))
+                }
+            };
+        }
+
+        if !self.within_rate_limit(kind, notification).await {
+            return (DeliveryStatus::RateLimited, Some("límite de envíos".to_string()));
+        }
+
+        match channel.send(notification).await {
+            Ok(()) => (DeliveryStatus::Delivered, None),
+            Err(ChannelError::NotConfigured(reason)) => (DeliveryStatus::Skipped, Some(reason)),
+            Err(ChannelError::Failed(reason)) => {
+                warn!(
+                    "Fallo entregando {} por {} (intento {}): {}",
+                    queue_id,
+                    kind.as_str(),
+                    attempts,
+                    reason
+                );
+                (DeliveryStatus::Failed, Some(reason))
+            }
+        }
+    }
+
+    /// Avisa en el mensaje cuántas repeticiones de la notificación se
+    /// descartaron en la ventana de deduplicación anterior.
+    async fn add_repeats(&self, notification: &mut Notification, repeats: i32) {
+        let locale = self.user_locale(notification.user_id).await;
+        notification.message = format!(
+            "{} {}",
+            notification.message,
+            locale.t_with("dedup.repeats", &[("count", &repeats)])
+        );
+        if let Some(metadata) = notification.metadata.as_object_mut() {
+            metadata.insert("repeats".to_string(), repeats.into());
+        }
+    }
+
+    /// Las urgentes y la bandeja in-app no tienen límite. Si no se puede
+    /// comprobar el límite se envía igual.
+    async fn within_rate_limit(&self, kind: ChannelKind, notification: &Notification) -> bool {
+        if kind == ChannelKind::WebSocket || notification.priority == NotificationPriority::Urgent {
+            return true;
+        }
+        let Some(limit) = self.limits.rate_limit(kind) else {
+            return true;
+        };
+
+        rate_limits::try_acquire(
+            &self.pool,
+            notification.user_id,
+            kind.as_str(),
+            limit,
+            self.limits.rate_limit_window,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            error!("Error comprobando el límite de envíos de {}: {}", kind.as_str(), e);
+            true
+        })
+    }
+
+    /// Envía las notificaciones retenidas que ya vencieron, un resumen por
+    /// usuario y canal, y avisa de las omitidas por límite de envíos.
+    async fn flush_held(&self) {
+        self.flush_overflow().await;
+
+        let held = match held_notifications::take_due(&self.pool, HELD_BATCH_SIZE).await {
+            Ok(held) => held,
+            Err(e) => {
+                error!("Error leyendo notificaciones retenidas: {}", e);
+                return;
+            }
+        };
+
+        let mut groups: BTreeMap<(i32, String), Vec<HeldNotification>> = BTreeMap::new();
+        for entry in held {
+            groups.entry((entry.user_id, entry.channel.clone())).or_default().push(entry);
+        }
+
+        for ((user_id, channel), entries) in groups {
+            self.deliver_held(user_id, &channel, entries).await;
+        }
+    }
+
+    /// Un aviso por usuario y canal con la cantidad de notificaciones que no
+    /// se enviaron por el límite; siguen disponibles en la bandeja. El aviso
+    /// pasa por `deliver`, así respeta preferencias y horas de silencio.
+    async fn flush_overflow(&self) {
+        let overflow = match rate_limits::take_overflow(&self.pool, self.limits.rate_limit_window, HELD_BATCH_SIZE).await {
+            Ok(overflow) => overflow,
+            Err(e) => {
+                error!("Error leyendo notificaciones omitidas por límite: {}", e);
+                return;
+            }
+        };
+
+        for Overflow { user_id, channel, count } in overflow {
+            let Some(kind) = ChannelKind::from_name(&channel) else {
+                continue;
+            };
+            let preferences = match UserPreferences::load(&self.pool, user_id).await {
+                Ok(preferences) => preferences,
+                Err(e) => {
+                    error!("Error cargando preferencias del usuario {}: {}", user_id, e);
+                    continue;
+                }
+            };
+            let locale = self.user_locale(user_id).await;
+            let summary = Notification::new(
+                user_id,
+                NotificationType::SystemAlert,
+                locale.t_with("rate_limit.summary_title", &[("count", &count)]),
+                locale.t_with("rate_limit.summary_message", &[("count", &count)]),
+                serde_json::json!({ "rate_limited": true, "count": count }),
+            )
+            .with_priority(NotificationPriority::Normal);
+
+            // Id propio para registrar la entrega o retenerlo como cualquier otra
+            let queue_id = format!("rate-limit:{}:{}:{}", user_id, kind.as_str(), Utc::now().timestamp_millis());
+            let (status, detail) = self.deliver(kind, &queue_id, &summary, &preferences, 1).await;
+            if status == DeliveryStatus::Failed {
+                warn!(
+                    "Fallo avisando al usuario {} de {} notificaciones omitidas por {}",
+                    user_id,
+                    count,
+                    kind.as_str()
+                );
+            }
+
+            if let Err(e) = notifications::record_delivery(
+                &self.pool,
+                &queue_id,
+                user_id,
+                kind.as_str(),
+                status,
+                1,
+                detail.as_deref(),
+            )
+            .await
+            {
+                error!("Error registrando entrega de {}: {}", queue_id, e);
+            }
+        }
+    }
+
+    async fn deliver_held(&self, user_id: i32, channel_name: &str, entries: Vec<HeldNotification>) {
+        let notifications: Vec<Notification> = entries.iter().map(|entry| entry.notification.0.clone()).collect();
+        let Some(digest) = build_digest(&notifications, self.user_locale(user_id).await) else {
+            return;
+        };
+
+        let channel = ChannelKind::from_name(channel_name).and_then(|kind| self.channels.get(&kind));
+        let (status, detail) = match channel {
+            None => (DeliveryStatus::Unavailable, None),
+            Some(channel) => match channel.send(&digest).await {
+                Ok(()) => {
+                    info!(
+                        "Resumen de {} notificaciones enviado al usuario {} por {}",
+                        entries.len(),
+                        user_id,
+                        channel_name
+                    );
+                    (DeliveryStatus::Delivered, None)
+                }
+                Err(ChannelError::NotConfigured(reason)) => (DeliveryStatus::Skipped, Some(reason)),
+                Err(ChannelError::Failed(reason)) => {
+                    warn!(
+                        "Fallo enviando el resumen del usuario {} por {}: {}",
+                        user_id, channel_name, reason
+                    );
+                    let retry_at = Utc::now() + chrono::Duration::seconds(HELD_RETRY_DELAY_SECS);
+                    for entry in &entries {
+                        if let Err(e) = held_notifications::hold_notification(
+                            &self.pool,
+                            &entry.queue_id,
+                            channel_name,
+                            &entry.notification.0,
+                            retry_at,
+                        )
+                        .await
+                        {
+                            error!("Error volviendo a retener {}: {}", entry.queue_id, e);
+                        }
+                    }
+                    return;
+                }
+            },
+        };
+
+        for entry in &entries {
+            if let Err(e) = notifications::record_delivery(
+                &self.pool,
+                &entry.queue_id,
+                user_id,
+                channel_name,
+                status,
+                1,
+                detail.as_deref(),
+            )
+            .await
+            {
+                error!("Error registrando entrega de {}: {}", entry.queue_id, e);
+            }
+        }
+    }
+
+    /// Idioma de los textos que arma el propio dispatcher (resúmenes); si
+    /// no se puede leer se usa el de por defecto.
+    async fn user_locale(&self, user_id: i32) -> Locale {
+        users::get_user_locale(&self.pool, user_id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Error leyendo el idioma del usuario {}: {}", user_id, e);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    async fn ack(&self, notification_type: NotificationType, id: &str) {
+        if let Err(e) = self.queue.ack(notification_type, id).await {
+            error!("Error confirmando {} en la cola: {}", id, e);
+        }
+    }
+}
+
+/// Dispatcher con todos los canales que estén configurados en el entorno.
+/// También lanza las tareas que necesitan: los reintentos de webhooks y,
+/// sin webhook de Telegram, el polling del bot.
+pub fn build_dispatcher(
+    pool: PgPool,
+    queue: Arc<dyn NotificationQueue>,
+    bus: Arc<dyn NotificationBus>,
+) -> Dispatcher {
+    // Las entregas de webhooks que fallan se reintentan fuera de la cola
+    let webhooks = Arc::new(WebhookChannel::new(pool.clone(), queue.clone()));
+    tokio::spawn(webhooks.clone().run_retry_worker(WEBHOOK_RETRY_INTERVAL));
+
+    // Los canales sin implementación se registran como no disponibles
+    let mut dispatcher = Dispatcher::new(pool.clone(), queue)
+        .with_channel(Arc::new(WebSocketChannel::new(bus)))
+        .with_channel(webhooks);
+
+    // Con TELEGRAM_WEBHOOK_SECRET las actualizaciones llegan a /telegram/webhook
+    if let Some(client) = TelegramClient::from_env() {
+        if std::env::var("TELEGRAM_WEBHOOK_SECRET").is_err() {
+            let bot = Arc::new(TelegramBot::new(pool.clone(), client.clone()));
+            tokio::spawn(bot.run_polling());
+        }
+        dispatcher = dispatcher.with_channel(Arc::new(TelegramChannel::new(pool.clone(), client)));
+    } else {
+        warn!("TELEGRAM_BOT_TOKEN no configurado, canal de Telegram deshabilitado");
+    }
+
+    // Los estados de entrega de WhatsApp llegan a /whatsapp/webhook
+    if let Some(client) = WhatsAppClient::from_env() {
+        dispatcher = dispatcher.with_channel(Arc::new(WhatsAppChannel::new(pool.clone(), client)));
+    } else {
+        warn!(
+            "WHATSAPP_ACCESS_TOKEN o WHATSAPP_PHONE_NUMBER_ID no configurados, canal de WhatsApp deshabilitado"
+        );
+    }
+
+    // Web Push necesita VAPID_PRIVATE_KEY y VAPID_SUBJECT (ver el binario vapid_keys)
+    if let Some(client) = WebPushClient::from_env() {
+        dispatcher = dispatcher.with_channel(Arc::new(WebPushChannel::new(pool.clone(), client)));
+    } else {
+        warn!("Claves VAPID no configuradas, canal de Web Push deshabilitado");
+    }
+
+    // Email necesita EMAIL_FROM y SMTP_URL, o MAIL_DROP_DIR para pruebas locales
+    if let Some(mailer) = mailer_from_env() {
+        dispatcher = dispatcher.with_channel(Arc::new(EmailChannel::new(pool, mailer)));
+    } else {
+        warn!("Email no configurado, canal de email deshabilitado");
+    }
+
+    dispatcher
+}
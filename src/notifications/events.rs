@@ -0,0 +1,310 @@
+use chrono::{DateTime, Utc};
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use super::{Notification, NotificationType};
+use crate::models::price_alerts::PriceAlert;
+
+/// Versión del sobre y de los payloads que reciben los webhooks nuevos.
+pub const LATEST_VERSION: i32 = 1;
+/// Versiones que todavía se pueden fijar en un webhook. Al cambiar de forma
+/// incompatible un payload se agrega una versión y la conversión en
+/// `EventData::data_for_version`.
+pub const SUPPORTED_VERSIONS: [i32; 1] = [1];
+
+pub fn is_supported_version(version: i32) -> bool {
+    SUPPORTED_VERSIONS.contains(&version)
+}
+
+/// Payload de un tipo de evento del catálogo.
+pub trait EventData: Serialize + JsonSchema {
+    const EVENT_TYPE: &'static str;
+    const DESCRIPTION: &'static str;
+
+    /// Datos del evento con la forma de `version`.
+    fn data_for_version(&self, _version: i32) -> Result<JsonValue, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+}
+
+/// Acepta números o strings y los conserva como string, para no perder
+/// precisión en precios y cantidades.
+fn deserialize_decimal_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<JsonValue>::deserialize(deserializer)? {
+        Some(JsonValue::String(s)) => Some(s),
+        Some(JsonValue::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PriceAlertCreated {
+    pub alert_id: i32,
+    pub asset: String,
+    /// Decimal como string.
+    pub target_price: String,
+    pub condition: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&PriceAlert> for PriceAlertCreated {
+    fn from(alert: &PriceAlert) -> Self {
+        Self {
+            alert_id: alert.id,
+            asset: alert.asset.clone(),
+            target_price: alert.target_price.to_string(),
+            condition: alert.condition.clone(),
+            created_at: alert.created_at,
+        }
+    }
+}
+
+impl EventData for PriceAlertCreated {
+    const EVENT_TYPE: &'static str = "price_alert.created";
+    const DESCRIPTION: &'static str = "Se creó una alerta de precio.";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PriceAlertTriggered {
+    /// `event_id` de la notificación en la bandeja del usuario.
+    pub notification_id: Option<i64>,
+    pub title: String,
+    pub message: String,
+    pub alert_id: Option<i32>,
+    pub asset: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_decimal_string")]
+    pub target_price: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_decimal_string")]
+    pub current_price: Option<String>,
+    pub condition: Option<String>,
+}
+
+impl EventData for PriceAlertTriggered {
+    const EVENT_TYPE: &'static str = "price_alert.triggered";
+    const DESCRIPTION: &'static str = "El precio alcanzó el objetivo de una alerta.";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct StrategyUpdated {
+    pub notification_id: Option<i64>,
+    pub title: String,
+    pub message: String,
+    pub strategy_id: Option<i32>,
+    pub status: Option<String>,
+}
+
+impl EventData for StrategyUpdated {
+    const EVENT_TYPE: &'static str = "strategy.updated";
+    const DESCRIPTION: &'static str = "Cambió el estado o la configuración de una estrategia.";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TradeExecuted {
+    pub notification_id: Option<i64>,
+    pub title: String,
+    pub message: String,
+    pub strategy_id: Option<i32>,
+    pub symbol: Option<String>,
+    /// `buy` o `sell`.
+    pub side: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_decimal_string")]
+    pub quantity: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_decimal_string")]
+    pub price: Option<String>,
+}
+
+impl EventData for TradeExecuted {
+    const EVENT_TYPE: &'static str = "trade.executed";
+    const DESCRIPTION: &'static str = "Se ejecutó una operación.";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MarketSentimentUpdated {
+    pub notification_id: Option<i64>,
+    pub title: String,
+    pub message: String,
+    pub symbol: Option<String>,
+    pub sentiment: Option<String>,
+}
+
+impl EventData for MarketSentimentUpdated {
+    const EVENT_TYPE: &'static str = "market_sentiment.updated";
+    const DESCRIPTION: &'static str = "Cambió el sentimiento de mercado de un activo.";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SystemAlert {
+    pub notification_id: Option<i64>,
+    pub title: String,
+    pub message: String,
+    /// Datos adicionales, dependientes de la alerta.
+    #[serde(default)]
+    pub details: JsonValue,
+}
+
+impl EventData for SystemAlert {
+    const EVENT_TYPE: &'static str = "system.alert";
+    const DESCRIPTION: &'static str = "Aviso del sistema, por ejemplo un webhook deshabilitado.";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Ping {
+    pub webhook_id: Uuid,
+}
+
+impl EventData for Ping {
+    const EVENT_TYPE: &'static str = "ping";
+    const DESCRIPTION: &'static str = "Prueba enviada desde `/webhooks/:id/test`.";
+}
+
+/// Evento listo para enviar. Los datos se guardan ya convertidos a cada
+/// versión soportada para renderizar el sobre que fijó cada webhook.
+#[derive(Debug, Clone)]
+pub struct WebhookEvent {
+    pub id: Uuid,
+    pub event_type: &'static str,
+    pub description: &'static str,
+    pub created_at: DateTime<Utc>,
+    data: Vec<(i32, JsonValue)>,
+}
+
+/// Sobre común de todos los payloads de webhooks.
+#[derive(Debug, Serialize)]
+pub struct EventEnvelope<'a> {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: &'a str,
+    pub version: i32,
+    pub created_at: DateTime<Utc>,
+    pub data: &'a JsonValue,
+}
+
+impl WebhookEvent {
+    pub fn new<T: EventData>(data: &T) -> Result<Self, serde_json::Error> {
+        let data = SUPPORTED_VERSIONS
+            .iter()
+            .map(|version| Ok((*version, data.data_for_version(*version)?)))
+            .collect::<Result<_, serde_json::Error>>()?;
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            event_type: T::EVENT_TYPE,
+            description: T::DESCRIPTION,
+            created_at: Utc::now(),
+            data,
+        })
+    }
+
+    /// Evento correspondiente a una notificación del dispatcher. Los campos
+    /// específicos del tipo se toman de `metadata`. El id sale del
+    /// `event_id` de la notificación, así cada reintento repite el mismo.
+    pub fn from_notification(notification: &Notification) -> Result<Self, serde_json::Error> {
+        let mut fields = match &notification.metadata {
+            JsonValue::Object(map) => map.clone(),
+            _ => serde_json::Map::new(),
+        };
+        fields.insert("notification_id".to_string(), serde_json::json!(notification.event_id));
+        fields.insert("title".to_string(), JsonValue::String(notification.title.clone()));
+        fields.insert("message".to_string(), JsonValue::String(notification.message.clone()));
+
+        let fields = JsonValue::Object(fields);
+        let mut event = match notification.notification_type {
+            NotificationType::PriceAlert => Self::new(&serde_json::from_value::<PriceAlertTriggered>(fields)?),
+            NotificationType::StrategyUpdate => Self::new(&serde_json::from_value::<StrategyUpdated>(fields)?),
+            NotificationType::TradeExecution => Self::new(&serde_json::from_value::<TradeExecuted>(fields)?),
+            NotificationType::MarketSentiment => {
+                Self::new(&serde_json::from_value::<MarketSentimentUpdated>(fields)?)
+            }
+            NotificationType::SystemAlert => Self::new(&SystemAlert {
+                notification_id: notification.event_id,
+                title: notification.title.clone(),
+                message: notification.message.clone(),
+                details: notification.metadata.clone(),
+            }),
+        }?;
+
+        if let Some(event_id) = notification.event_id {
+            event.id = Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("notification:{}", event_id).as_bytes());
+        }
+        Ok(event)
+    }
+
+    /// Datos en la última versión, para los formatos que no se versionan.
+    pub fn latest_data(&self) -> &JsonValue {
+        self.data
+            .iter()
+            .find(|(version, _)| *version == LATEST_VERSION)
+            .map(|(_, data)| data)
+            .unwrap_or(&JsonValue::Null)
+    }
+
+    /// Cuerpo JSON con el sobre en `version`, o `None` si no está soportada.
+    pub fn render(&self, version: i32) -> Option<Result<String, serde_json::Error>> {
+        let (_, data) = self.data.iter().find(|(v, _)| *v == version)?;
+        Some(serde_json::to_string(&EventEnvelope {
+            id: self.id,
+            event_type: self.event_type,
+            version,
+            created_at: self.created_at,
+            data,
+        }))
+    }
+}
+
+/// Entrada del catálogo publicado en `/webhooks/events`.
+#[derive(Debug, Serialize)]
+pub struct EventDescriptor {
+    #[serde(rename = "type")]
+    pub event_type: &'static str,
+    pub description: &'static str,
+    pub versions: Vec<i32>,
+    /// JSON Schema de `data` en la última versión.
+    pub schema: RootSchema,
+}
+
+fn describe<T: EventData>() -> EventDescriptor {
+    EventDescriptor {
+        event_type: T::EVENT_TYPE,
+        description: T::DESCRIPTION,
+        versions: SUPPORTED_VERSIONS.to_vec(),
+        schema: schema_for!(T),
+    }
+}
+
+pub fn catalog() -> Vec<EventDescriptor> {
+    vec![
+        describe::<PriceAlertCreated>(),
+        describe::<PriceAlertTriggered>(),
+        describe::<StrategyUpdated>(),
+        describe::<TradeExecuted>(),
+        describe::<MarketSentimentUpdated>(),
+        describe::<SystemAlert>(),
+        describe::<Ping>(),
+    ]
+}
+
+/// Tipo de evento del catálogo que genera cada tipo de notificación.
+/// Tipo de notificación al que pertenece un evento, según su categoría
+/// (`price_alert.created` y `price_alert.triggered` son de alertas de
+/// precio). `None` para eventos como `ping`.
+pub fn notification_type_for(event_type: &str) -> Option<NotificationType> {
+    let category = |event_type: &str| event_type.split('.').next().unwrap_or_default().to_string();
+    NotificationType::ALL
+        .into_iter()
+        .find(|t| event_type.contains('.') && category(event_type_for(t)) == category(event_type))
+}
+
+pub fn event_type_for(notification_type: &NotificationType) -> &'static str {
+    match notification_type {
+        NotificationType::PriceAlert => PriceAlertTriggered::EVENT_TYPE,
+        NotificationType::MarketSentiment => MarketSentimentUpdated::EVENT_TYPE,
+        NotificationType::StrategyUpdate => StrategyUpdated::EVENT_TYPE,
+        NotificationType::TradeExecution => TradeExecuted::EVENT_TYPE,
+        NotificationType::SystemAlert => SystemAlert::EVENT_TYPE,
+    }
+}
@@ -0,0 +1,96 @@
+use std::{collections::HashMap, time::Duration};
+
+use tracing::warn;
+
+use super::dispatcher::ChannelKind;
+
+const DEFAULT_DEDUP_WINDOW_SECS: u64 = 5 * 60;
+const DEFAULT_RATE_LIMIT_WINDOW_SECS: u64 = 60 * 60;
+/// Envíos por usuario y ventana en cada canal. La bandeja in-app y los
+/// webhooks no tienen límite por defecto.
+const DEFAULT_RATE_LIMITS: [(ChannelKind, u32); 4] = [
+    (ChannelKind::Email, 20),
+    (ChannelKind::Telegram, 60),
+    (ChannelKind::WhatsApp, 10),
+    (ChannelKind::WebPush, 60),
+];
+
+#[derive(Debug, Clone)]
+pub struct LimitsConfig {
+    /// Ventana en la que se descartan las notificaciones con la misma clave
+    /// de deduplicación.
+    pub dedup_window: Duration,
+    /// Ventana fija de los límites de envío.
+    pub rate_limit_window: Duration,
+    pub rate_limits: HashMap<ChannelKind, u32>,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            dedup_window: Duration::from_secs(DEFAULT_DEDUP_WINDOW_SECS),
+            rate_limit_window: Duration::from_secs(DEFAULT_RATE_LIMIT_WINDOW_SECS),
+            rate_limits: HashMap::from(DEFAULT_RATE_LIMITS),
+        }
+    }
+}
+
+impl LimitsConfig {
+    /// `NOTIFICATION_RATE_LIMITS` reemplaza los límites de los canales que
+    /// menciona (`email=10,webhook=100`; `0` quita el límite).
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(secs) = std::env::var("NOTIFICATION_DEDUP_WINDOW_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            config.dedup_window = Duration::from_secs(secs);
+        }
+        if let Some(secs) = std::env::var("NOTIFICATION_RATE_LIMIT_WINDOW_SECONDS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v| *v > 0)
+        {
+            config.rate_limit_window = Duration::from_secs(secs);
+        }
+        if let Ok(spec) = std::env::var("NOTIFICATION_RATE_LIMITS") {
+            match parse_rate_limits(&spec) {
+                Ok(limits) => {
+                    for (channel, limit) in limits {
+                        if limit == 0 {
+                            config.rate_limits.remove(&channel);
+                        } else {
+                            config.rate_limits.insert(channel, limit);
+                        }
+                    }
+                }
+                Err(e) => warn!("NOTIFICATION_RATE_LIMITS inválido, se usan los límites por defecto: {}", e),
+            }
+        }
+        config
+    }
+
+    pub fn rate_limit(&self, channel: ChannelKind) -> Option<u32> {
+        self.rate_limits.get(&channel).copied()
+    }
+}
+
+/// `canal=límite` separados por comas.
+pub fn parse_rate_limits(spec: &str) -> Result<Vec<(ChannelKind, u32)>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (channel, limit) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("se esperaba canal=límite: {}", entry))?;
+            let channel = ChannelKind::from_name(channel.trim())
+                .ok_or_else(|| format!("canal desconocido: {}", channel.trim()))?;
+            let limit = limit
+                .trim()
+                .parse()
+                .map_err(|_| format!("límite inválido: {}", limit.trim()))?;
+            Ok((channel, limit))
+        })
+        .collect()
+}
@@ -1,8 +1,25 @@
+pub mod bus;
+pub mod chat;
+pub mod digest;
+pub mod dispatcher;
+pub mod email;
+pub mod events;
+pub mod limits;
+pub mod preferences;
 pub mod queue;
+pub mod sse;
+pub mod telegram;
+pub mod telegram_bot;
+pub mod ticker;
 pub mod websocket;
+pub mod web_push;
 pub mod webhook;
+pub mod whatsapp;
 pub mod models;
 
+#[cfg(test)]
+mod tests;
+
 use crate::models::users::DbUser;
 use serde::{Deserialize, Serialize};
 
@@ -15,11 +32,90 @@ pub enum NotificationType {
     SystemAlert,
 }
 
+impl NotificationType {
+    pub const ALL: [NotificationType; 5] = [
+        NotificationType::PriceAlert,
+        NotificationType::MarketSentiment,
+        NotificationType::StrategyUpdate,
+        NotificationType::TradeExecution,
+        NotificationType::SystemAlert,
+    ];
+
+    /// Tipo a partir de su nombre (`price_alert`, ...).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationType::PriceAlert => "price_alert",
+            NotificationType::MarketSentiment => "market_sentiment",
+            NotificationType::StrategyUpdate => "strategy_update",
+            NotificationType::TradeExecution => "trade_execution",
+            NotificationType::SystemAlert => "system_alert",
+        }
+    }
+
+    /// Color de acento (RGB) en los canales que lo muestran.
+    pub fn color(&self) -> u32 {
+        match self {
+            NotificationType::PriceAlert => 0xF59E0B,
+            NotificationType::MarketSentiment => 0x8B5CF6,
+            NotificationType::StrategyUpdate => 0x3B82F6,
+            NotificationType::TradeExecution => 0x10B981,
+            NotificationType::SystemAlert => 0xEF4444,
+        }
+    }
+
+    pub fn default_priority(&self) -> NotificationPriority {
+        match self {
+            NotificationType::MarketSentiment => NotificationPriority::Low,
+            NotificationType::StrategyUpdate => NotificationPriority::Normal,
+            NotificationType::PriceAlert | NotificationType::TradeExecution | NotificationType::SystemAlert => {
+                NotificationPriority::High
+            }
+        }
+    }
+}
+
+/// Prioridad de una notificación; los canales pueden exigir un mínimo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl NotificationPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationPriority::Low => "low",
+            NotificationPriority::Normal => "normal",
+            NotificationPriority::High => "high",
+            NotificationPriority::Urgent => "urgent",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Notification {
     pub id: Option<i32>,
     pub user_id: i32,
+    /// Asignado al guardar la notificación en el historial.
+    #[serde(default)]
+    pub event_id: Option<i64>,
     pub notification_type: NotificationType,
+    /// Por defecto la del tipo; las urgentes saltean las horas de silencio
+    /// y los límites de envío.
+    #[serde(default)]
+    pub priority: NotificationPriority,
+    /// Las notificaciones con la misma clave dentro de la ventana de
+    /// deduplicación se descartan. Sin clave se usa `default_dedup_key`.
+    #[serde(default)]
+    pub dedup_key: Option<String>,
     pub title: String,
     pub message: String,
     pub metadata: serde_json::Value,
@@ -38,6 +134,9 @@ impl Notification {
         Self {
             id: None,
             user_id,
+            event_id: None,
+            priority: notification_type.default_priority(),
+            dedup_key: None,
             notification_type,
             title,
             message,
@@ -46,18 +145,35 @@ impl Notification {
             read_at: None,
         }
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NotificationPreference {
-    pub user_id: i32,
-    pub email_enabled: bool,
-    pub telegram_enabled: bool,
-    pub whatsapp_enabled: bool,
-    pub web_push_enabled: bool,
-    pub price_alerts_enabled: bool,
-    pub market_sentiment_enabled: bool,
-    pub strategy_updates_enabled: bool,
-    pub trade_execution_enabled: bool,
-    pub system_alerts_enabled: bool,
+    pub fn with_priority(mut self, priority: NotificationPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_dedup_key(mut self, dedup_key: impl Into<String>) -> Self {
+        self.dedup_key = Some(dedup_key.into());
+        self
+    }
+
+    /// Clave de deduplicación: la explícita o, si no, la alerta que la
+    /// generó o el contenido.
+    pub fn dedup_key(&self) -> String {
+        if let Some(key) = &self.dedup_key {
+            return key.clone();
+        }
+        match self.metadata.get("alert_id").and_then(serde_json::Value::as_i64) {
+            Some(alert_id) => format!("{}:alert:{}", self.notification_type.as_str(), alert_id),
+            None => format!("{}:{}:{}", self.notification_type.as_str(), self.title, self.message),
+        }
+    }
+
+    /// Activo al que se refiere la notificación (`metadata.asset`), en
+    /// mayúsculas.
+    pub fn asset(&self) -> Option<String> {
+        self.metadata
+            .get("asset")
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_uppercase)
+    }
 }
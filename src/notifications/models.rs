@@ -1,11 +1,18 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use super::Notification;
+
+pub use crate::models::notifications::{NotificationPreference, NotificationRule};
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct DbNotification {
     pub id: i32,
     pub user_id: i32,
+    /// Secuencia por usuario, creciente, que usan los clientes para reanudar.
+    pub event_id: i64,
     pub notification_type: String,
+    pub priority: String,
     pub title: String,
     pub message: String,
     pub metadata: serde_json::Value,
@@ -13,18 +20,67 @@ pub struct DbNotification {
     pub read_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct DbNotificationPreference {
-    pub user_id: i32,
-    pub email_enabled: bool,
-    pub telegram_enabled: bool,
-    pub whatsapp_enabled: bool,
-    pub web_push_enabled: bool,
-    pub price_alerts_enabled: bool,
-    pub market_sentiment_enabled: bool,
-    pub strategy_updates_enabled: bool,
-    pub trade_execution_enabled: bool,
-    pub system_alerts_enabled: bool,
+/// Notificación tal como la reciben los clientes por WebSocket y SSE, igual
+/// si se entrega en vivo o se reenvía desde el historial.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NotificationFrame {
+    pub id: Option<i32>,
+    pub event_id: Option<i64>,
+    pub notification_type: String,
+    pub priority: String,
+    pub title: String,
+    pub message: String,
+    pub metadata: serde_json::Value,
     pub created_at: chrono::DateTime<chrono::Utc>,
-    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub read_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<&Notification> for NotificationFrame {
+    fn from(notification: &Notification) -> Self {
+        Self {
+            id: notification.id,
+            event_id: notification.event_id,
+            notification_type: notification.notification_type.as_str().to_string(),
+            priority: notification.priority.as_str().to_string(),
+            title: notification.title.clone(),
+            message: notification.message.clone(),
+            metadata: notification.metadata.clone(),
+            created_at: notification.created_at,
+            read_at: notification.read_at,
+        }
+    }
+}
+
+impl From<DbNotification> for NotificationFrame {
+    fn from(notification: DbNotification) -> Self {
+        Self {
+            id: Some(notification.id),
+            event_id: Some(notification.event_id),
+            notification_type: notification.notification_type,
+            priority: notification.priority,
+            title: notification.title,
+            message: notification.message,
+            metadata: notification.metadata,
+            created_at: notification.created_at,
+            read_at: notification.read_at,
+        }
+    }
+}
+
+/// Filtros del listado `/notifications`. `cursor` es el `event_id` a partir
+/// del cual (excluido) se siguen listando notificaciones más antiguas.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NotificationListQuery {
+    pub notification_type: Option<String>,
+    pub unread: Option<bool>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    pub cursor: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationPage {
+    pub notifications: Vec<DbNotification>,
+    pub next_cursor: Option<i64>,
 }
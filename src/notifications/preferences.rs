@@ -0,0 +1,129 @@
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+
+use super::{dispatcher::ChannelKind, Notification, NotificationPriority};
+use crate::{
+    db::notifications,
+    models::notifications::{DigestMode, NotificationPreference, NotificationRule},
+};
+
+/// Hora local a la que se envían los resúmenes diarios.
+const DAILY_DIGEST_HOUR: u32 = 8;
+
+/// Preferencias de un usuario junto con sus reglas: decide por qué canales
+/// sale cada notificación.
+#[derive(Debug, Clone)]
+pub struct UserPreferences {
+    pub preferences: NotificationPreference,
+    pub rules: Vec<NotificationRule>,
+}
+
+impl UserPreferences {
+    /// Las del usuario, o las de por defecto si nunca las guardó.
+    pub async fn load(pool: &PgPool, user_id: i32) -> Result<Self, sqlx::Error> {
+        let preferences = match notifications::get_notification_preferences(pool, user_id).await {
+            Err(sqlx::Error::RowNotFound) => NotificationPreference::defaults(user_id),
+            other => other?,
+        };
+        let rules = notifications::list_notification_rules(pool, user_id).await?;
+
+        Ok(Self { preferences, rules })
+    }
+
+    /// El canal tiene que estar habilitado y la notificación alcanzar su
+    /// prioridad mínima. Después decide la regla más específica que
+    /// coincide o, si no hay ninguna, el interruptor del tipo.
+    pub fn allows(&self, channel: ChannelKind, notification: &Notification) -> bool {
+        if !self.preferences.channel_enabled(channel)
+            || notification.priority < self.preferences.min_priority_for(channel)
+        {
+            return false;
+        }
+
+        let asset = notification.asset();
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches(channel, &notification.notification_type, asset.as_deref()))
+            .max_by_key(|rule| rule.specificity())
+            .map_or_else(
+                || self.preferences.type_enabled(&notification.notification_type),
+                |rule| rule.enabled,
+            )
+    }
+    /// Hasta cuándo retener la notificación en `channel`: hasta el próximo
+    /// resumen si el canal los usa, y en cualquier caso hasta el fin de las
+    /// horas de silencio. `None` si sale ya. Las urgentes no se retienen,
+    /// ni tampoco la bandeja in-app y los webhooks.
+    pub fn hold_until(
+        &self,
+        channel: ChannelKind,
+        notification: &Notification,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        if matches!(channel, ChannelKind::WebSocket | ChannelKind::Webhook)
+            || notification.priority == NotificationPriority::Urgent
+        {
+            return None;
+        }
+
+        let tz = self.preferences.tz();
+        let digest_at = self
+            .preferences
+            .digest
+            .get(&channel)
+            .map(|mode| next_digest_at(now, tz, *mode));
+        let send_at = digest_at.unwrap_or(now);
+
+        match self.preferences.quiet_hours() {
+            Some((start, end)) => quiet_hours_end(send_at, tz, start, end).or(digest_at),
+            None => digest_at,
+        }
+    }
+}
+
+/// Convierte una hora local a UTC. En el salto de horario de verano la
+/// hora no existe y se toma la siguiente.
+fn local_to_utc(tz: Tz, local: NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.with_timezone(&Utc),
+        LocalResult::None => local_to_utc(tz, local + Duration::hours(1)),
+    }
+}
+
+/// Fin de las horas de silencio si `at` cae dentro de ellas.
+pub fn quiet_hours_end(at: DateTime<Utc>, tz: Tz, start: NaiveTime, end: NaiveTime) -> Option<DateTime<Utc>> {
+    let local = at.with_timezone(&tz).naive_local();
+    let time = local.time();
+    let quiet = if start < end {
+        start <= time && time < end
+    } else {
+        time >= start || time < end
+    };
+    if !quiet {
+        return None;
+    }
+
+    let end_date = if time < end { local.date() } else { local.date().succ_opt()? };
+    Some(local_to_utc(tz, end_date.and_time(end)))
+}
+
+/// Próximo envío de resúmenes posterior a `now`.
+pub fn next_digest_at(now: DateTime<Utc>, tz: Tz, mode: DigestMode) -> DateTime<Utc> {
+    let local = now.with_timezone(&tz).naive_local();
+    let next = match mode {
+        DigestMode::Hourly => {
+            let hour = local.date().and_hms_opt(local.hour(), 0, 0).unwrap_or(local);
+            hour + Duration::hours(1)
+        }
+        DigestMode::Daily => {
+            let today = local.date().and_hms_opt(DAILY_DIGEST_HOUR, 0, 0).unwrap_or(local);
+            if local < today {
+                today
+            } else {
+                today + Duration::days(1)
+            }
+        }
+    };
+    local_to_utc(tz, next)
+}
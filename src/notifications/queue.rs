@@ -1,107 +1,499 @@
-use redis::{aio::Connection, Client, RedisError};
-use serde_json::json;
-use tracing::{error, info};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+use redis::{
+    aio::ConnectionManager,
+    streams::{StreamClaimReply, StreamId, StreamPendingCountReply, StreamRangeReply, StreamReadOptions, StreamReadReply},
+    AsyncCommands, Client, RedisError,
+};
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{info, warn};
 
 use super::{Notification, NotificationType};
 
-pub struct NotificationQueue {
+const CONSUMER_GROUP: &str = "notification-workers";
+const PAYLOAD_FIELD: &str = "payload";
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_VISIBILITY_TIMEOUT_SECS: u64 = 60;
+/// Entradas pendientes revisadas por cada `pop` en busca de reintentos.
+const PENDING_SCAN_COUNT: usize = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum QueueError {
+    #[error("Error de Redis: {0}")]
+    Redis(#[from] RedisError),
+    #[error("Error serializando notificación: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Entrada inválida en la cola: {0}")]
+    InvalidEntry(String),
+}
+
+/// Una notificación entregada a un consumidor y todavía sin confirmar.
+#[derive(Debug, Clone)]
+pub struct QueuedNotification {
+    pub id: String,
+    pub notification: Notification,
+    /// Cuántas veces se entregó, contando esta.
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    /// Entregas sin ack tras las cuales la entrada pasa a dead-letter.
+    pub max_attempts: u32,
+    /// Tiempo sin ack tras el cual una entrada se vuelve a entregar.
+    pub visibility_timeout: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            visibility_timeout: Duration::from_secs(DEFAULT_VISIBILITY_TIMEOUT_SECS),
+        }
+    }
+}
+
+impl QueueConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_attempts: std::env::var("NOTIFICATION_QUEUE_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default.max_attempts),
+            visibility_timeout: std::env::var("NOTIFICATION_QUEUE_VISIBILITY_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.visibility_timeout),
+        }
+    }
+}
+
+/// Cola de notificaciones con entrega al menos una vez: lo que devuelve
+/// `pop` se vuelve a entregar si no se confirma con `ack` antes de
+/// `visibility_timeout`, hasta `max_attempts` veces.
+#[async_trait]
+pub trait NotificationQueue: Send + Sync {
+    async fn push(&self, notification: &Notification) -> Result<String, QueueError>;
+
+    /// Siguiente notificación del tipo, priorizando las entregas vencidas.
+    /// No bloquea: devuelve `None` si no hay nada disponible.
+    async fn pop(
+        &self,
+        notification_type: NotificationType,
+        consumer: &str,
+    ) -> Result<Option<QueuedNotification>, QueueError>;
+
+    async fn ack(&self, notification_type: NotificationType, id: &str) -> Result<(), QueueError>;
+
+    /// Entradas todavía sin confirmar (pendientes o sin entregar).
+    async fn len(&self, notification_type: NotificationType) -> Result<u64, QueueError>;
+
+    async fn dead_letters(
+        &self,
+        notification_type: NotificationType,
+        count: usize,
+    ) -> Result<Vec<QueuedNotification>, QueueError>;
+
+    /// Si otros procesos (como el binario `dispatcher`) pueden consumir
+    /// esta cola. Si no, el dispatcher tiene que correr en el mismo proceso.
+    fn is_shared(&self) -> bool;
+}
+
+fn stream_key(notification_type: &NotificationType) -> &'static str {
+    match notification_type {
+        NotificationType::PriceAlert => "stream:price_alerts",
+        NotificationType::MarketSentiment => "stream:market_sentiment",
+        NotificationType::StrategyUpdate => "stream:strategy_updates",
+        NotificationType::TradeExecution => "stream:trade_execution",
+        NotificationType::SystemAlert => "stream:system_alerts",
+    }
+}
+
+fn dead_letter_key(notification_type: &NotificationType) -> String {
+    format!("{}:dead", stream_key(notification_type))
+}
+
+fn parse_entry(entry: &StreamId, attempts: u32) -> Result<QueuedNotification, QueueError> {
+    let payload: String = entry
+        .get(PAYLOAD_FIELD)
+        .ok_or_else(|| QueueError::InvalidEntry(format!("{} sin payload", entry.id)))?;
+
+    Ok(QueuedNotification {
+        id: entry.id.clone(),
+        notification: serde_json::from_str(&payload)?,
+        attempts,
+    })
+}
+
+/// Cola sobre Redis Streams con un consumer group por tipo de notificación.
+pub struct RedisNotificationQueue {
     redis_client: Client,
+    config: QueueConfig,
+    connection: OnceCell<ConnectionManager>,
+    groups: Mutex<HashSet<&'static str>>,
 }
 
-impl NotificationQueue {
+impl RedisNotificationQueue {
     pub fn new(redis_url: &str) -> Result<Self, RedisError> {
+        Self::with_config(redis_url, QueueConfig::from_env())
+    }
+
+    pub fn with_config(redis_url: &str, config: QueueConfig) -> Result<Self, RedisError> {
         let redis_client = Client::open(redis_url)?;
-        Ok(Self { redis_client })
+        Ok(Self {
+            redis_client,
+            config,
+            connection: OnceCell::new(),
+            groups: Mutex::new(HashSet::new()),
+        })
     }
 
-    pub async fn get_connection(&self) -> Result<Connection, RedisError> {
-        self.redis_client.get_async_connection().await
+    pub fn client(&self) -> &Client {
+        &self.redis_client
     }
 
-    pub async fn push_notification(&self, notification: &Notification) -> Result<(), RedisError> {
-        let mut conn = self.get_connection().await?;
-        
-        let notification_json = serde_json::to_string(notification).map_err(|e| {
-            error!("Error serializando notificación: {}", e);
-            RedisError::from(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Error serializando notificación",
-            ))
-        })?;
-
-        let queue_key = match notification.notification_type {
-            NotificationType::PriceAlert => "queue:price_alerts",
-            NotificationType::MarketSentiment => "queue:market_sentiment",
-            NotificationType::StrategyUpdate => "queue:strategy_updates",
-            NotificationType::TradeExecution => "queue:trade_execution",
-            NotificationType::SystemAlert => "queue:system_alerts",
-        };
+    /// Conexión compartida; se abre en el primer uso y se reconecta sola si
+    /// Redis se cae, así los workers no quedan con una conexión rota.
+    pub async fn get_connection(&self) -> Result<ConnectionManager, RedisError> {
+        self.connection
+            .get_or_try_init(|| ConnectionManager::new(self.redis_client.clone()))
+            .await
+            .cloned()
+    }
 
-        redis::cmd("LPUSH")
-            .arg(queue_key)
-            .arg(notification_json)
-            .query_async(&mut conn)
-            .await?;
+    async fn ensure_group(&self, key: &'static str) -> Result<(), QueueError> {
+        let mut groups = self.groups.lock().await;
+        if groups.contains(key) {
+            return Ok(());
+        }
 
-        info!(
-            "Notificación agregada a la cola {}: {:?}",
-            queue_key, notification
-        );
+        let mut conn = self.get_connection().await?;
+        let created: Result<(), RedisError> = conn.xgroup_create_mkstream(key, CONSUMER_GROUP, "0").await;
+        match created {
+            Ok(()) => info!("Consumer group creado para {}", key),
+            // El grupo ya existía (otra instancia o un arranque anterior)
+            Err(e) if e.code() == Some("BUSYGROUP") => {}
+            Err(e) => return Err(e.into()),
+        }
 
+        groups.insert(key);
         Ok(())
     }
 
-    pub async fn pop_notification(
+    /// Mueve la entrada al stream de dead-letter y la quita del principal.
+    async fn dead_letter(
         &self,
-        notification_type: NotificationType,
-    ) -> Result<Option<Notification>, RedisError> {
+        notification_type: &NotificationType,
+        id: &str,
+        attempts: u32,
+    ) -> Result<(), QueueError> {
+        let key = stream_key(notification_type);
         let mut conn = self.get_connection().await?;
 
-        let queue_key = match notification_type {
-            NotificationType::PriceAlert => "queue:price_alerts",
-            NotificationType::MarketSentiment => "queue:market_sentiment",
-            NotificationType::StrategyUpdate => "queue:strategy_updates",
-            NotificationType::TradeExecution => "queue:trade_execution",
-            NotificationType::SystemAlert => "queue:system_alerts",
-        };
+        let entries: StreamRangeReply = conn.xrange(key, id, id).await?;
+        if let Some(entry) = entries.ids.first() {
+            let payload: String = entry.get(PAYLOAD_FIELD).unwrap_or_default();
+            let _: String = conn.xadd(
+                dead_letter_key(notification_type),
+                "*",
+                &[
+                    (PAYLOAD_FIELD, payload),
+                    ("original_id", id.to_string()),
+                    ("attempts", attempts.to_string()),
+                ],
+            )
+            .await?;
+        }
 
-        let notification_json: Option<String> = redis::cmd("RPOP")
-            .arg(queue_key)
-            .query_async(&mut conn)
+        let _: i64 = conn.xack(key, CONSUMER_GROUP, &[id]).await?;
+        let _: i64 = conn.xdel(key, &[id]).await?;
+        warn!("Notificación {} de {} enviada a dead-letter tras {} intentos", id, key, attempts);
+        Ok(())
+    }
+
+    /// Reclama una entrada pendiente cuyo consumidor no confirmó a tiempo.
+    async fn claim_stale(
+        &self,
+        notification_type: &NotificationType,
+        consumer: &str,
+    ) -> Result<Option<QueuedNotification>, QueueError> {
+        let key = stream_key(notification_type);
+        let visibility_ms = self.config.visibility_timeout.as_millis() as usize;
+        let mut conn = self.get_connection().await?;
+
+        let pending: StreamPendingCountReply = conn
+            .xpending_count(key, CONSUMER_GROUP, "-", "+", PENDING_SCAN_COUNT)
             .await?;
 
-        match notification_json {
-            Some(json_str) => {
-                let notification: Notification = serde_json::from_str(&json_str).map_err(|e| {
-                    error!("Error deserializando notificación: {}", e);
-                    RedisError::from(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Error deserializando notificación",
-                    ))
-                })?;
-                Ok(Some(notification))
+        for entry in pending.ids.iter().filter(|p| p.last_delivered_ms >= visibility_ms) {
+            let attempts = entry.times_delivered as u32;
+            if attempts >= self.config.max_attempts {
+                self.dead_letter(notification_type, &entry.id, attempts).await?;
+                continue;
+            }
+
+            // XCLAIM solo devuelve la entrada si sigue vencida, así dos
+            // consumidores no pueden reclamar la misma.
+            let claimed: StreamClaimReply = conn
+                .xclaim(key, CONSUMER_GROUP, consumer, visibility_ms, &[&entry.id])
+                .await?;
+            if let Some(claimed) = claimed.ids.first() {
+                return parse_entry(claimed, attempts + 1).map(Some);
             }
+        }
+
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl NotificationQueue for RedisNotificationQueue {
+    fn is_shared(&self) -> bool {
+        true
+    }
+
+    async fn push(&self, notification: &Notification) -> Result<String, QueueError> {
+        let key = stream_key(&notification.notification_type);
+        self.ensure_group(key).await?;
+
+        let payload = serde_json::to_string(notification)?;
+        let mut conn = self.get_connection().await?;
+        let id: String = conn.xadd(key, "*", &[(PAYLOAD_FIELD, payload)]).await?;
+
+        info!("Notificación agregada a la cola {} ({})", key, id);
+        Ok(id)
+    }
+
+    async fn pop(
+        &self,
+        notification_type: NotificationType,
+        consumer: &str,
+    ) -> Result<Option<QueuedNotification>, QueueError> {
+        let key = stream_key(&notification_type);
+        self.ensure_group(key).await?;
+
+        if let Some(stale) = self.claim_stale(&notification_type, consumer).await? {
+            return Ok(Some(stale));
+        }
+
+        // Sin BLOCK: la conexión es compartida y un comando bloqueante
+        // demoraría al resto.
+        let options = StreamReadOptions::default().group(CONSUMER_GROUP, consumer).count(1);
+        let mut conn = self.get_connection().await?;
+        let reply: StreamReadReply = conn.xread_options(&[key], &[">"], &options).await?;
+
+        match reply.keys.first().and_then(|k| k.ids.first()) {
+            Some(entry) => parse_entry(entry, 1).map(Some),
             None => Ok(None),
         }
     }
 
-    pub async fn get_queue_length(
+    async fn ack(&self, notification_type: NotificationType, id: &str) -> Result<(), QueueError> {
+        let key = stream_key(&notification_type);
+        let mut conn = self.get_connection().await?;
+
+        let _: i64 = conn.xack(key, CONSUMER_GROUP, &[id]).await?;
+        let _: i64 = conn.xdel(key, &[id]).await?;
+        Ok(())
+    }
+
+    async fn len(&self, notification_type: NotificationType) -> Result<u64, QueueError> {
+        let mut conn = self.get_connection().await?;
+        Ok(conn.xlen(stream_key(&notification_type)).await?)
+    }
+
+    async fn dead_letters(
         &self,
         notification_type: NotificationType,
-    ) -> Result<i64, RedisError> {
+        count: usize,
+    ) -> Result<Vec<QueuedNotification>, QueueError> {
         let mut conn = self.get_connection().await?;
+        let entries: StreamRangeReply = conn
+            .xrange_count(dead_letter_key(&notification_type), "-", "+", count)
+            .await?;
+
+        entries
+            .ids
+            .iter()
+            .map(|entry| {
+                let attempts = entry
+                    .get::<String>("attempts")
+                    .and_then(|a| a.parse().ok())
+                    .unwrap_or_default();
+                parse_entry(entry, attempts)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct PendingEntry {
+    notification: Notification,
+    attempts: u32,
+    delivered_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct InMemoryStream {
+    ready: VecDeque<(String, Notification)>,
+    pending: HashMap<String, PendingEntry>,
+    dead: Vec<QueuedNotification>,
+}
+
+/// Misma semántica que la cola de Redis, en memoria, para tests y
+/// despliegues de una sola instancia.
+pub struct InMemoryNotificationQueue {
+    config: QueueConfig,
+    streams: Mutex<HashMap<&'static str, InMemoryStream>>,
+    next_id: Mutex<u64>,
+}
+
+impl InMemoryNotificationQueue {
+    pub fn new(config: QueueConfig) -> Self {
+        Self {
+            config,
+            streams: Mutex::new(HashMap::new()),
+            next_id: Mutex::new(0),
+        }
+    }
+}
+
+impl Default for InMemoryNotificationQueue {
+    fn default() -> Self {
+        Self::new(QueueConfig::default())
+    }
+}
 
-        let queue_key = match notification_type {
-            NotificationType::PriceAlert => "queue:price_alerts",
-            NotificationType::MarketSentiment => "queue:market_sentiment",
-            NotificationType::StrategyUpdate => "queue:strategy_updates",
-            NotificationType::TradeExecution => "queue:trade_execution",
-            NotificationType::SystemAlert => "queue:system_alerts",
+#[async_trait]
+impl NotificationQueue for InMemoryNotificationQueue {
+    fn is_shared(&self) -> bool {
+        false
+    }
+
+    async fn push(&self, notification: &Notification) -> Result<String, QueueError> {
+        let id = {
+            let mut next_id = self.next_id.lock().await;
+            *next_id += 1;
+            format!("{}-0", *next_id)
         };
 
-        redis::cmd("LLEN")
-            .arg(queue_key)
-            .query_async(&mut conn)
+        self.streams
+            .lock()
             .await
+            .entry(stream_key(&notification.notification_type))
+            .or_default()
+            .ready
+            .push_back((id.clone(), notification.clone()));
+        Ok(id)
+    }
+
+    async fn pop(
+        &self,
+        notification_type: NotificationType,
+        _consumer: &str,
+    ) -> Result<Option<QueuedNotification>, QueueError> {
+        let mut streams = self.streams.lock().await;
+        let stream = streams.entry(stream_key(&notification_type)).or_default();
+
+        let mut stale: Vec<String> = stream
+            .pending
+            .iter()
+            .filter(|(_, entry)| entry.delivered_at.elapsed() >= self.config.visibility_timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        stale.sort();
+
+        for id in stale {
+            let entry = stream.pending.get_mut(&id).expect("entrada pendiente");
+            if entry.attempts >= self.config.max_attempts {
+                let entry = stream.pending.remove(&id).expect("entrada pendiente");
+                stream.dead.push(QueuedNotification {
+                    id,
+                    notification: entry.notification,
+                    attempts: entry.attempts,
+                });
+                continue;
+            }
+
+            entry.attempts += 1;
+            entry.delivered_at = Instant::now();
+            return Ok(Some(QueuedNotification {
+                id,
+                notification: entry.notification.clone(),
+                attempts: entry.attempts,
+            }));
+        }
+
+        let Some((id, notification)) = stream.ready.pop_front() else {
+            return Ok(None);
+        };
+        stream.pending.insert(
+            id.clone(),
+            PendingEntry {
+                notification: notification.clone(),
+                attempts: 1,
+                delivered_at: Instant::now(),
+            },
+        );
+
+        Ok(Some(QueuedNotification {
+            id,
+            notification,
+            attempts: 1,
+        }))
     }
+
+    async fn ack(&self, notification_type: NotificationType, id: &str) -> Result<(), QueueError> {
+        if let Some(stream) = self.streams.lock().await.get_mut(stream_key(&notification_type)) {
+            stream.pending.remove(id);
+        }
+        Ok(())
+    }
+
+    async fn len(&self, notification_type: NotificationType) -> Result<u64, QueueError> {
+        Ok(self
+            .streams
+            .lock()
+            .await
+            .get(stream_key(&notification_type))
+            .map(|stream| (stream.ready.len() + stream.pending.len()) as u64)
+            .unwrap_or(0))
+    }
+
+    async fn dead_letters(
+        &self,
+        notification_type: NotificationType,
+        count: usize,
+    ) -> Result<Vec<QueuedNotification>, QueueError> {
+        Ok(self
+            .streams
+            .lock()
+            .await
+            .get(stream_key(&notification_type))
+            .map(|stream| stream.dead.iter().take(count).cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+/// Usa Redis si `REDIS_URL` está configurado y la cola en memoria si no.
+/// Con la cola en memoria la API reparte las notificaciones ella misma
+/// (ver `create_router`).
+pub fn queue_from_env() -> Arc<dyn NotificationQueue> {
+    match std::env::var("REDIS_URL") {
+        Ok(url) => match RedisNotificationQueue::new(&url) {
+            Ok(queue) => return Arc::new(queue),
+            Err(e) => warn!("REDIS_URL inválido, usando cola en memoria: {}", e),
+        },
+        Err(_) => info!("REDIS_URL no configurado, usando cola de notificaciones en memoria"),
+    }
+
+    Arc::new(InMemoryNotificationQueue::default())
 }
@@ -0,0 +1,210 @@
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use chrono::{TimeZone, Utc};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::{
+    models::NotificationFrame,
+    websocket::{authenticate, WebSocketServer, CLOSE_FORBIDDEN, CLOSE_INTERNAL_ERROR},
+};
+use crate::{app_state::AppState, db::notifications};
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/// Tamaño de cada página al reenviar el historial.
+const REPLAY_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct SseParams {
+    /// `EventSource` no permite headers, así que el token puede ir en la query.
+    pub token: Option<String>,
+    /// Alternativa al header `Last-Event-ID` para la primera conexión.
+    pub last_event_id: Option<i64>,
+    pub client: Option<String>,
+}
+
+/// Quita la conexión del registro cuando el cliente corta el stream.
+struct ConnectionGuard {
+    server: Arc<WebSocketServer>,
+    user_id: i32,
+    connection_id: Uuid,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let server = self.server.clone();
+        let (user_id, connection_id) = (self.user_id, self.connection_id);
+        tokio::spawn(async move {
+            server.remove_connection(user_id, connection_id).await;
+            info!("Stream SSE {} cerrado para usuario {}", connection_id, user_id);
+        });
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+}
+
+fn last_event_id(headers: &HeaderMap, params: &SseParams) -> Option<i64> {
+    headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(params.last_event_id)
+}
+
+fn notification_event(event_id: Option<i64>, payload: serde_json::Value) -> Option<Event> {
+    let event = Event::default().event("notification");
+    let event = match event_id {
+        Some(id) => event.id(id.to_string()),
+        None => event,
+    };
+    event.json_data(payload).ok()
+}
+
+/// Historial posterior a `after`, leído por páginas a medida que se envía.
+fn replay_stream(
+    pool: PgPool,
+    user_id: i32,
+    after: i64,
+    last_sent: Arc<AtomicI64>,
+) -> impl Stream<Item = Event> {
+    stream::unfold(Some(after), move |cursor| {
+        let pool = pool.clone();
+        let last_sent = last_sent.clone();
+        async move {
+            let cursor = cursor?;
+            let page = match notifications::list_notifications_after(&pool, user_id, cursor, REPLAY_PAGE_SIZE).await {
+                Ok(page) => page,
+                Err(e) => {
+                    error!("Error obteniendo historial del usuario {}: {}", user_id, e);
+                    return None;
+                }
+            };
+
+            let next = (page.len() as i64 == REPLAY_PAGE_SIZE)
+                .then(|| page.last().map(|n| n.event_id))
+                .flatten();
+            if let Some(last) = page.last() {
+                last_sent.store(last.event_id, Ordering::Relaxed);
+            }
+
+            let events: Vec<Event> = page
+                .into_iter()
+                .filter_map(|n| notification_event(Some(n.event_id), json!(NotificationFrame::from(n))))
+                .collect();
+            Some((stream::iter(events), next))
+        }
+    })
+    .flatten()
+}
+
+/// Stream SSE con las mismas notificaciones que el WebSocket. Con
+/// `Last-Event-ID` primero reenvía lo que el cliente se perdió.
+pub async fn sse_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SseParams>,
+) -> Response {
+    let Some(token) = bearer_token(&headers).or_else(|| params.token.clone()) else {
+        return (StatusCode::UNAUTHORIZED, "Token requerido".to_string()).into_response();
+    };
+
+    let claims = match authenticate(&state.pool, &token).await {
+        Ok(claims) => claims,
+        Err((code, reason)) => {
+            let status = match code {
+                CLOSE_FORBIDDEN => StatusCode::FORBIDDEN,
+                CLOSE_INTERNAL_ERROR => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::UNAUTHORIZED,
+            };
+            return (status, reason).into_response();
+        }
+    };
+
+    let client_type = params
+        .client
+        .as_deref()
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| "sse".to_string());
+
+    // Registrar antes de leer el historial para no perder lo que llegue
+    // mientras tanto; los duplicados se descartan por event_id.
+    let Ok((connection_id, receiver)) = state.ws_server.register_sse(claims.user_id, client_type).await else {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            "Límite de conexiones alcanzado".to_string(),
+        )
+            .into_response();
+    };
+    let guard = ConnectionGuard {
+        server: state.ws_server.clone(),
+        user_id: claims.user_id,
+        connection_id,
+    };
+
+    let resume_from = last_event_id(&headers, &params);
+    let last_sent = Arc::new(AtomicI64::new(resume_from.unwrap_or(0)));
+
+    let replay = match resume_from {
+        Some(after) => replay_stream(state.pool.clone(), claims.user_id, after, last_sent.clone()).boxed(),
+        None => stream::empty().boxed(),
+    };
+
+    let live = stream::unfold((receiver, guard), |(mut receiver, guard)| async move {
+        let notification = receiver.recv().await?;
+        Some((notification, (receiver, guard)))
+    })
+    .filter_map(move |notification| {
+        let last_sent = last_sent.clone();
+        async move {
+            if let Some(event_id) = notification.event_id {
+                if event_id <= last_sent.load(Ordering::Relaxed) {
+                    return None;
+                }
+                last_sent.store(event_id, Ordering::Relaxed);
+            }
+            notification_event(notification.event_id, json!(NotificationFrame::from(&notification)))
+        }
+    });
+
+    // El stream termina cuando expira el token
+    let until_expiry = Utc
+        .timestamp_opt(claims.exp, 0)
+        .single()
+        .and_then(|exp| (exp - Utc::now()).to_std().ok())
+        .unwrap_or_default();
+
+    let events = replay
+        .chain(live)
+        .take_until(tokio::time::sleep(until_expiry))
+        .map(Ok::<_, Infallible>);
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL).text("keep-alive"))
+        .into_response()
+}
@@ -0,0 +1,253 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use rand::Rng;
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use sqlx::PgPool;
+
+use super::{
+    dispatcher::{ChannelError, ChannelKind, NotificationChannel},
+    Notification,
+};
+use crate::db::telegram;
+
+const DEFAULT_API_BASE_URL: &str = "https://api.telegram.org";
+/// Segundos que Telegram mantiene abierto cada `getUpdates`.
+pub(crate) const LONG_POLL_TIMEOUT: u64 = 30;
+/// Sin caracteres ambiguos (0/O, 1/I) para que se pueda tipear a mano.
+const LINK_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+pub(crate) const LINK_CODE_LEN: usize = 8;
+pub const LINK_CODE_TTL_MINUTES: i64 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TelegramError {
+    #[error("Error HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Error de la API de Telegram ({code}): {description}")]
+    Api { code: i32, description: String },
+}
+
+impl TelegramError {
+    /// El usuario bloqueó el bot o el chat ya no existe: reintentar no sirve.
+    fn is_unreachable_chat(&self) -> bool {
+        matches!(self, TelegramError::Api { code: 400 | 403, .. })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+    error_code: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<Message>,
+    pub callback_query: Option<CallbackQuery>,
+}
+
+/// Pulsación de un botón de un teclado inline.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CallbackQuery {
+    pub id: String,
+    pub message: Option<Message>,
+    pub data: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Message {
+    pub message_id: i64,
+    pub chat: Chat,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chat {
+    pub id: i64,
+}
+
+/// Cliente mínimo de la Bot API. La URL base es configurable para poder
+/// apuntarla a un servidor local en las pruebas.
+#[derive(Clone)]
+pub struct TelegramClient {
+    http_client: Client,
+    base_url: String,
+    token: String,
+}
+
+impl TelegramClient {
+    pub fn new(base_url: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            http_client: Client::builder()
+                .timeout(Duration::from_secs(LONG_POLL_TIMEOUT + 10))
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: token.into(),
+        }
+    }
+
+    /// `TELEGRAM_BOT_TOKEN` y, opcionalmente, `TELEGRAM_API_BASE_URL`.
+    /// `None` si no hay token configurado.
+    pub fn from_env() -> Option<Self> {
+        let token = std::env::var("TELEGRAM_BOT_TOKEN").ok().filter(|t| !t.is_empty())?;
+        let base_url =
+            std::env::var("TELEGRAM_API_BASE_URL").unwrap_or_else(|_| DEFAULT_API_BASE_URL.to_string());
+        Some(Self::new(base_url, token))
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, body: &JsonValue) -> Result<T, TelegramError> {
+        let url = format!("{}/bot{}/{}", self.base_url, self.token, method);
+        let response: ApiResponse<T> = self.http_client.post(&url).json(body).send().await?.json().await?;
+
+        match (response.ok, response.result) {
+            (true, Some(result)) => Ok(result),
+            _ => Err(TelegramError::Api {
+                code: response.error_code.unwrap_or_default(),
+                description: response.description.unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// Envía `text`, que ya debe estar escapado para MarkdownV2.
+    pub async fn send_message(&self, chat_id: &str, text: &str) -> Result<(), TelegramError> {
+        self.send_message_with_keyboard(chat_id, text, None).await
+    }
+
+    /// Como `send_message`, con un teclado inline opcional
+    /// (`[[{"text": ..., "callback_data": ...}]]`).
+    pub async fn send_message_with_keyboard(
+        &self,
+        chat_id: &str,
+        text: &str,
+        keyboard: Option<JsonValue>,
+    ) -> Result<(), TelegramError> {
+        let mut body = json!({
+            "chat_id": chat_id,
+            "text": text,
+            "parse_mode": "MarkdownV2",
+        });
+        if let Some(keyboard) = keyboard {
+            body["reply_markup"] = json!({ "inline_keyboard": keyboard });
+        }
+        self.call::<JsonValue>("sendMessage", &body).await.map(|_| ())
+    }
+
+    pub async fn edit_message_text(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+        text: &str,
+        keyboard: Option<JsonValue>,
+    ) -> Result<(), TelegramError> {
+        let mut body = json!({
+            "chat_id": chat_id,
+            "message_id": message_id,
+            "text": text,
+            "parse_mode": "MarkdownV2",
+        });
+        if let Some(keyboard) = keyboard {
+            body["reply_markup"] = json!({ "inline_keyboard": keyboard });
+        }
+        self.call::<JsonValue>("editMessageText", &body).await.map(|_| ())
+    }
+
+    /// Confirma la pulsación de un botón; `text` se muestra como aviso breve.
+    pub async fn answer_callback_query(&self, callback_query_id: &str, text: &str) -> Result<(), TelegramError> {
+        let body = json!({
+            "callback_query_id": callback_query_id,
+            "text": text,
+        });
+        self.call::<JsonValue>("answerCallbackQuery", &body).await.map(|_| ())
+    }
+
+    pub async fn get_updates(&self, offset: i64, timeout_secs: u64) -> Result<Vec<Update>, TelegramError> {
+        let body = json!({
+            "offset": offset,
+            "timeout": timeout_secs,
+            "allowed_updates": ["message", "callback_query"],
+        });
+        self.call("getUpdates", &body).await
+    }
+}
+
+/// Escapa los caracteres reservados de MarkdownV2.
+pub fn escape_markdown_v2(text: &str) -> String {
+    const RESERVED: &[char] = &[
+        '_', '*', '[', ']', '(', ')', '~', '`', '>', '#', '+', '-', '=', '|', '{', '}', '.', '!', '\\',
+    ];
+
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if RESERVED.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub fn format_notification(notification: &Notification) -> String {
+    format!(
+        "*{}*\n{}",
+        escape_markdown_v2(&notification.title),
+        escape_markdown_v2(&notification.message)
+    )
+}
+
+pub fn generate_link_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..LINK_CODE_LEN)
+        .map(|_| LINK_CODE_ALPHABET[rng.gen_range(0..LINK_CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Canal del dispatcher: envía la notificación al chat vinculado.
+pub struct TelegramChannel {
+    pool: PgPool,
+    client: TelegramClient,
+}
+
+impl TelegramChannel {
+    pub fn new(pool: PgPool, client: TelegramClient) -> Self {
+        Self { pool, client }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for TelegramChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Telegram
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), ChannelError> {
+        let link = telegram::get_telegram_link(&self.pool, notification.user_id)
+            .await
+            .map_err(|e| ChannelError::Failed(e.to_string()))?
+            .ok_or_else(|| ChannelError::NotConfigured("sin chat de Telegram vinculado".to_string()))?;
+        if link.is_muted() {
+            return Err(ChannelError::NotConfigured("silenciado con /mute".to_string()));
+        }
+        let chat_id = link.telegram_id;
+
+        match self.client.send_message(&chat_id, &format_notification(notification)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.is_unreachable_chat() => Err(ChannelError::NotConfigured(e.to_string())),
+            Err(e) => Err(ChannelError::Failed(e.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkCode {
+    pub code: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Enlace `https://t.me/<bot>?start=<code>` si `TELEGRAM_BOT_USERNAME`
+    /// está configurado.
+    pub deep_link: Option<String>,
+}
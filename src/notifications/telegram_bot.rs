@@ -0,0 +1,517 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use reqwest::Client;
+use serde_json::{json, Value as JsonValue};
+use sqlx::{types::BigDecimal, PgPool};
+use tracing::{error, info, warn};
+
+use super::telegram::{escape_markdown_v2, CallbackQuery, Message, TelegramClient, Update, LINK_CODE_LEN, LONG_POLL_TIMEOUT};
+use crate::{
+    api::tickers::fetch_last_price,
+    db::{asset_pairs, instruments::find_instrument, price_alerts, telegram, users},
+    i18n::Locale,
+    models::{instruments::split_symbol, price_alerts::CreatePriceAlertRequest},
+};
+
+const POLL_ERROR_DELAY: Duration = Duration::from_secs(5);
+const DEFAULT_EXCHANGE: &str = "binance";
+const DEFAULT_QUOTE_ASSET: &str = "USDT";
+/// Opciones de "posponer" que se ofrecen en cada alerta.
+const SNOOZE_OPTIONS: [&str; 2] = ["1h", "1d"];
+/// Tope de `/mute` y de "posponer".
+const MAX_DURATION_DAYS: i64 = 365;
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `/start` con el código de vinculación opcional.
+    Start(Option<String>),
+    Help,
+    Alerts,
+    Alert {
+        asset: String,
+        condition: String,
+        target_price: BigDecimal,
+    },
+    Pairs,
+    Mute(chrono::Duration),
+    Unmute,
+    Price {
+        base: String,
+        quote: String,
+    },
+    /// Mensaje con la forma de un código de vinculación.
+    Code(String),
+}
+
+/// Mensaje que no se pudo interpretar; se responde con el uso correcto.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UsageError {
+    NotACommand,
+    UnknownCommand,
+    InvalidCondition,
+    InvalidPrice(String),
+    Alert,
+    Mute,
+    Price,
+}
+
+impl UsageError {
+    pub fn message(&self, locale: Locale) -> String {
+        match self {
+            UsageError::NotACommand => locale.t("telegram_bot.help"),
+            UsageError::UnknownCommand => {
+                locale.t_with("telegram_bot.unknown_command", &[("help", &locale.t("telegram_bot.help"))])
+            }
+            UsageError::InvalidCondition => locale.t("telegram_bot.invalid_condition"),
+            UsageError::InvalidPrice(price) => locale.t_with("telegram_bot.invalid_price", &[("price", price)]),
+            UsageError::Alert => locale.t("telegram_bot.usage_alert"),
+            UsageError::Mute => locale.t_with("telegram_bot.usage_mute", &[("max_days", &MAX_DURATION_DAYS)]),
+            UsageError::Price => locale.t("telegram_bot.usage_price"),
+        }
+    }
+}
+
+/// Acepta `30m`, `2h` o `1d`, hasta `MAX_DURATION_DAYS`.
+pub fn parse_duration(text: &str) -> Option<chrono::Duration> {
+    let text = text.trim().to_lowercase();
+    let (split, unit) = text.char_indices().last()?;
+    let amount: i64 = text[..split].parse().ok().filter(|n| *n > 0)?;
+
+    let duration = match unit {
+        'm' => chrono::Duration::try_minutes(amount)?,
+        'h' => chrono::Duration::try_hours(amount)?,
+        'd' => chrono::Duration::try_days(amount)?,
+        _ => return None,
+    };
+    (duration <= chrono::Duration::days(MAX_DURATION_DAYS)).then_some(duration)
+}
+
+/// Interpreta un mensaje. `Err` indica qué uso hay que responder.
+pub fn parse_command(text: &str) -> Result<Command, UsageError> {
+    let text = text.trim();
+    if !text.starts_with('/') {
+        let looks_like_code = text.len() == LINK_CODE_LEN && text.chars().all(|c| c.is_ascii_alphanumeric());
+        return if looks_like_code {
+            Ok(Command::Code(text.to_uppercase()))
+        } else {
+            Err(UsageError::NotACommand)
+        };
+    }
+
+    let mut parts = text.split_whitespace();
+    let command = parts.next().unwrap_or_default();
+    // En grupos llega como `/alerts@NombreDelBot`
+    let command = command.split('@').next().unwrap_or_default().to_lowercase();
+    let args: Vec<&str> = parts.collect();
+
+    match (command.as_str(), args.as_slice()) {
+        ("/start", []) => Ok(Command::Start(None)),
+        ("/start", [code]) => Ok(Command::Start(Some(code.to_uppercase()))),
+        ("/help", _) => Ok(Command::Help),
+        ("/alerts", _) => Ok(Command::Alerts),
+        ("/alert", [asset, condition, price]) => {
+            let condition = match condition.to_lowercase().as_str() {
+                "above" | ">" => "above",
+                "below" | "<" => "below",
+                _ => return Err(UsageError::InvalidCondition),
+            };
+            let target_price = BigDecimal::from_str(&price.replace(',', "."))
+                .ok()
+                .filter(|p| *p > BigDecimal::from(0))
+                .ok_or_else(|| UsageError::InvalidPrice(price.to_string()))?;
+            Ok(Command::Alert {
+                asset: asset.to_uppercase(),
+                condition: condition.to_string(),
+                target_price,
+            })
+        }
+        ("/alert", _) => Err(UsageError::Alert),
+        ("/pairs", _) => Ok(Command::Pairs),
+        ("/mute", [duration]) => parse_duration(duration).map(Command::Mute).ok_or(UsageError::Mute),
+        ("/mute", _) => Err(UsageError::Mute),
+        ("/unmute", _) => Ok(Command::Unmute),
+        ("/price", [symbol]) => {
+            let (base, quote) =
+                split_symbol(symbol).unwrap_or_else(|| (symbol.to_uppercase(), DEFAULT_QUOTE_ASSET.to_string()));
+            Ok(Command::Price { base, quote })
+        }
+        ("/price", _) => Err(UsageError::Price),
+        _ => Err(UsageError::UnknownCommand),
+    }
+}
+
+/// Acción de un botón inline: `del:<id>` o `snooze:<id>:<duración>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallbackAction {
+    DeleteAlert(i32),
+    SnoozeAlert(i32, chrono::Duration),
+}
+
+pub fn parse_callback(data: &str) -> Option<CallbackAction> {
+    let mut parts = data.split(':');
+    match (parts.next()?, parts.next()?.parse().ok()?, parts.next()) {
+        ("del", id, None) => Some(CallbackAction::DeleteAlert(id)),
+        ("snooze", id, Some(duration)) => Some(CallbackAction::SnoozeAlert(id, parse_duration(duration)?)),
+        _ => None,
+    }
+}
+
+/// Respuesta a un mensaje: texto plano (se escapa al enviar) y teclado
+/// inline opcional.
+struct Reply {
+    text: String,
+    keyboard: Option<JsonValue>,
+}
+
+impl From<String> for Reply {
+    fn from(text: String) -> Self {
+        Self { text, keyboard: None }
+    }
+}
+
+impl From<&str> for Reply {
+    fn from(text: &str) -> Self {
+        text.to_string().into()
+    }
+}
+
+/// Atiende los mensajes del bot: vinculación y comandos sobre los datos del
+/// usuario vinculado al chat.
+pub struct TelegramBot {
+    pool: PgPool,
+    client: TelegramClient,
+    http_client: Client,
+}
+
+impl TelegramBot {
+    pub fn new(pool: PgPool, client: TelegramClient) -> Self {
+        Self {
+            pool,
+            client,
+            http_client: Client::new(),
+        }
+    }
+
+    pub async fn handle_update(&self, update: Update) {
+        if let Some(callback) = update.callback_query {
+            self.handle_callback(callback).await;
+        } else if let Some(message) = update.message {
+            self.handle_message(message).await;
+        }
+    }
+
+    async fn handle_message(&self, message: Message) {
+        let Some(text) = message.text.as_deref() else {
+            return;
+        };
+        let chat_id = message.chat.id.to_string();
+
+        // Sin chat vinculado se responde en el idioma por defecto
+        let linked = telegram::find_user_by_telegram_id(&self.pool, &chat_id).await;
+        let locale = match linked {
+            Ok(Some(user_id)) => self.user_locale(user_id).await,
+            _ => Locale::default(),
+        };
+
+        let reply = match parse_command(text) {
+            Ok(Command::Start(None)) => locale.t("telegram_bot.welcome").into(),
+            Ok(Command::Start(Some(code))) | Ok(Command::Code(code)) => self.link(&chat_id, &code, locale).await.into(),
+            Ok(Command::Help) => locale.t("telegram_bot.help").into(),
+            Ok(command) => match linked {
+                Ok(Some(user_id)) => self.run_command(user_id, command, locale).await,
+                Ok(None) => locale.t("telegram_bot.not_linked").into(),
+                Err(e) => {
+                    error!("Error buscando usuario del chat {}: {}", chat_id, e);
+                    locale.t("telegram_bot.internal_error").into()
+                }
+            },
+            Err(usage) => usage.message(locale).into(),
+        };
+
+        let text = escape_markdown_v2(&reply.text);
+        if let Err(e) = self.client.send_message_with_keyboard(&chat_id, &text, reply.keyboard).await {
+            warn!("Error respondiendo al chat {}: {}", chat_id, e);
+        }
+    }
+
+    async fn link(&self, chat_id: &str, code: &str, locale: Locale) -> String {
+        match telegram::consume_link_code(&self.pool, code, chat_id).await {
+            Ok(Some(user_id)) => {
+                info!("Chat de Telegram {} vinculado al usuario {}", chat_id, user_id);
+                self.user_locale(user_id).await.t("telegram_bot.linked")
+            }
+            Ok(None) => locale.t("telegram_bot.invalid_code"),
+            Err(e) => {
+                error!("Error vinculando chat de Telegram {}: {}", chat_id, e);
+                locale.t("telegram_bot.link_error")
+            }
+        }
+    }
+
+    async fn run_command(&self, user_id: i32, command: Command, locale: Locale) -> Reply {
+        match command {
+            Command::Alerts => self.list_alerts(user_id, locale).await,
+            Command::Alert {
+                asset,
+                condition,
+                target_price,
+            } => {
+                // Las alertas se evalúan contra el par con la cotización por defecto
+                match find_instrument(&self.pool, DEFAULT_EXCHANGE, &asset, DEFAULT_QUOTE_ASSET).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        return locale
+                            .t_with("telegram_bot.unknown_pair", &[("base", &asset), ("quote", &DEFAULT_QUOTE_ASSET)])
+                            .into()
+                    }
+                    Err(e) => {
+                        error!("Error buscando el instrumento de la alerta: {}", e);
+                        return locale.t("telegram_bot.internal_error").into();
+                    }
+                }
+
+                let request = CreatePriceAlertRequest {
+                    target_price,
+                    condition,
+                    asset,
+                    trigger_price: None,
+                };
+                match price_alerts::create_price_alert(&self.pool, user_id, &request).await {
+                    Ok(alert) => locale
+                        .t_with(
+                            "telegram_bot.alert_created",
+                            &[
+                                ("id", &alert.id),
+                                ("asset", &alert.asset),
+                                ("condition", &alert.condition),
+                                ("target_price", &alert.target_price),
+                            ],
+                        )
+                        .into(),
+                    Err(e) => {
+                        error!("Error creando alerta desde Telegram: {}", e);
+                        locale.t("telegram_bot.alert_create_error").into()
+                    }
+                }
+            }
+            Command::Pairs => match asset_pairs::list_asset_pairs(&self.pool, user_id).await {
+                Ok(pairs) if pairs.is_empty() => locale.t("telegram_bot.no_pairs").into(),
+                Ok(pairs) => pairs
+                    .iter()
+                    .map(|p| {
+                        locale.t_with(
+                            "telegram_bot.pair",
+                            &[("base", &p.base_asset), ("quote", &p.quote_asset), ("exchange", &p.exchange)],
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+                    .into(),
+                Err(_) => locale.t("telegram_bot.pairs_error").into(),
+            },
+            Command::Mute(duration) => {
+                let Some(until) = Utc::now().checked_add_signed(duration) else {
+                    return locale.t("telegram_bot.invalid_duration").into();
+                };
+                match telegram::set_muted_until(&self.pool, user_id, Some(until)).await {
+                    Ok(()) => locale
+                        .t_with("telegram_bot.muted", &[("until", &until.format(DATE_FORMAT))])
+                        .into(),
+                    Err(e) => {
+                        error!("Error silenciando Telegram del usuario {}: {}", user_id, e);
+                        locale.t("telegram_bot.mute_error").into()
+                    }
+                }
+            }
+            Command::Unmute => match telegram::set_muted_until(&self.pool, user_id, None).await {
+                Ok(()) => locale.t("telegram_bot.unmuted").into(),
+                Err(_) => locale.t("telegram_bot.unmute_error").into(),
+            },
+            Command::Price { base, quote } => self.price(&base, &quote, locale).await,
+            Command::Start(_) | Command::Code(_) | Command::Help => locale.t("telegram_bot.help").into(),
+        }
+    }
+
+    async fn list_alerts(&self, user_id: i32, locale: Locale) -> Reply {
+        let alerts = match price_alerts::list_price_alerts(&self.pool, user_id).await {
+            Ok(alerts) => alerts,
+            Err(e) => {
+                error!("Error listando alertas del usuario {}: {}", user_id, e);
+                return locale.t("telegram_bot.alerts_error").into();
+            }
+        };
+        if alerts.is_empty() {
+            return locale.t("telegram_bot.no_alerts").into();
+        }
+
+        let now = Utc::now();
+        let text = alerts
+            .iter()
+            .map(|a| {
+                let line = locale.t_with(
+                    "telegram_bot.alert",
+                    &[
+                        ("id", &a.id),
+                        ("asset", &a.asset),
+                        ("condition", &a.condition),
+                        ("target_price", &a.target_price),
+                    ],
+                );
+                match a.snoozed_until.filter(|until| *until > now) {
+                    Some(until) => locale.t_with(
+                        "telegram_bot.alert_snoozed",
+                        &[("alert", &line), ("until", &until.format(DATE_FORMAT))],
+                    ),
+                    None => line,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // Una fila de botones por alerta
+        let keyboard = alerts
+            .iter()
+            .map(|a| {
+                let mut row = vec![json!({
+                    "text": locale.t_with("telegram_bot.delete_button", &[("id", &a.id)]),
+                    "callback_data": format!("del:{}", a.id),
+                })];
+                row.extend(SNOOZE_OPTIONS.iter().map(|d| {
+                    json!({
+                        "text": locale.t_with("telegram_bot.snooze_button", &[("duration", d)]),
+                        "callback_data": format!("snooze:{}:{}", a.id, d),
+                    })
+                }));
+                JsonValue::Array(row)
+            })
+            .collect();
+
+        Reply {
+            text,
+            keyboard: Some(JsonValue::Array(keyboard)),
+        }
+    }
+
+    async fn price(&self, base: &str, quote: &str, locale: Locale) -> Reply {
+        let instrument = match find_instrument(&self.pool, DEFAULT_EXCHANGE, base, quote).await {
+            Ok(Some(instrument)) => instrument,
+            Ok(None) => {
+                return locale
+                    .t_with("telegram_bot.unknown_pair", &[("base", &base), ("quote", &quote)])
+                    .into()
+            }
+            Err(_) => return locale.t("telegram_bot.internal_error").into(),
+        };
+
+        match fetch_last_price(&self.http_client, &instrument).await {
+            Ok(price) => format!("{}: {}", instrument.symbol, price).into(),
+            Err(e) => {
+                warn!("Error obteniendo precio de {}: {}", instrument.symbol, e);
+                locale
+                    .t_with("telegram_bot.price_error", &[("symbol", &instrument.symbol)])
+                    .into()
+            }
+        }
+    }
+
+    async fn handle_callback(&self, callback: CallbackQuery) {
+        let Some(message) = callback.message else {
+            return;
+        };
+        let chat_id = message.chat.id.to_string();
+
+        let user_id = match telegram::find_user_by_telegram_id(&self.pool, &chat_id).await {
+            Ok(user_id) => user_id,
+            Err(e) => {
+                error!("Error buscando usuario del chat {}: {}", chat_id, e);
+                None
+            }
+        };
+
+        let locale = match user_id {
+            Some(user_id) => self.user_locale(user_id).await,
+            None => Locale::default(),
+        };
+
+        let notice = match (user_id, callback.data.as_deref().and_then(parse_callback)) {
+            (Some(user_id), Some(action)) => self.run_callback(user_id, action, locale).await,
+            (Some(_), None) => locale.t("telegram_bot.unknown_action"),
+            (None, _) => locale.t("telegram_bot.chat_not_linked"),
+        };
+
+        if let Err(e) = self.client.answer_callback_query(&callback.id, &notice).await {
+            warn!("Error respondiendo callback en el chat {}: {}", chat_id, e);
+        }
+
+        // Actualizar el listado para reflejar el cambio
+        if let Some(user_id) = user_id {
+            let listing = self.list_alerts(user_id, locale).await;
+            let text = format!("{}\n\n{}", notice, listing.text);
+            if let Err(e) = self
+                .client
+                .edit_message_text(&chat_id, message.message_id, &escape_markdown_v2(&text), listing.keyboard)
+                .await
+            {
+                warn!("Error actualizando mensaje en el chat {}: {}", chat_id, e);
+            }
+        }
+    }
+
+    async fn run_callback(&self, user_id: i32, action: CallbackAction, locale: Locale) -> String {
+        match action {
+            CallbackAction::DeleteAlert(id) => match price_alerts::delete_price_alert(&self.pool, id, user_id).await {
+                Ok(true) => locale.t_with("telegram_bot.alert_deleted", &[("id", &id)]),
+                Ok(false) => locale.t_with("telegram_bot.alert_not_found", &[("id", &id)]),
+                Err(_) => locale.t("telegram_bot.delete_error"),
+            },
+            CallbackAction::SnoozeAlert(id, duration) => {
+                let Some(until) = Utc::now().checked_add_signed(duration) else {
+                    return locale.t("telegram_bot.invalid_duration");
+                };
+                match price_alerts::snooze_price_alert(&self.pool, id, user_id, until).await {
+                    Ok(true) => locale.t_with(
+                        "telegram_bot.alert_snoozed_until",
+                        &[("id", &id), ("until", &until.format(DATE_FORMAT))],
+                    ),
+                    Ok(false) => locale.t_with("telegram_bot.alert_not_found", &[("id", &id)]),
+                    Err(_) => locale.t("telegram_bot.snooze_error"),
+                }
+            }
+        }
+    }
+
+    /// Idioma del usuario vinculado; si no se puede leer, el de por defecto.
+    async fn user_locale(&self, user_id: i32) -> Locale {
+        users::get_user_locale(&self.pool, user_id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Error leyendo el idioma del usuario {}: {}", user_id, e);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    /// Long polling de `getUpdates`, para cuando no se registra el webhook
+    /// `/telegram/webhook` (p. ej. si la API no es accesible desde afuera).
+    pub async fn run_polling(self: Arc<Self>) {
+        let mut offset = 0;
+        info!("Bot de Telegram escuchando actualizaciones");
+
+        loop {
+            match self.client.get_updates(offset, LONG_POLL_TIMEOUT).await {
+                Ok(updates) => {
+                    for update in updates {
+                        offset = offset.max(update.update_id + 1);
+                        self.handle_update(update).await;
+                    }
+                }
+                Err(e) => {
+                    error!("Error obteniendo actualizaciones de Telegram: {}", e);
+                    tokio::time::sleep(POLL_ERROR_DELAY).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,630 @@
+use std::time::Duration;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::json;
+
+use super::bus::{publish_disconnect, BusMessage, InMemoryBus, NotificationBus};
+use super::digest::build_digest;
+use super::dispatcher::ChannelKind;
+use super::preferences::{next_digest_at, quiet_hours_end, UserPreferences};
+use super::queue::{InMemoryNotificationQueue, NotificationQueue, QueueConfig};
+use super::chat::{discord_payload, retry_after, slack_payload, ChatMessage};
+use super::email::{render_email, template_environment, unsubscribe_token, verify_unsubscribe_token};
+use super::events::{self, WebhookEvent};
+use super::limits::parse_rate_limits;
+use super::models::{DbNotification, NotificationFrame};
+use super::ticker::{TickerChannel, TickerFeed};
+use super::websocket::{add_subscriptions, max_connections_per_user, WebSocketServer, MAX_TICKER_SUBSCRIPTIONS};
+use super::telegram_bot::{parse_callback, parse_command, CallbackAction, Command, UsageError};
+use super::telegram::{escape_markdown_v2, format_notification, TelegramClient};
+use super::web_push::{encrypt_with, parse_auth_secret, parse_p256dh, VapidKeys, MAX_PAYLOAD_LEN};
+use super::webhook::{
+    matches_event, sign_payload, validate_event_patterns, validate_webhook_url, verify_signature, WebhookRetryPolicy,
+};
+use super::whatsapp::{normalize_phone_number, verify_webhook_signature, within_session_window, WhatsAppClient};
+use super::{Notification, NotificationPriority, NotificationType};
+use crate::i18n::Locale;
+use crate::models::notifications::{DigestMode, NotificationPreference, NotificationRule};
+
+fn price_alert(user_id: i32) -> Notification {
+    Notification::new(
+        user_id,
+        NotificationType::PriceAlert,
+        "BTC/USDT".to_string(),
+        "Precio objetivo alcanzado".to_string(),
+        json!({}),
+    )
+}
+
+#[tokio::test]
+async fn test_queue_ack_removes_entry() {
+    let queue = InMemoryNotificationQueue::default();
+    queue.push(&price_alert(1)).await.unwrap();
+
+    let entry = queue.pop(NotificationType::PriceAlert, "worker-1").await.unwrap().unwrap();
+    assert_eq!(entry.attempts, 1);
+    assert_eq!(queue.len(NotificationType::PriceAlert).await.unwrap(), 1);
+
+    queue.ack(NotificationType::PriceAlert, &entry.id).await.unwrap();
+    assert_eq!(queue.len(NotificationType::PriceAlert).await.unwrap(), 0);
+    assert!(queue.pop(NotificationType::PriceAlert, "worker-1").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_queue_redelivers_and_dead_letters() {
+    let queue = InMemoryNotificationQueue::new(QueueConfig {
+        max_attempts: 2,
+        visibility_timeout: Duration::ZERO,
+    });
+    queue.push(&price_alert(1)).await.unwrap();
+
+    let first = queue.pop(NotificationType::PriceAlert, "worker-1").await.unwrap().unwrap();
+    let second = queue.pop(NotificationType::PriceAlert, "worker-2").await.unwrap().unwrap();
+    assert_eq!(first.id, second.id);
+    assert_eq!(second.attempts, 2);
+
+    // Superado el máximo de intentos deja de entregarse
+    assert!(queue.pop(NotificationType::PriceAlert, "worker-1").await.unwrap().is_none());
+
+    let dead = queue.dead_letters(NotificationType::PriceAlert, 10).await.unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].id, first.id);
+    assert_eq!(queue.len(NotificationType::PriceAlert).await.unwrap(), 0);
+}
+
+#[test]
+fn test_webhook_signature_covers_timestamp() {
+    let body = r#"{"event":"price_alert"}"#;
+    let now = chrono::Utc::now().timestamp();
+    let header = sign_payload("whsec_test", now, body).unwrap();
+
+    assert!(header.starts_with("v1="));
+    assert!(verify_signature("whsec_test", now, body, &header, 300));
+    // Otro timestamp con la misma firma no verifica
+    assert!(!verify_signature("whsec_test", now + 1, body, &header, 300));
+    // Fuera de la tolerancia se rechaza aunque la firma sea válida
+    let old = now - 600;
+    let old_header = sign_payload("whsec_test", old, body).unwrap();
+    assert!(!verify_signature("whsec_test", old, body, &old_header, 300));
+
+    // Durante una rotación basta con que coincida una de las firmas
+    let rotated = format!("{},{}", sign_payload("whsec_new", now, body).unwrap(), header);
+    assert!(verify_signature("whsec_test", now, body, &rotated, 300));
+}
+
+#[test]
+fn test_webhook_event_patterns() {
+    assert!(matches_event(&json!([]), "trade_execution"));
+    assert!(matches_event(&json!(["*"]), "trade_execution"));
+    assert!(matches_event(&json!(["price_alert.*"]), "price_alert.created"));
+    assert!(matches_event(&json!(["price_alert.*"]), "price_alert"));
+    assert!(!matches_event(&json!(["price_alert.*"]), "price_alerts.created"));
+    assert!(!matches_event(&json!(["price_alert.created"]), "price_alert.triggered"));
+    // Nombres antiguos guardados como variantes del enum
+    assert!(matches_event(&json!(["PriceAlert"]), "price_alert.triggered"));
+    assert!(matches_event(&json!(["strategy_update"]), "strategy.updated"));
+
+    assert!(validate_event_patterns(&["price_alert.*".to_string(), "*".to_string()], Locale::Es).is_ok());
+    assert!(validate_event_patterns(&["*.created".to_string()], Locale::Es).is_err());
+    assert!(validate_event_patterns(&["price alert".to_string()], Locale::Es).is_err());
+}
+
+#[test]
+fn test_event_envelope_from_notification() {
+    let mut notification = price_alert(1);
+    notification.event_id = Some(42);
+    notification.metadata = json!({ "alert_id": 7, "asset": "BTC", "current_price": 65000.5 });
+
+    let event = WebhookEvent::from_notification(&notification).unwrap();
+    let body: serde_json::Value =
+        serde_json::from_str(&event.render(events::LATEST_VERSION).unwrap().unwrap()).unwrap();
+
+    assert_eq!(body["type"], "price_alert.triggered");
+    assert_eq!(body["version"], events::LATEST_VERSION);
+    assert_eq!(body["data"]["notification_id"], 42);
+    assert_eq!(body["data"]["alert_id"], 7);
+    assert_eq!(body["data"]["current_price"], "65000.5");
+    assert!(event.render(0).is_none());
+
+    // Cada tipo de notificación tiene su entrada en el catálogo
+    let catalog = events::catalog();
+    for notification_type in super::NotificationType::ALL {
+        let event_type = events::event_type_for(&notification_type);
+        assert!(catalog.iter().any(|e| e.event_type == event_type), "{} sin schema", event_type);
+    }
+}
+
+#[test]
+fn test_live_and_replayed_frames_match() {
+    let mut notification = price_alert(7);
+    notification.id = Some(3);
+    notification.event_id = Some(42);
+    let stored = DbNotification {
+        id: 3,
+        user_id: 7,
+        event_id: 42,
+        notification_type: "price_alert".to_string(),
+        priority: notification.priority.as_str().to_string(),
+        title: notification.title.clone(),
+        message: notification.message.clone(),
+        metadata: notification.metadata.clone(),
+        created_at: notification.created_at,
+        read_at: None,
+    };
+
+    let live = json!(NotificationFrame::from(&notification));
+    assert_eq!(live, json!(NotificationFrame::from(stored)));
+    assert_eq!(live["notification_type"], "price_alert");
+    assert!(live.get("dedup_key").is_none());
+}
+
+#[test]
+fn test_telegram_markdown_escaping() {
+    assert_eq!(escape_markdown_v2("BTC > 70.000 (+5%)!"), "BTC \\> 70\\.000 \\(\\+5%\\)\\!");
+    assert_eq!(escape_markdown_v2("a_b*c"), "a\\_b\\*c");
+
+    let text = format_notification(&price_alert(1));
+    assert_eq!(text, "*BTC/USDT*\nPrecio objetivo alcanzado");
+}
+
+#[tokio::test]
+async fn test_telegram_client_against_local_api() {
+    use axum::{extract::Path, routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    let received = Arc::new(Mutex::new(Vec::<(String, serde_json::Value)>::new()));
+    let captured = received.clone();
+    let app = Router::new().route(
+        "/:bot/:method",
+        post(move |Path((bot, method)): Path<(String, String)>, Json(body): Json<serde_json::Value>| {
+            let captured = captured.clone();
+            async move {
+                assert_eq!(bot, "bottest-token");
+                captured.lock().unwrap().push((method, body));
+                Json(json!({ "ok": true, "result": { "message_id": 1 } }))
+            }
+        }),
+    );
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+    let client = TelegramClient::new(format!("http://{}", addr), "test-token");
+    client.send_message("12345", "*hola*").await.unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].0, "sendMessage");
+    assert_eq!(received[0].1["chat_id"], "12345");
+    assert_eq!(received[0].1["parse_mode"], "MarkdownV2");
+}
+
+#[test]
+fn test_telegram_command_parsing() {
+    assert_eq!(parse_command("/alerts@CryptoBot"), Ok(Command::Alerts));
+    assert_eq!(parse_command("/start abcd2345"), Ok(Command::Start(Some("ABCD2345".to_string()))));
+    assert_eq!(parse_command("abcd2345"), Ok(Command::Code("ABCD2345".to_string())));
+    assert_eq!(parse_command("/mute 2h"), Ok(Command::Mute(chrono::Duration::hours(2))));
+    assert_eq!(
+        parse_command("/price eth/usdc"),
+        Ok(Command::Price { base: "ETH".to_string(), quote: "USDC".to_string() })
+    );
+    assert_eq!(
+        parse_command("/price eth"),
+        Ok(Command::Price { base: "ETH".to_string(), quote: "USDT".to_string() })
+    );
+
+    match parse_command("/alert btc above 70000") {
+        Ok(Command::Alert { asset, condition, target_price }) => {
+            assert_eq!(asset, "BTC");
+            assert_eq!(condition, "above");
+            assert_eq!(target_price.to_string(), "70000");
+        }
+        other => panic!("comando inesperado: {:?}", other),
+    }
+    assert!(parse_command("/alert btc sideways 1").is_err());
+    assert_eq!(parse_command("/alert btc below 0"), Err(UsageError::InvalidPrice("0".to_string())));
+    assert_eq!(parse_command("/alert btc below -5"), Err(UsageError::InvalidPrice("-5".to_string())));
+    assert_eq!(parse_command("/mute forever"), Err(UsageError::Mute));
+    assert!(UsageError::Mute.message(Locale::En).starts_with("Usage: /mute"));
+    assert!(parse_command("/mute 99999999d").is_err());
+    assert!(parse_command("/mute 2é").is_err());
+    assert!(parse_command("hola").is_err());
+
+    assert_eq!(parse_callback("del:12"), Some(CallbackAction::DeleteAlert(12)));
+    assert_eq!(parse_callback("snooze:12:1d"), Some(CallbackAction::SnoozeAlert(12, chrono::Duration::days(1))));
+    assert_eq!(parse_callback("del:abc"), None);
+}
+
+#[test]
+fn test_email_templates_render_for_every_type() {
+    let env = template_environment();
+    for notification_type in NotificationType::ALL {
+        let notification = Notification::new(
+            1,
+            notification_type,
+            "BTC <USDT>".to_string(),
+            "Sube & baja".to_string(),
+            json!({ "target_price": "65000" }),
+        );
+        for locale in Locale::ALL {
+            let (subject, html, text) =
+                render_email(&env, &notification, Some("https://api.example.com/u"), locale).unwrap();
+
+            assert_eq!(subject, "BTC <USDT>");
+            assert!(html.contains("BTC &lt;USDT&gt;") && html.contains("Sube &amp; baja"));
+            assert!(text.contains("Sube & baja") && text.contains("- target price: 65000"));
+            assert!(text.contains("https://api.example.com/u"));
+            let label = locale.t(&format!("email.{}.label", notification.notification_type.as_str()));
+            assert!(html.contains(&label) && text.contains(&label));
+            assert!(html.contains(&format!("lang=\"{}\"", locale.as_str())));
+        }
+    }
+}
+
+#[test]
+fn test_unsubscribe_token_round_trip() {
+    let token = unsubscribe_token("secreto", 42).unwrap();
+    assert_eq!(verify_unsubscribe_token("secreto", &token), Some(42));
+    assert_eq!(verify_unsubscribe_token("otro", &token), None);
+    assert_eq!(verify_unsubscribe_token("secreto", &token.replacen("42", "43", 1)), None);
+}
+
+#[test]
+fn test_whatsapp_phone_numbers_and_session_window() {
+    assert_eq!(normalize_phone_number("+54 9 11 1234-5678"), Some("5491112345678".to_string()));
+    assert_eq!(normalize_phone_number("0034 (600) 123 456"), Some("34600123456".to_string()));
+    assert_eq!(normalize_phone_number("11 1234 5678"), None);
+    assert_eq!(normalize_phone_number("+54 11 abc"), None);
+
+    let now = chrono::Utc::now();
+    assert!(within_session_window(Some(now - chrono::Duration::hours(23)), now));
+    assert!(!within_session_window(Some(now - chrono::Duration::hours(25)), now));
+    assert!(!within_session_window(None, now));
+}
+
+#[test]
+fn test_whatsapp_webhook_signature() {
+    use hmac::{Hmac, Mac};
+
+    let body = br#"{"entry":[]}"#;
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"app-secret").unwrap();
+    mac.update(body);
+    let header = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+    assert!(verify_webhook_signature("app-secret", body, &header));
+    assert!(!verify_webhook_signature("otro", body, &header));
+    assert!(!verify_webhook_signature("app-secret", body, "sha256=zz"));
+}
+
+#[tokio::test]
+async fn test_whatsapp_client_against_local_api() {
+    use axum::{extract::Path, http::HeaderMap, routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    let received = Arc::new(Mutex::new(Vec::<serde_json::Value>::new()));
+    let captured = received.clone();
+    let app = Router::new().route(
+        "/:phone_number_id/messages",
+        post(move |Path(phone_number_id): Path<String>, headers: HeaderMap, Json(body): Json<serde_json::Value>| {
+            let captured = captured.clone();
+            async move {
+                assert_eq!(phone_number_id, "1000");
+                assert_eq!(headers["authorization"], "Bearer test-token");
+                captured.lock().unwrap().push(body);
+                Json(json!({ "messages": [{ "id": "wamid.TEST" }] }))
+            }
+        }),
+    );
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+    let client = WhatsAppClient::new(format!("http://{}", addr), "1000", "test-token");
+    let params = ["BTC/USDT".to_string(), "Precio\nobjetivo   alcanzado".to_string()];
+    let message_id = client.send_template("5491112345678", "price_alert", &params, None).await.unwrap();
+    assert_eq!(message_id, "wamid.TEST");
+
+    let received = received.lock().unwrap();
+    let template = &received[0]["template"];
+    assert_eq!(template["name"], "price_alert");
+    assert_eq!(template["language"]["code"], "es");
+    assert_eq!(template["components"][0]["parameters"][1]["text"], "Precio objetivo alcanzado");
+}
+
+#[test]
+fn test_chat_payloads_from_notification() {
+    let mut notification = price_alert(1);
+    notification.metadata = json!({ "asset": "BTC", "target_price": 65000, "condition": "above" });
+    let message = ChatMessage::from_event(&WebhookEvent::from_notification(&notification).unwrap());
+
+    assert_eq!(message.title, "BTC/USDT");
+    assert_eq!(message.color, NotificationType::PriceAlert.color());
+    assert!(message.fields.contains(&("Target price".to_string(), "65000".to_string())));
+
+    let discord = discord_payload(&message);
+    assert_eq!(discord["embeds"][0]["color"], 0xF59E0B);
+    assert_eq!(discord["embeds"][0]["description"], "Precio objetivo alcanzado");
+    assert_eq!(discord["embeds"][0]["footer"]["text"], "price_alert.triggered");
+
+    let slack = slack_payload(&message);
+    assert_eq!(slack["attachments"][0]["color"], "#f59e0b");
+    assert_eq!(slack["attachments"][0]["blocks"][0]["text"]["text"], "BTC/USDT");
+    assert_eq!(slack["text"], "BTC/USDT: Precio objetivo alcanzado");
+}
+
+#[test]
+fn test_chat_webhook_urls_and_rate_limits() {
+    use crate::models::notifications::WebhookKind;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    assert!(validate_webhook_url(WebhookKind::Discord, "https://discord.com/api/webhooks/1/abc", Locale::Es).is_ok());
+    assert!(validate_webhook_url(WebhookKind::Discord, "https://example.com/api/webhooks/1/abc", Locale::Es).is_err());
+    assert!(validate_webhook_url(WebhookKind::Slack, "https://hooks.slack.com/services/T0/B0/x", Locale::Es).is_ok());
+    assert!(validate_webhook_url(WebhookKind::Slack, "http://hooks.slack.com/services/T0/B0/x", Locale::Es).is_err());
+    assert!(validate_webhook_url(WebhookKind::Generic, "http://localhost:8080/hook", Locale::Es).is_ok());
+
+    let mut headers = HeaderMap::new();
+    assert_eq!(
+        retry_after(&headers, r#"{"retry_after": 1.5, "global": false}"#),
+        Some(Duration::from_millis(1500))
+    );
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+    assert_eq!(retry_after(&headers, ""), Some(Duration::from_secs(30)));
+    assert_eq!(retry_after(&HeaderMap::new(), "rate limited"), None);
+}
+
+#[test]
+fn test_web_push_encryption_matches_rfc8291_vector() {
+    // RFC 8291, apéndice A
+    let ua_public = parse_p256dh(
+        "BCVxsr7N_eNgVRqvHtD0zTZsEc6-VV-JvLexhqUzORcxaOzi6-AYWXvTBHm4bjyPjs7Vd8pZGH6SRpkNtoIAiw4",
+    )
+    .unwrap();
+    let auth_secret = parse_auth_secret("BTBZMqHH6r4Tts7J_aSIgg").unwrap();
+    let as_secret = p256::SecretKey::from_slice(
+        &URL_SAFE_NO_PAD.decode("yfWPiYE-n46HLnH0KqZOF1fJJU3MYrct3AELtAQ-oRw").unwrap(),
+    )
+    .unwrap();
+    let salt: [u8; 16] = URL_SAFE_NO_PAD.decode("DGv6ra1nlYgDCS1FRnbzlw").unwrap().try_into().unwrap();
+
+    let body = encrypt_with(b"When I grow up, I want to be a watermelon", &ua_public, &auth_secret, &as_secret, salt).unwrap();
+    assert_eq!(
+        URL_SAFE_NO_PAD.encode(body),
+        "DGv6ra1nlYgDCS1FRnbzlwAAEABBBP4z9KsN6nGRTbVYI_c7VJSPQTBtkgcy27mlmlMoZIIgDll6e3vCYLocInmYWAmS6TlzAC8wEqKK6PBru3jl7A_yl95bQpu6cVPTpK4Mqgkf1CXztLVBSt2Ks3oZwbuwXPXLWyouBWLVWGNWQexSgSxsj_Qulcy4a-fN"
+    );
+
+    assert!(parse_auth_secret("AAAA").is_err());
+    assert!(encrypt_with(&[0; MAX_PAYLOAD_LEN + 1], &ua_public, &auth_secret, &as_secret, salt).is_err());
+}
+
+#[test]
+fn test_vapid_authorization_header() {
+    let (private_key, public_key) = VapidKeys::generate();
+    let keys = VapidKeys::new(&private_key, "mailto:soporte@example.com").unwrap();
+    assert_eq!(keys.public_key(), public_key);
+
+    let header = keys.authorization("https://fcm.googleapis.com/fcm/send/abc").unwrap();
+    let (token, key) = header.strip_prefix("vapid t=").unwrap().split_once(", k=").unwrap();
+    assert_eq!(key, public_key);
+
+    let parts: Vec<&str> = token.split('.').collect();
+    assert_eq!(parts.len(), 3);
+    let claims: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+    assert_eq!(claims["aud"], "https://fcm.googleapis.com");
+    assert_eq!(claims["sub"], "mailto:soporte@example.com");
+    assert_eq!(URL_SAFE_NO_PAD.decode(parts[2]).unwrap().len(), 64);
+}
+
+#[test]
+fn test_preferences_rules_and_min_priority() {
+    let rule = |id, notification_type: Option<&str>, channel: Option<&str>, asset: Option<&str>, enabled| NotificationRule {
+        id,
+        user_id: 1,
+        notification_type: notification_type.map(str::to_string),
+        channel: channel.map(str::to_string),
+        asset: asset.map(str::to_string),
+        enabled,
+        created_at: chrono::Utc::now(),
+    };
+    let mut preferences = UserPreferences {
+        preferences: NotificationPreference::defaults(1),
+        rules: vec![
+            // Sin alertas de precio por email, salvo las de BTC; nada de DOGE
+            rule(1, Some("price_alert"), Some("email"), None, false),
+            rule(2, Some("price_alert"), None, Some("BTC"), true),
+            rule(3, None, None, Some("DOGE"), false),
+        ],
+    };
+
+    let mut alert = price_alert(1);
+    alert.metadata = json!({ "asset": "eth" });
+    assert!(!preferences.allows(ChannelKind::Email, &alert));
+    assert!(preferences.allows(ChannelKind::Telegram, &alert));
+    assert!(!preferences.allows(ChannelKind::WhatsApp, &alert), "WhatsApp está deshabilitado por defecto");
+
+    alert.metadata = json!({ "asset": "BTC" });
+    assert!(preferences.allows(ChannelKind::Email, &alert));
+    assert!(!preferences.allows(ChannelKind::WhatsApp, &alert), "las reglas no activan canales deshabilitados");
+
+    alert.metadata = json!({ "asset": "DOGE" });
+    assert!(ChannelKind::ALL.iter().all(|kind| !preferences.allows(*kind, &alert)));
+
+    let sentiment = Notification::new(1, NotificationType::MarketSentiment, "Sentimiento".to_string(), String::new(), json!({}));
+    assert!(preferences.allows(ChannelKind::Telegram, &sentiment));
+    preferences.preferences.min_priority.insert(ChannelKind::Telegram, NotificationPriority::Normal);
+    assert!(!preferences.allows(ChannelKind::Telegram, &sentiment));
+    assert!(preferences.allows(ChannelKind::WebSocket, &sentiment));
+}
+
+#[test]
+fn test_quiet_hours_and_digests() {
+    use chrono::{NaiveTime, TimeZone, Utc};
+    use chrono_tz::Tz;
+
+    let madrid: Tz = "Europe/Madrid".parse().unwrap();
+    let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    // 03:00 en Madrid (UTC+2 en verano)
+    let night = Utc.with_ymd_and_hms(2024, 7, 10, 1, 0, 0).unwrap();
+    let noon = Utc.with_ymd_and_hms(2024, 7, 10, 10, 0, 0).unwrap();
+
+    assert_eq!(
+        quiet_hours_end(night, madrid, time(22, 0), time(7, 0)),
+        Some(Utc.with_ymd_and_hms(2024, 7, 10, 5, 0, 0).unwrap())
+    );
+    assert_eq!(quiet_hours_end(noon, madrid, time(22, 0), time(7, 0)), None);
+    assert_eq!(
+        next_digest_at(noon, madrid, DigestMode::Daily),
+        Utc.with_ymd_and_hms(2024, 7, 11, 6, 0, 0).unwrap()
+    );
+    assert_eq!(
+        next_digest_at(noon, madrid, DigestMode::Hourly),
+        Utc.with_ymd_and_hms(2024, 7, 10, 11, 0, 0).unwrap()
+    );
+
+    let mut preferences = UserPreferences {
+        preferences: NotificationPreference::defaults(1),
+        rules: Vec::new(),
+    };
+    preferences.preferences.timezone = "Europe/Madrid".to_string();
+    preferences.preferences.quiet_hours_start = Some(time(22, 0));
+    preferences.preferences.quiet_hours_end = Some(time(7, 0));
+    preferences.preferences.digest.insert(ChannelKind::Email, DigestMode::Hourly);
+
+    let alert = price_alert(1);
+    assert_eq!(preferences.hold_until(ChannelKind::WebSocket, &alert, night), None);
+    assert_eq!(preferences.hold_until(ChannelKind::Telegram, &alert, noon), None);
+    assert!(preferences.hold_until(ChannelKind::Telegram, &alert, night).is_some());
+    assert_eq!(
+        preferences.hold_until(ChannelKind::Email, &alert, noon),
+        Some(Utc.with_ymd_and_hms(2024, 7, 10, 11, 0, 0).unwrap())
+    );
+
+    let mut second = price_alert(1);
+    second.title = "ETH/USDT".to_string();
+    let digest = build_digest(&[alert.clone(), second], Locale::Es).unwrap();
+    assert_eq!(digest.title, "Resumen: 2 notificaciones");
+    assert_eq!(digest.message.lines().count(), 2);
+    assert_eq!(digest.metadata["count"], 2);
+    assert_eq!(build_digest(std::slice::from_ref(&alert), Locale::En).unwrap().title, alert.title);
+}
+
+#[test]
+fn test_priority_dedup_key_and_rate_limit_spec() {
+    let alert = price_alert(1);
+    assert_eq!(alert.priority, NotificationPriority::High);
+    assert_eq!(alert.dedup_key(), price_alert(1).dedup_key());
+
+    let mut with_alert_id = price_alert(1);
+    with_alert_id.metadata = json!({ "alert_id": 7 });
+    assert_eq!(with_alert_id.dedup_key(), "price_alert:alert:7");
+    assert_eq!(alert.clone().with_dedup_key("btc").dedup_key(), "btc");
+
+    // Notificaciones encoladas antes de existir la prioridad
+    let mut legacy = serde_json::to_value(&alert).unwrap();
+    legacy.as_object_mut().unwrap().remove("priority");
+    let legacy: Notification = serde_json::from_value(legacy).unwrap();
+    assert_eq!(legacy.priority, NotificationPriority::Normal);
+
+    assert_eq!(
+        parse_rate_limits("email=10, web_push=0").unwrap(),
+        vec![(ChannelKind::Email, 10), (ChannelKind::WebPush, 0)]
+    );
+    assert!(parse_rate_limits("sms=5").is_err());
+    assert!(parse_rate_limits("email").is_err());
+}
+
+#[test]
+fn test_ticker_channels_and_subscription_cap() {
+    let channel = TickerChannel::parse("ticker:Binance:btc-usdt").unwrap();
+    assert_eq!(channel.exchange, "binance");
+    assert_eq!((channel.base_asset.as_str(), channel.quote_asset.as_str()), ("BTC", "USDT"));
+    assert_eq!(TickerChannel::parse("ticker:binance:BTC/USDT"), Some(channel.clone()));
+    assert_eq!(TickerChannel::parse("ticker::BTCUSDT"), None);
+    assert_eq!(TickerChannel::parse("trades:binance:BTCUSDT"), None);
+    assert_eq!(TickerChannel::parse("ticker:binance:-USDT"), None);
+
+    let mut tickers = std::collections::HashMap::new();
+    let connection_id = uuid::Uuid::new_v4();
+    let channels: Vec<_> = (0..MAX_TICKER_SUBSCRIPTIONS + 5)
+        .map(|i| TickerChannel::parse(&format!("ticker:binance:A{}USDT", i)).unwrap())
+        .collect();
+    let added = add_subscriptions(&mut tickers, connection_id, channels);
+    assert!(added.limit_reached);
+    assert_eq!(added.subscribed.len(), MAX_TICKER_SUBSCRIPTIONS);
+    assert_eq!(tickers.len(), MAX_TICKER_SUBSCRIPTIONS);
+
+    // Volver a suscribirse a un canal propio no cuenta contra el límite
+    let channel = TickerChannel::parse("ticker:binance:A0USDT").unwrap();
+    let again = add_subscriptions(&mut tickers, connection_id, vec![channel]);
+    assert!(!again.limit_reached);
+    assert_eq!(again.subscribed, vec!["ticker:binance:A0/USDT".to_string()]);
+}
+
+#[tokio::test]
+async fn test_connection_limit_per_user() {
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .connect_lazy("postgres://localhost/unused")
+        .unwrap();
+    let server = WebSocketServer::new(std::sync::Arc::new(TickerFeed::new(pool)));
+
+    let mut opened = Vec::new();
+    for _ in 0..max_connections_per_user() {
+        let (connection_id, _rx) = server.register_sse(1, "web".to_string()).await.unwrap();
+        opened.push(connection_id);
+    }
+    assert!(server.register_sse(1, "web".to_string()).await.is_err());
+    // El límite es por usuario
+    assert!(server.register_sse(2, "web".to_string()).await.is_ok());
+
+    server.remove_connection(1, opened[0]).await;
+    assert!(server.register_sse(1, "web".to_string()).await.is_ok());
+    assert_eq!(server.list_connections(Some(1)).await.len(), max_connections_per_user());
+}
+
+#[tokio::test]
+async fn test_in_memory_bus_fans_out_to_every_subscriber() {
+    use futures::StreamExt;
+
+    let bus = InMemoryBus::new();
+    let mut first = bus.subscribe().await.unwrap();
+    let mut second = bus.subscribe().await.unwrap();
+
+    publish_disconnect(&bus, 7, "desactivado").await.unwrap();
+    for stream in [&mut first, &mut second] {
+        match stream.next().await {
+            Some(BusMessage::Disconnect { user_id, reason }) => {
+                assert_eq!(user_id, 7);
+                assert_eq!(reason, "desactivado");
+            }
+            other => panic!("mensaje inesperado: {:?}", other),
+        }
+    }
+
+    // Quien se suscribe después no recibe lo ya publicado
+    let mut late = bus.subscribe().await.unwrap();
+    bus.publish(&BusMessage::Notification(price_alert(1))).await.unwrap();
+    assert!(matches!(late.next().await, Some(BusMessage::Notification(_))));
+    assert!(matches!(first.next().await, Some(BusMessage::Notification(_))));
+}
+
+#[test]
+fn test_webhook_backoff_is_exponential_capped_and_jittered() {
+    let policy = WebhookRetryPolicy::default();
+    let within = |delay: Duration, expected: Duration| {
+        delay >= expected.mul_f64(0.8) && delay < expected.mul_f64(1.2)
+    };
+
+    for _ in 0..100 {
+        assert!(within(policy.backoff(0), policy.base_delay));
+        assert!(within(policy.backoff(1), policy.base_delay));
+        assert!(within(policy.backoff(3), policy.base_delay * 4));
+        assert!(within(policy.backoff(40), policy.max_delay));
+    }
+
+    // El jitter hace que no todos los reintentos esperen lo mismo
+    let delays: std::collections::HashSet<_> = (0..20).map(|_| policy.backoff(2)).collect();
+    assert!(delays.len() > 1);
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::BigDecimal, PgPool};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use super::websocket::WebSocketServer;
+use crate::{
+    api::tickers::fetch_last_price,
+    db::instruments::find_instrument,
+    models::instruments::{canonical_symbol, split_symbol, Instrument},
+    utils::serde::{deserialize_bigdecimal, serialize_bigdecimal},
+};
+
+pub const TICKER_CHANNEL_PREFIX: &str = "ticker";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ticker {
+    pub channel: String,
+    pub exchange: String,
+    pub symbol: String,
+    #[serde(serialize_with = "serialize_bigdecimal", deserialize_with = "deserialize_bigdecimal")]
+    pub price: BigDecimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Canal de ticker ya normalizado, p. ej. `ticker:binance:BTC/USDT`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TickerChannel {
+    pub exchange: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+}
+
+impl TickerChannel {
+    /// Acepta `ticker:<exchange>:<símbolo>` con el símbolo en cualquiera de
+    /// los formatos de `split_symbol` (`BTCUSDT`, `BTC-USDT`, `BTC/USDT`).
+    pub fn parse(channel: &str) -> Option<Self> {
+        let mut parts = channel.trim().splitn(3, ':');
+        if parts.next()? != TICKER_CHANNEL_PREFIX {
+            return None;
+        }
+
+        let exchange = parts.next()?.to_lowercase();
+        let (base_asset, quote_asset) = split_symbol(parts.next()?)?;
+        if exchange.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            exchange,
+            base_asset,
+            quote_asset,
+        })
+    }
+
+    pub fn symbol(&self) -> String {
+        canonical_symbol(&self.base_asset, &self.quote_asset)
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}:{}:{}", TICKER_CHANNEL_PREFIX, self.exchange, self.symbol())
+    }
+}
+
+/// Cuánto se reutiliza un instrumento encontrado antes de volver a
+/// buscarlo en el catálogo.
+const INSTRUMENT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Obtiene precios de los canales de ticker. Resuelve cada canal a su
+/// instrumento del catálogo y lo cachea un tiempo; los canales sin
+/// instrumento no se cachean, así se detectan los que agregue una
+/// sincronización posterior.
+pub struct TickerFeed {
+    pool: PgPool,
+    client: Client,
+    instruments: Mutex<HashMap<String, (Instrument, Instant)>>,
+}
+
+impl TickerFeed {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            client: Client::new(),
+            instruments: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn instrument(&self, channel: &TickerChannel) -> Option<Instrument> {
+        let name = channel.name();
+        if let Some((instrument, cached_at)) = self.instruments.lock().await.get(&name) {
+            if cached_at.elapsed() < INSTRUMENT_CACHE_TTL {
+                return Some(instrument.clone());
+            }
+        }
+
+        match find_instrument(&self.pool, &channel.exchange, &channel.base_asset, &channel.quote_asset).await {
+            Ok(Some(instrument)) => {
+                self.instruments
+                    .lock()
+                    .await
+                    .insert(name, (instrument.clone(), Instant::now()));
+                Some(instrument)
+            }
+            Ok(None) => {
+                debug!("Canal de ticker sin instrumento en el catálogo: {}", name);
+                self.instruments.lock().await.remove(&name);
+                None
+            }
+            Err(e) => {
+                warn!("Error buscando el instrumento del canal {}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// Si el canal corresponde a un instrumento del catálogo.
+    pub async fn is_known(&self, channel: &TickerChannel) -> bool {
+        self.instrument(channel).await.is_some()
+    }
+
+    /// Precio actual del canal, o `None` si no hay instrumento o el
+    /// exchange no responde.
+    pub async fn fetch(&self, channel: &TickerChannel) -> Option<Ticker> {
+        let instrument = self.instrument(channel).await?;
+        match fetch_last_price(&self.client, &instrument).await {
+            Ok(price) => Some(Ticker {
+                channel: channel.name(),
+                exchange: channel.exchange.clone(),
+                symbol: channel.symbol(),
+                price,
+                timestamp: Utc::now(),
+            }),
+            Err(e) => {
+                debug!("Error obteniendo precio de {}: {}", channel.name(), e);
+                None
+            }
+        }
+    }
+
+    /// Olvida los instrumentos de los canales que ya no tienen suscriptores.
+    async fn retain(&self, active: &[String]) {
+        self.instruments
+            .lock()
+            .await
+            .retain(|channel, _| active.contains(channel));
+    }
+}
+
+/// Consulta periódicamente el precio de los canales con suscriptores y lo
+/// publica en el servidor WebSocket, que se encarga del throttling.
+pub async fn run_ticker_feed(ws_server: Arc<WebSocketServer>, poll_interval: Duration) {
+    let feed = ws_server.ticker_feed();
+    let mut interval = tokio::time::interval(poll_interval);
+
+    loop {
+        interval.tick().await;
+
+        let channels = ws_server.active_ticker_channels().await;
+        feed.retain(&channels).await;
+
+        for channel in channels {
+            let Some(parsed) = TickerChannel::parse(&channel) else {
+                continue;
+            };
+            if let Some(ticker) = feed.fetch(&parsed).await {
+                ws_server.publish_ticker(ticker).await;
+            }
+        }
+    }
+}
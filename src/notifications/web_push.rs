@@ -0,0 +1,366 @@
+use std::time::Duration;
+
+use aes_gcm::{aead::Aead, Aes128Gcm, KeyInit, Nonce};
+use axum::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hkdf::Hkdf;
+use p256::{
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey, SecretKey,
+};
+use rand::{rngs::OsRng, RngCore};
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use super::{
+    dispatcher::{ChannelError, ChannelKind, NotificationChannel},
+    Notification, NotificationPriority,
+};
+use crate::{db::push_subscriptions, models::notifications::PushSubscription};
+
+/// Tamaño de registro declarado en la cabecera `aes128gcm`. Todo el
+/// payload va en un único registro.
+const RECORD_SIZE: u32 = 4096;
+/// Cabecera (salt, rs, idlen, clave pública) más el tag de AES-GCM y el
+/// delimitador de relleno.
+const ENCRYPTION_OVERHEAD: usize = 16 + 4 + 1 + 65 + 16 + 1;
+/// Los servicios de push aceptan cuerpos de hasta 4096 bytes.
+pub const MAX_PAYLOAD_LEN: usize = RECORD_SIZE as usize - ENCRYPTION_OVERHEAD;
+/// Validez del JWT de VAPID; el máximo permitido son 24 horas.
+const VAPID_TOKEN_TTL_SECS: i64 = 12 * 3600;
+const DEFAULT_TTL_SECS: u64 = 24 * 3600;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebPushError {
+    #[error("Clave inválida: {0}")]
+    InvalidKey(String),
+    #[error("El payload supera los {MAX_PAYLOAD_LEN} bytes")]
+    PayloadTooLarge,
+    #[error("Error de cifrado")]
+    Encryption,
+    #[error("Error HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+    /// 404 o 410: la suscripción ya no existe y hay que borrarla.
+    #[error("Suscripción expirada")]
+    Gone,
+    #[error("El servicio de push respondió {status}: {body}")]
+    Rejected { status: u16, body: String },
+}
+
+impl WebPushError {
+    /// 429 y 5xx pueden resolverse reintentando más tarde.
+    fn is_transient(&self) -> bool {
+        match self {
+            WebPushError::Http(_) => true,
+            WebPushError::Rejected { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+fn decode_base64url(value: &str) -> Result<Vec<u8>, WebPushError> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim().trim_end_matches('='))
+        .map_err(|e| WebPushError::InvalidKey(e.to_string()))
+}
+
+/// Clave pública del navegador (`p256dh`, punto P-256 sin comprimir).
+pub fn parse_p256dh(p256dh: &str) -> Result<PublicKey, WebPushError> {
+    PublicKey::from_sec1_bytes(&decode_base64url(p256dh)?)
+        .map_err(|_| WebPushError::InvalidKey("p256dh no es un punto P-256".to_string()))
+}
+
+/// Secreto de autenticación del navegador (`auth`, 16 bytes).
+pub fn parse_auth_secret(auth: &str) -> Result<[u8; 16], WebPushError> {
+    decode_base64url(auth)?
+        .try_into()
+        .map_err(|_| WebPushError::InvalidKey("auth debe tener 16 bytes".to_string()))
+}
+
+/// Par de claves VAPID (RFC 8292) con el que el servidor se identifica ante
+/// los servicios de push. El navegador recibe la pública al suscribirse.
+#[derive(Clone)]
+pub struct VapidKeys {
+    signing_key: SigningKey,
+    public_key: String,
+    subject: String,
+}
+
+impl VapidKeys {
+    /// `private_key` es el escalar de 32 bytes en base64url, el formato que
+    /// generan las herramientas de Web Push. `subject` es un `mailto:` o una
+    /// URL de contacto.
+    pub fn new(private_key: &str, subject: impl Into<String>) -> Result<Self, WebPushError> {
+        let secret = SecretKey::from_slice(&decode_base64url(private_key)?)
+            .map_err(|_| WebPushError::InvalidKey("clave privada VAPID inválida".to_string()))?;
+        let public_key = URL_SAFE_NO_PAD.encode(secret.public_key().to_encoded_point(false).as_bytes());
+
+        Ok(Self {
+            signing_key: SigningKey::from(&secret),
+            public_key,
+            subject: subject.into(),
+        })
+    }
+
+    /// `VAPID_PRIVATE_KEY` y `VAPID_SUBJECT`. `None` si falta alguna o la
+    /// clave es inválida.
+    pub fn from_env() -> Option<Self> {
+        let private_key = std::env::var("VAPID_PRIVATE_KEY").ok().filter(|k| !k.is_empty())?;
+        let subject = std::env::var("VAPID_SUBJECT").ok().filter(|s| !s.is_empty())?;
+        match Self::new(&private_key, subject) {
+            Ok(keys) => Some(keys),
+            Err(e) => {
+                warn!("VAPID_PRIVATE_KEY inválida: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Genera un par nuevo: (privada, pública) en base64url.
+    pub fn generate() -> (String, String) {
+        let secret = SecretKey::random(&mut OsRng);
+        (
+            URL_SAFE_NO_PAD.encode(secret.to_bytes()),
+            URL_SAFE_NO_PAD.encode(secret.public_key().to_encoded_point(false).as_bytes()),
+        )
+    }
+
+    /// Clave pública que usa el navegador como `applicationServerKey`.
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Header `Authorization` para el servicio de push de `endpoint`: un
+    /// JWT ES256 con el origen del servicio como audiencia.
+    pub fn authorization(&self, endpoint: &str) -> Result<String, WebPushError> {
+        let url = reqwest::Url::parse(endpoint).map_err(|e| WebPushError::InvalidKey(e.to_string()))?;
+        let audience = url.origin().ascii_serialization();
+
+        let header = URL_SAFE_NO_PAD.encode(json!({ "typ": "JWT", "alg": "ES256" }).to_string());
+        let claims = URL_SAFE_NO_PAD.encode(
+            json!({
+                "aud": audience,
+                "exp": chrono::Utc::now().timestamp() + VAPID_TOKEN_TTL_SECS,
+                "sub": self.subject,
+            })
+            .to_string(),
+        );
+        let signing_input = format!("{}.{}", header, claims);
+        let signature: Signature = self.signing_key.sign(signing_input.as_bytes());
+
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+            self.public_key
+        ))
+    }
+}
+
+fn hkdf_expand<const N: usize>(salt: &[u8], ikm: &[u8], info: &[u8]) -> Result<[u8; N], WebPushError> {
+    let mut okm = [0u8; N];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut okm)
+        .map_err(|_| WebPushError::Encryption)?;
+    Ok(okm)
+}
+
+/// Cifra el payload para una suscripción (RFC 8291, codificación
+/// `aes128gcm` de RFC 8188) con una clave efímera y un salt aleatorios.
+pub fn encrypt(payload: &[u8], p256dh: &str, auth: &str) -> Result<Vec<u8>, WebPushError> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    encrypt_with(payload, &parse_p256dh(p256dh)?, &parse_auth_secret(auth)?, &SecretKey::random(&mut OsRng), salt)
+}
+
+/// `encrypt` con la clave efímera y el salt dados, para las pruebas.
+pub(crate) fn encrypt_with(
+    payload: &[u8],
+    ua_public: &PublicKey,
+    auth_secret: &[u8; 16],
+    as_secret: &SecretKey,
+    salt: [u8; 16],
+) -> Result<Vec<u8>, WebPushError> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(WebPushError::PayloadTooLarge);
+    }
+
+    let ua_public_bytes = ua_public.to_encoded_point(false);
+    let as_public_bytes = as_secret.public_key().to_encoded_point(false);
+    let shared = p256::ecdh::diffie_hellman(as_secret.to_nonzero_scalar(), ua_public.as_affine());
+
+    // IKM = HKDF(auth_secret, ecdh_secret, "WebPush: info" || 0x00 || ua_public || as_public)
+    let mut key_info = b"WebPush: info\0".to_vec();
+    key_info.extend_from_slice(ua_public_bytes.as_bytes());
+    key_info.extend_from_slice(as_public_bytes.as_bytes());
+    let ikm: [u8; 32] = hkdf_expand(auth_secret, shared.raw_secret_bytes(), &key_info)?;
+
+    let cek: [u8; 16] = hkdf_expand(&salt, &ikm, b"Content-Encoding: aes128gcm\0")?;
+    let nonce: [u8; 12] = hkdf_expand(&salt, &ikm, b"Content-Encoding: nonce\0")?;
+
+    // Un único registro, terminado con el delimitador 0x02 y sin relleno
+    let mut plaintext = payload.to_vec();
+    plaintext.push(0x02);
+    let ciphertext = Aes128Gcm::new_from_slice(&cek)
+        .map_err(|_| WebPushError::Encryption)?
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+        .map_err(|_| WebPushError::Encryption)?;
+
+    let mut body = Vec::with_capacity(ENCRYPTION_OVERHEAD + payload.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public_bytes.len() as u8);
+    body.extend_from_slice(as_public_bytes.as_bytes());
+    body.extend_from_slice(&ciphertext);
+    Ok(body)
+}
+
+/// Prioridad pedida al servicio de push; `high` puede despertar al
+/// dispositivo.
+fn urgency(priority: NotificationPriority) -> &'static str {
+    match priority {
+        NotificationPriority::Low => "low",
+        NotificationPriority::Normal => "normal",
+        NotificationPriority::High | NotificationPriority::Urgent => "high",
+    }
+}
+
+/// Lo que recibe el service worker en el evento `push`.
+pub fn notification_payload(notification: &Notification) -> serde_json::Value {
+    json!({
+        "title": notification.title,
+        "body": notification.message,
+        "type": notification.notification_type.as_str(),
+        "event_id": notification.event_id,
+        // Las notificaciones del mismo tipo se reemplazan en el sistema
+        "tag": notification.notification_type.as_str(),
+        "data": notification.metadata,
+    })
+}
+
+#[derive(Clone)]
+pub struct WebPushClient {
+    http_client: Client,
+    vapid: VapidKeys,
+    ttl_secs: u64,
+}
+
+impl WebPushClient {
+    pub fn new(vapid: VapidKeys) -> Self {
+        Self {
+            http_client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            vapid,
+            ttl_secs: std::env::var("WEB_PUSH_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_TTL_SECS),
+        }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        VapidKeys::from_env().map(Self::new)
+    }
+
+    pub async fn send(
+        &self,
+        subscription: &PushSubscription,
+        payload: &[u8],
+        urgency: &str,
+    ) -> Result<(), WebPushError> {
+        let body = encrypt(payload, &subscription.p256dh, &subscription.auth)?;
+
+        let response = self
+            .http_client
+            .post(&subscription.endpoint)
+            .header("Authorization", self.vapid.authorization(&subscription.endpoint)?)
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .header("TTL", self.ttl_secs.to_string())
+            .header("Urgency", urgency)
+            .body(body)
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND | StatusCode::GONE => Err(WebPushError::Gone),
+            status => Err(WebPushError::Rejected {
+                status: status.as_u16(),
+                body: response.text().await.unwrap_or_default().chars().take(200).collect(),
+            }),
+        }
+    }
+}
+
+/// Canal del dispatcher: envía la notificación a todas las suscripciones
+/// del usuario y borra las que el servicio de push da por expiradas.
+pub struct WebPushChannel {
+    pool: PgPool,
+    client: WebPushClient,
+}
+
+impl WebPushChannel {
+    pub fn new(pool: PgPool, client: WebPushClient) -> Self {
+        Self { pool, client }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WebPushChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::WebPush
+    }
+
+    /// Falla (y se reintenta) sólo si ninguna suscripción la recibió y
+    /// alguna tuvo un error transitorio.
+    async fn send(&self, notification: &Notification) -> Result<(), ChannelError> {
+        let subscriptions = push_subscriptions::list_subscriptions(&self.pool, notification.user_id)
+            .await
+            .map_err(|e| ChannelError::Failed(e.to_string()))?;
+        if subscriptions.is_empty() {
+            return Err(ChannelError::NotConfigured("sin suscripciones de push".to_string()));
+        }
+
+        let mut payload = notification_payload(notification).to_string();
+        if payload.len() > MAX_PAYLOAD_LEN {
+            // Sin los metadatos; el service worker puede pedirlos a la API
+            let mut trimmed = notification_payload(notification);
+            trimmed["data"] = serde_json::Value::Null;
+            payload = trimmed.to_string();
+        }
+        let urgency = urgency(notification.priority);
+
+        let mut delivered = 0;
+        let mut transient_error = None;
+        for subscription in &subscriptions {
+            match self.client.send(subscription, payload.as_bytes(), urgency).await {
+                Ok(()) => delivered += 1,
+                Err(WebPushError::Gone) => {
+                    info!("Suscripción de push {} expirada, se elimina", subscription.id);
+                    if let Err(e) = push_subscriptions::delete_by_id(&self.pool, subscription.id).await {
+                        warn!("Error eliminando la suscripción {}: {}", subscription.id, e);
+                    }
+                }
+                Err(e) => {
+                    warn!("Error enviando push a la suscripción {}: {}", subscription.id, e);
+                    if e.is_transient() {
+                        transient_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+
+        match (delivered, transient_error) {
+            (0, Some(error)) => Err(ChannelError::Failed(error)),
+            (0, None) => Err(ChannelError::NotConfigured("ninguna suscripción válida".to_string())),
+            _ => Ok(()),
+        }
+    }
+}
@@ -1,153 +1,617 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::Rng;
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 use sqlx::PgPool;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::{
+    chat::{self, ChatMessage},
+    events::{self, Ping, WebhookEvent},
+    dispatcher::{ChannelError, ChannelKind, NotificationChannel},
+    queue::NotificationQueue,
+    Notification, NotificationType,
+};
+use crate::{
+    db::{
+        notifications::list_webhooks,
+        users,
+        webhook_deliveries::{self, DeliveryAttempt},
+    },
+    i18n::Locale,
+    models::notifications::{self, WebhookDelivery, WebhookKind},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Error de base de datos: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Error de serialización: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Versión de payload no soportada: {0}")]
+    UnsupportedVersion(i32),
+}
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// Igual en todos los reintentos de una entrega, para que el receptor
+/// pueda descartar duplicados.
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+const SIGNATURE_VERSION: &str = "v1";
+
+/// Secreto aleatorio de 32 bytes generado en el servidor.
+pub fn generate_webhook_secret() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+/// Firma `v1=<hex>` de HMAC-SHA256 sobre `"{timestamp}.{body}"`. Al cubrir
+/// el timestamp, un payload capturado no sirve fuera de la ventana de
+/// tolerancia del receptor.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> Result<String, hmac::digest::InvalidLength> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    Ok(format!("{}={}", SIGNATURE_VERSION, hex::encode(mac.finalize().into_bytes())))
+}
+
+/// Verificación del lado del receptor: acepta si alguna de las firmas del
+/// header (puede haber dos durante una rotación) coincide y el timestamp
+/// está dentro de `tolerance_secs`.
+pub fn verify_signature(secret: &str, timestamp: i64, body: &str, header: &str, tolerance_secs: i64) -> bool {
+    if (Utc::now().timestamp() - timestamp).abs() > tolerance_secs {
+        return false;
+    }
+
+    let Ok(mut mac) = HmacSha256::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    header
+        .split(',')
+        .filter_map(|part| part.trim().strip_prefix("v1="))
+        .filter_map(|signature| hex::decode(signature).ok())
+        .any(|signature| mac.clone().verify_slice(&signature).is_ok())
+}
+
+/// Valor del header de firma: con el secreto actual y, si la rotación
+/// todavía está en su período de gracia, también con el anterior.
+fn signature_header(webhook: &notifications::WebhookConfig, timestamp: i64, body: &str) -> Result<String, hmac::digest::InvalidLength> {
+    let mut signatures = vec![sign_payload(&webhook.secret, timestamp, body)?];
+
+    let previous_valid = webhook
+        .previous_secret_expires_at
+        .map_or(false, |expires_at| expires_at > Utc::now());
+    if let (Some(previous), true) = (&webhook.previous_secret, previous_valid) {
+        signatures.push(sign_payload(previous, timestamp, body)?);
+    }
+
+    Ok(signatures.join(","))
+}
+
+/// Si `event_type` coincide con alguno de los patrones suscritos. Una lista
+/// vacía acepta todos; `*` acepta cualquier evento y `price_alert.*` acepta
+/// `price_alert` y todos sus subtipos. Los nombres de tipo de notificación
+/// (`price_alert`, `PriceAlert`, ...) siguen aceptando el evento que generan.
+pub fn matches_event(patterns: &serde_json::Value, event_type: &str) -> bool {
+    let Some(patterns) = patterns.as_array() else {
+        return true;
+    };
+    if patterns.is_empty() {
+        return true;
+    }
+
+    let normalize = |s: &str| s.to_ascii_lowercase().replace('_', "");
+    let event = normalize(event_type);
+
+    patterns.iter().filter_map(|p| p.as_str()).any(|pattern| {
+        let pattern = normalize(pattern.trim());
+        if pattern == "*" {
+            return true;
+        }
+        let legacy = NotificationType::ALL
+            .iter()
+            .find(|t| normalize(t.as_str()) == pattern)
+            .map(|t| normalize(events::event_type_for(t)));
+        if legacy.as_deref() == Some(event.as_str()) {
+            return true;
+        }
+        match pattern.strip_suffix(".*") {
+            Some(prefix) => event == prefix || event.starts_with(&format!("{}.", prefix)),
+            None => event == pattern,
+        }
+    })
+}
+
+/// Valida los patrones de suscripción: segmentos alfanuméricos separados
+/// por puntos, con `*` sólo como patrón completo o como último segmento.
+pub fn validate_event_patterns(patterns: &[String], locale: Locale) -> Result<(), String> {
+    for pattern in patterns {
+        let segments: Vec<&str> = pattern.split('.').collect();
+        let valid = pattern == "*"
+            || segments.iter().enumerate().all(|(i, segment)| {
+                (*segment == "*" && i == segments.len() - 1 && i > 0)
+                    || (!segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+            });
+        if !valid {
+            return Err(locale.t_with("webhooks.invalid_event_pattern", &[("pattern", pattern)]));
+        }
+    }
+    Ok(())
+}
+
+/// Los webhooks de Discord y Slack tienen que apuntar a los incoming
+/// webhooks de esas plataformas.
+pub fn validate_webhook_url(kind: WebhookKind, url: &str, locale: Locale) -> Result<(), String> {
+    let invalid = || locale.t_with("webhooks.invalid_url", &[("url", &url)]);
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid())?;
+
+    let valid = match kind {
+        WebhookKind::Generic => matches!(parsed.scheme(), "http" | "https"),
+        WebhookKind::Discord => {
+            parsed.scheme() == "https"
+                && matches!(
+                    parsed.host_str(),
+                    Some("discord.com" | "discordapp.com" | "ptb.discord.com" | "canary.discord.com")
+                )
+                && parsed.path().starts_with("/api/webhooks/")
+        }
+        WebhookKind::Slack => {
+            parsed.scheme() == "https"
+                && parsed.host_str() == Some("hooks.slack.com")
+                && parsed.path().starts_with("/services/")
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Cuerpo del evento para el webhook: el sobre con la versión fijada o el
+/// mensaje nativo de Discord o Slack.
+fn render_for(event: &WebhookEvent, webhook: &notifications::WebhookConfig) -> Result<String, WebhookError> {
+    match webhook.kind {
+        WebhookKind::Generic => event
+            .render(webhook.api_version)
+            .ok_or(WebhookError::UnsupportedVersion(webhook.api_version))?
+            .map_err(WebhookError::from),
+        WebhookKind::Discord => Ok(serde_json::to_string(&chat::discord_payload(&ChatMessage::from_event(event)))?),
+        WebhookKind::Slack => Ok(serde_json::to_string(&chat::slack_payload(&ChatMessage::from_event(event)))?),
+    }
+}
+
+const RESPONSE_SNIPPET_LEN: usize = 500;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_BATCH_SIZE: i64 = 50;
+/// Espera ante un 429 sin `Retry-After`.
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct WebhookRetryPolicy {
+    /// Intentos por notificación, contando el primero.
+    pub max_attempts: i32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Fallos consecutivos (de cualquier notificación) tras los que se
+    /// deshabilita el webhook.
+    pub disable_after: i32,
+}
+
+impl Default for WebhookRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 6,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(6 * 3600),
+            disable_after: 10,
+        }
+    }
+}
+
+impl WebhookRetryPolicy {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let env_i32 = |name: &str, default: i32| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &i32| *v > 0)
+                .unwrap_or(default)
+        };
+
+        Self {
+            max_attempts: env_i32("WEBHOOK_MAX_ATTEMPTS", default.max_attempts),
+            disable_after: env_i32("WEBHOOK_DISABLE_AFTER_FAILURES", default.disable_after),
+            ..default
+        }
+    }
+
+    /// Espera antes del intento `attempt + 1`: exponencial con un jitter de
+    /// ±20% para no sincronizar los reintentos de muchos webhooks.
+    pub fn backoff(&self, attempt: i32) -> Duration {
+        let exponent = (attempt.max(1) - 1).min(20) as u32;
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0.8..1.2);
+        delay.mul_f64(jitter)
+    }
+}
 
-use super::Notification;
+/// Resultado de un POST a un webhook.
+struct PostOutcome {
+    status_code: Option<i32>,
+    latency_ms: i32,
+    response_snippet: Option<String>,
+    error: Option<String>,
+    /// El receptor respondió 429 y pidió esperar este tiempo.
+    retry_after: Option<Duration>,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct WebhookConfig {
-    pub id: i32,
-    pub user_id: i32,
-    pub url: String,
-    pub secret: String,
-    pub enabled: bool,
-    pub notification_types: Vec<String>,
+impl PostOutcome {
+    fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
 }
 
-pub struct WebhookManager {
+/// Canal del dispatcher: envía la notificación a los webhooks habilitados
+/// del usuario que aceptan su tipo. Cada intento queda en
+/// `webhook_deliveries` y los fallidos se reintentan con backoff desde
+/// `run_retry_worker`.
+pub struct WebhookChannel {
     http_client: Client,
     pool: PgPool,
+    policy: WebhookRetryPolicy,
+    /// Cola donde se avisa al dueño cuando su webhook se deshabilita.
+    alerts: Arc<dyn NotificationQueue>,
 }
 
-impl WebhookManager {
-    pub fn new(pool: PgPool) -> Self {
+impl WebhookChannel {
+    pub fn new(pool: PgPool, alerts: Arc<dyn NotificationQueue>) -> Self {
         Self {
-            http_client: Client::new(),
+            http_client: Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
             pool,
+            policy: WebhookRetryPolicy::from_env(),
+            alerts,
         }
     }
 
-    pub async fn send_webhook(
-        &self,
-        notification: &Notification,
-        config: &WebhookConfig,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let payload = serde_json::json!({
-            "notification": notification,
-            "timestamp": chrono::Utc::now(),
-        });
-
-        // Generar firma HMAC para seguridad
-        use hmac::{Hmac, Mac};
-        use sha2::Sha256;
-        type HmacSha256 = Hmac<Sha256>;
-
-        let mut mac = HmacSha256::new_from_slice(config.secret.as_bytes())?;
-        mac.update(serde_json::to_string(&payload)?.as_bytes());
-        let signature = hex::encode(mac.finalize().into_bytes());
-
-        let response = self
+    pub fn with_policy(mut self, policy: WebhookRetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    async fn post(&self, webhook: &notifications::WebhookConfig, delivery_id: Uuid, payload: &str) -> PostOutcome {
+        let started = Instant::now();
+        let elapsed = |started: Instant| started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+        // Cada intento lleva su propio timestamp; el id de entrega se repite
+        let timestamp = Utc::now().timestamp();
+        let signature = match signature_header(webhook, timestamp, payload) {
+            Ok(signature) => signature,
+            Err(e) => {
+                return PostOutcome {
+                    status_code: None,
+                    latency_ms: 0,
+                    response_snippet: None,
+                    error: Some(format!("Secreto inválido: {}", e)),
+                    retry_after: None,
+                }
+            }
+        };
+
+        let mut request = self
             .http_client
-            .post(&config.url)
-            .header("X-Webhook-Signature", signature)
-            .json(&payload)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            error!(
-                "Error enviando webhook a {}: {}",
-                config.url,
-                response.status()
-            );
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Error HTTP: {}", response.status()),
-            )));
+            .post(&webhook.url)
+            .header("Content-Type", "application/json");
+        // Discord y Slack no verifican firmas
+        if webhook.kind == WebhookKind::Generic {
+            request = request
+                .header(SIGNATURE_HEADER, signature)
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(DELIVERY_HEADER, delivery_id.to_string());
         }
 
-        info!("Webhook enviado exitosamente a {}", config.url);
-        Ok(())
+        match request.body(payload.to_string()).send().await {
+            Ok(response) => {
+                let status = response.status();
+                let headers = response.headers().clone();
+                let body = response.text().await.unwrap_or_default();
+                let retry_after = (status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+                    .then(|| chat::retry_after(&headers, &body).unwrap_or(DEFAULT_RATE_LIMIT_DELAY));
+                PostOutcome {
+                    status_code: Some(status.as_u16() as i32),
+                    latency_ms: elapsed(started),
+                    response_snippet: Some(body.chars().take(RESPONSE_SNIPPET_LEN).collect()),
+                    error: (!status.is_success()).then(|| format!("HTTP {}", status)),
+                    retry_after,
+                }
+            }
+            Err(e) => PostOutcome {
+                status_code: None,
+                latency_ms: elapsed(started),
+                response_snippet: None,
+                error: Some(e.to_string()),
+                retry_after: None,
+            },
+        }
     }
 
-    pub async fn get_webhook_configs(
+    /// Hace un intento y lo registra, programando el siguiente si falla.
+    async fn deliver(
         &self,
-        user_id: i32,
-    ) -> Result<Vec<WebhookConfig>, sqlx::Error> {
-        sqlx::query_as!(
-            WebhookConfig,
-            r#"
-            SELECT id, user_id, url, secret, enabled, notification_types
-            FROM webhook_configs
-            WHERE user_id = $1 AND enabled = true
-            "#,
-            user_id
+        webhook: &notifications::WebhookConfig,
+        delivery_id: Uuid,
+        attempt: i32,
+        payload: String,
+    ) -> Result<WebhookDelivery, sqlx::Error> {
+        let outcome = self.post(webhook, delivery_id, &payload).await;
+
+        let (status, next_attempt_at) = if outcome.succeeded() {
+            webhook_deliveries::reset_failures(&self.pool, webhook.id).await?;
+            info!("Webhook enviado exitosamente a {}", webhook.url);
+            ("succeeded", None)
+        } else if let Some(retry_after) = outcome.retry_after.filter(|_| attempt < self.policy.max_attempts) {
+            // Un 429 no es un fallo del receptor: se espera lo que pide sin
+            // sumar al contador que deshabilita el webhook
+            warn!("Webhook {} limitado por el receptor, reintento en {:?}", webhook.url, retry_after);
+            let delay = chrono::Duration::from_std(retry_after).unwrap_or_default();
+            ("retry_scheduled", Some(Utc::now() + delay))
+        } else {
+            warn!(
+                "Error enviando webhook a {} (intento {}): {}",
+                webhook.url,
+                attempt,
+                outcome.error.as_deref().unwrap_or_default()
+            );
+
+            let disabled =
+                webhook_deliveries::record_failure(&self.pool, webhook.id, self.policy.disable_after).await?;
+            if disabled {
+                self.alert_disabled(webhook).await;
+            }
+
+            if attempt < self.policy.max_attempts && !disabled {
+                let delay = chrono::Duration::from_std(self.policy.backoff(attempt)).unwrap_or_default();
+                ("retry_scheduled", Some(Utc::now() + delay))
+            } else {
+                ("failed", None)
+            }
+        };
+
+        webhook_deliveries::insert_attempt(
+            &self.pool,
+            &DeliveryAttempt {
+                delivery_id,
+                webhook_id: webhook.id,
+                user_id: webhook.user_id,
+                attempt,
+                status,
+                status_code: outcome.status_code,
+                latency_ms: Some(outcome.latency_ms),
+                response_snippet: outcome.response_snippet,
+                error: outcome.error,
+                payload,
+                next_attempt_at,
+            },
         )
-        .fetch_all(&self.pool)
         .await
     }
 
-    pub async fn create_webhook_config(
-        &self,
-        config: WebhookConfig,
-    ) -> Result<WebhookConfig, sqlx::Error> {
-        sqlx::query_as!(
-            WebhookConfig,
-            r#"
-            INSERT INTO webhook_configs (user_id, url, secret, enabled, notification_types)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, user_id, url, secret, enabled, notification_types
-            "#,
-            config.user_id,
-            config.url,
-            config.secret,
-            config.enabled,
-            &config.notification_types as &[String],
-        )
-        .fetch_one(&self.pool)
-        .await
+    async fn alert_disabled(&self, webhook: &notifications::WebhookConfig) {
+        error!(
+            "Webhook {} deshabilitado tras {} fallos consecutivos",
+            webhook.id, self.policy.disable_after
+        );
+
+        let locale = users::get_user_locale(&self.pool, webhook.user_id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Error leyendo el idioma del usuario {}: {}", webhook.user_id, e);
+                None
+            })
+            .unwrap_or_default();
+        let alert = Notification::new(
+            webhook.user_id,
+            NotificationType::SystemAlert,
+            locale.t("webhooks.disabled_title"),
+            locale.t_with(
+                "webhooks.disabled_message",
+                &[("url", &webhook.url), ("count", &self.policy.disable_after)],
+            ),
+            serde_json::json!({ "webhook_id": webhook.id, "url": webhook.url }),
+        );
+        if let Err(e) = self.alerts.push(&alert).await {
+            error!("Error encolando aviso de webhook deshabilitado: {}", e);
+        }
     }
 
-    pub async fn update_webhook_config(
-        &self,
-        config: WebhookConfig,
-    ) -> Result<WebhookConfig, sqlx::Error> {
-        sqlx::query_as!(
-            WebhookConfig,
-            r#"
-            UPDATE webhook_configs
-            SET url = $1, secret = $2, enabled = $3, notification_types = $4
-            WHERE id = $5 AND user_id = $6
-            RETURNING id, user_id, url, secret, enabled, notification_types
-            "#,
-            config.url,
-            config.secret,
-            config.enabled,
-            &config.notification_types as &[String],
-            config.id,
-            config.user_id,
-        )
-        .fetch_one(&self.pool)
-        .await
+    /// Reintenta las entregas cuyo backoff ya venció. Devuelve cuántas procesó.
+    /// Un error en una no corta la tanda: esa vuelve a quedar pendiente y se
+    /// sigue con las demás.
+    pub async fn process_due_retries(&self) -> Result<usize, sqlx::Error> {
+        let due = webhook_deliveries::claim_due_retries(&self.pool, RETRY_BATCH_SIZE).await?;
+        let count = due.len();
+
+        for previous in due {
+            let (id, delivery_id) = (previous.id, previous.delivery_id);
+            if let Err(e) = self.retry(previous).await {
+                error!("Error reintentando la entrega {}: {}", delivery_id, e);
+                if let Err(e) = webhook_deliveries::release_retry(&self.pool, id).await {
+                    error!("Error devolviendo el reintento {} a la espera: {}", delivery_id, e);
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    async fn retry(&self, previous: WebhookDelivery) -> Result<(), sqlx::Error> {
+        let webhook = match webhook_deliveries::get_webhook(&self.pool, previous.webhook_id).await? {
+            Some(webhook) if webhook.enabled => webhook,
+            _ => {
+                info!("Reintento {} descartado: webhook eliminado o deshabilitado", previous.delivery_id);
+                return Ok(());
+            }
+        };
+
+        self.deliver(&webhook, previous.delivery_id, previous.attempt + 1, previous.payload)
+            .await
+            .map(|_| ())
     }
 
-    pub async fn delete_webhook_config(
+    /// Reenvía manualmente el payload de una entrega como un nuevo intento.
+    pub async fn redeliver(
         &self,
-        id: i32,
         user_id: i32,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query!(
-            r#"
-            DELETE FROM webhook_configs
-            WHERE id = $1 AND user_id = $2
-            "#,
-            id,
-            user_id
+        webhook_id: Uuid,
+        delivery_id: Uuid,
+    ) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+        let Some(previous) = webhook_deliveries::latest_attempt(&self.pool, user_id, webhook_id, delivery_id).await?
+        else {
+            return Ok(None);
+        };
+        let Some(webhook) = webhook_deliveries::get_webhook(&self.pool, webhook_id).await? else {
+            return Ok(None);
+        };
+
+        self.deliver(&webhook, delivery_id, previous.attempt + 1, previous.payload)
+            .await
+            .map(Some)
+    }
+
+    /// Envía un evento a los webhooks habilitados del usuario suscritos a
+    /// su tipo, cada uno con la versión de payload que tiene fijada.
+    /// Devuelve el primer intento de cada uno. Si falla alguno se sigue con
+    /// el resto y se devuelve el error al final; al volver a enviar el mismo
+    /// evento se saltean los webhooks que ya tuvieron su intento.
+    pub async fn send_event(&self, user_id: i32, event: &WebhookEvent) -> Result<Vec<WebhookDelivery>, WebhookError> {
+        let webhooks = list_webhooks(&self.pool, user_id).await?;
+
+        let mut deliveries = Vec::new();
+        let mut failure = None;
+        for webhook in webhooks
+            .iter()
+            .filter(|w| w.enabled && matches_event(&w.notification_types, event.event_type))
+        {
+            match self.send_to(webhook, event).await {
+                Ok(delivery) => deliveries.push(delivery),
+                Err(e) => {
+                    error!("Error enviando el evento {} al webhook {}: {}", event.id, webhook.id, e);
+                    failure = Some(e);
+                }
+            }
+        }
+
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(deliveries),
+        }
+    }
+
+    async fn send_to(
+        &self,
+        webhook: &notifications::WebhookConfig,
+        event: &WebhookEvent,
+    ) -> Result<WebhookDelivery, WebhookError> {
+        // El mismo evento lleva siempre el mismo id de entrega por webhook
+        let delivery_id = Uuid::new_v5(&event.id, webhook.id.as_bytes());
+        if let Some(delivery) = webhook_deliveries::first_attempt(&self.pool, delivery_id).await? {
+            return Ok(delivery);
+        }
+
+        let payload = render_for(event, webhook)?;
+        Ok(self.deliver(webhook, delivery_id, 1, payload).await?)
+    }
+
+    /// Envía un evento `ping` a un webhook, aunque esté deshabilitado. El
+    /// intento queda registrado pero no se reintenta ni cuenta como fallo.
+    pub async fn ping(&self, user_id: i32, webhook_id: Uuid) -> Result<Option<WebhookDelivery>, WebhookError> {
+        let Some(webhook) = webhook_deliveries::get_webhook(&self.pool, webhook_id).await? else {
+            return Ok(None);
+        };
+        if webhook.user_id != user_id {
+            return Ok(None);
+        }
+
+        let payload = render_for(&WebhookEvent::new(&Ping { webhook_id: webhook.id })?, &webhook)?;
+        let delivery_id = Uuid::new_v4();
+        let outcome = self.post(&webhook, delivery_id, &payload).await;
+        let status = if outcome.succeeded() { "succeeded" } else { "failed" };
+
+        let delivery = webhook_deliveries::insert_attempt(
+            &self.pool,
+            &DeliveryAttempt {
+                delivery_id,
+                webhook_id: webhook.id,
+                user_id: webhook.user_id,
+                attempt: 1,
+                status,
+                status_code: outcome.status_code,
+                latency_ms: Some(outcome.latency_ms),
+                response_snippet: outcome.response_snippet,
+                error: outcome.error,
+                payload,
+                next_attempt_at: None,
+            },
         )
-        .execute(&self.pool)
         .await?;
 
+        Ok(Some(delivery))
+    }
+
+    pub async fn run_retry_worker(self: Arc<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match self.process_due_retries().await {
+                Ok(0) => {}
+                Ok(count) => info!("{} reintentos de webhooks procesados", count),
+                Err(e) => error!("Error procesando reintentos de webhooks: {}", e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Webhook
+    }
+
+    /// Se considera entregada cuando cada webhook tuvo su primer intento:
+    /// los fallos se reintentan aquí y no a través de la cola.
+    async fn send(&self, notification: &Notification) -> Result<(), ChannelError> {
+        let event = WebhookEvent::from_notification(notification).map_err(|e| ChannelError::Failed(e.to_string()))?;
+        let deliveries = self
+            .send_event(notification.user_id, &event)
+            .await
+            .map_err(|e| ChannelError::Failed(e.to_string()))?;
+
+        if deliveries.is_empty() {
+            return Err(ChannelError::NotConfigured("sin webhooks para el tipo".to_string()));
+        }
         Ok(())
     }
 }
@@ -1,19 +1,115 @@
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::{mpsc, RwLock};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Notify, RwLock,
+    },
+    task::JoinHandle,
+};
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
-        WebSocketUpgrade,
+        ws::{CloseFrame, Message, WebSocket},
+        Query, State, WebSocketUpgrade,
     },
-    response::Response,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
-use futures::{sink::SinkExt, stream::StreamExt};
+use chrono::{DateTime, TimeZone, Utc};
+use futures::{future::join_all, sink::SinkExt, stream::StreamExt};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use serde_json::json;
+use sqlx::PgPool;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use super::{
+    bus::{BusMessage, NotificationBus},
+    models::NotificationFrame,
+    ticker::{Ticker, TickerChannel, TickerFeed},
+    Notification,
+};
+use crate::{
+    app_state::AppState,
+    auth::jwt::{self, Claims},
+    db::{notifications, users},
+};
+
+/// Máximo de canales de ticker a los que puede suscribirse una conexión.
+pub(super) const MAX_TICKER_SUBSCRIPTIONS: usize = 50;
+const DEFAULT_MAX_CONNECTIONS_PER_USER: usize = 5;
+const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 30;
+const DEFAULT_IDLE_TIMEOUT_SECS: i64 = 90;
+/// Máximo de notificaciones reenviadas por cada mensaje `resume`.
+const MAX_RESUME_REPLAY: i64 = 500;
+/// Espera antes de volver a suscribirse al bus tras perder la conexión.
+const BUS_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Tiempo para enviar el mensaje `auth` cuando el token no va en la query.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Códigos de cierre propios (rango 4000-4999 reservado para aplicaciones)
+const CLOSE_UNAUTHORIZED: u16 = 4401;
+pub(crate) const CLOSE_FORBIDDEN: u16 = 4403;
+const CLOSE_IDLE_TIMEOUT: u16 = 4408;
+const CLOSE_TOO_MANY_CONNECTIONS: u16 = 4429;
+pub(crate) const CLOSE_INTERNAL_ERROR: u16 = 4500;
+
+type Users = Arc<RwLock<HashMap<i32, Vec<Connection>>>>;
+type Tickers = Arc<RwLock<HashMap<String, TickerState>>>;
+
+/// Una conexión abierta (WebSocket o SSE). Un mismo usuario puede tener
+/// varias (por ejemplo el cliente web y el mobile a la vez).
+#[derive(Debug, Clone)]
+struct Connection {
+    id: Uuid,
+    client_type: String,
+    connected_at: DateTime<Utc>,
+    sender: ConnectionSender,
+}
+
+/// Los WebSocket reciben frames ya serializados; los streams SSE reciben la
+/// notificación y la convierten en evento ellos mismos. El `Notify` de los
+/// WebSocket cierra el socket cuando su cola está llena; a los SSE les
+/// basta con soltar el sender.
+#[derive(Debug, Clone)]
+enum ConnectionSender {
+    WebSocket(mpsc::Sender<Message>, Arc<Notify>),
+    Sse(mpsc::Sender<Notification>),
+}
 
-use super::Notification;
+impl ConnectionSender {
+    fn transport(&self) -> &'static str {
+        match self {
+            ConnectionSender::WebSocket(..) => "websocket",
+            ConnectionSender::Sse(_) => "sse",
+        }
+    }
+}
 
-type Users = Arc<RwLock<HashMap<i32, mpsc::Sender<Message>>>>;
+/// Datos de una conexión visibles para los administradores.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub connection_id: Uuid,
+    pub user_id: i32,
+    pub transport: &'static str,
+    pub client_type: String,
+    pub connected_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct TickerState {
+    subscribers: HashSet<Uuid>,
+    latest: Option<Ticker>,
+    /// Hay un precio nuevo que todavía no se envió a los suscriptores.
+    dirty: bool,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct WebSocketMessage {
@@ -21,96 +117,875 @@ struct WebSocketMessage {
     payload: serde_json::Value,
 }
 
+impl WebSocketMessage {
+    fn new(message_type: &str, payload: serde_json::Value) -> Self {
+        Self {
+            message_type: message_type.to_string(),
+            payload,
+        }
+    }
+
+    fn to_message(&self) -> Option<Message> {
+        serde_json::to_string(self).ok().map(Message::Text)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionPayload {
+    #[serde(default)]
+    channels: Vec<String>,
+    channel: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResumePayload {
+    last_event_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AckPayload {
+    #[serde(default)]
+    event_ids: Vec<i64>,
+    event_id: Option<i64>,
+}
+
+impl AckPayload {
+    fn into_event_ids(self) -> Vec<i64> {
+        let mut event_ids = self.event_ids;
+        event_ids.extend(self.event_id);
+        event_ids
+    }
+}
+
+/// Datos de la conexión que necesitan los handlers de mensajes del cliente.
+struct ClientContext {
+    pool: PgPool,
+    ticker_feed: Arc<TickerFeed>,
+    user_id: i32,
+    connection_id: Uuid,
+    tx: mpsc::Sender<Message>,
+}
+
+impl SubscriptionPayload {
+    fn into_channels(self) -> Vec<String> {
+        let mut channels = self.channels;
+        channels.extend(self.channel);
+        channels
+    }
+}
+
+/// El usuario ya tiene abiertas las conexiones permitidas.
+#[derive(Debug)]
+pub(crate) struct ConnectionLimitReached;
+
 pub struct WebSocketServer {
     users: Users,
+    tickers: Tickers,
+    ticker_feed: Arc<TickerFeed>,
 }
 
 impl WebSocketServer {
-    pub fn new() -> Self {
+    pub fn new(ticker_feed: Arc<TickerFeed>) -> Self {
         Self {
             users: Arc::new(RwLock::new(HashMap::new())),
+            tickers: Arc::new(RwLock::new(HashMap::new())),
+            ticker_feed,
+        }
+    }
+
+    pub fn ticker_feed(&self) -> Arc<TickerFeed> {
+        self.ticker_feed.clone()
+    }
+
+    /// Cantidad de conexiones abiertas del usuario.
+    pub async fn connection_count(&self, user_id: i32) -> usize {
+        self.users
+            .read()
+            .await
+            .get(&user_id)
+            .map(Vec::len)
+            .unwrap_or(0)
+    }
+
+    /// Registra la conexión si el usuario no llegó al máximo. La
+    /// comprobación y el alta van bajo el mismo lock para que dos conexiones
+    /// simultáneas no superen el límite.
+    async fn try_register(&self, user_id: i32, connection: Connection) -> Result<(), ConnectionLimitReached> {
+        let mut users = self.users.write().await;
+        let connections = users.entry(user_id).or_default();
+        if connections.len() >= max_connections_per_user() {
+            if connections.is_empty() {
+                users.remove(&user_id);
+            }
+            return Err(ConnectionLimitReached);
+        }
+
+        connections.push(connection);
+        Ok(())
+    }
+
+    /// Conexiones abiertas, de un usuario o de todos.
+    pub async fn list_connections(&self, user_id: Option<i32>) -> Vec<ConnectionInfo> {
+        let users = self.users.read().await;
+        users
+            .iter()
+            .filter(|(id, _)| user_id.map_or(true, |user_id| **id == user_id))
+            .flat_map(|(id, connections)| {
+                connections.iter().map(move |connection| ConnectionInfo {
+                    connection_id: connection.id,
+                    user_id: *id,
+                    transport: connection.sender.transport(),
+                    client_type: connection.client_type.clone(),
+                    connected_at: connection.connected_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Registra un stream SSE del usuario. Recibe las mismas notificaciones
+    /// que sus WebSocket hasta que se llame a `remove_connection`.
+    pub(crate) async fn register_sse(
+        &self,
+        user_id: i32,
+        client_type: String,
+    ) -> Result<(Uuid, mpsc::Receiver<Notification>), ConnectionLimitReached> {
+        let (tx, rx) = mpsc::channel::<Notification>(100);
+        let connection_id = Uuid::new_v4();
+
+        self.try_register(
+            user_id,
+            Connection {
+                id: connection_id,
+                client_type: client_type.clone(),
+                connected_at: Utc::now(),
+                sender: ConnectionSender::Sse(tx),
+            },
+        )
+        .await?;
+        info!(
+            "Stream SSE {} abierto para usuario {} ({})",
+            connection_id, user_id, client_type
+        );
+
+        Ok((connection_id, rx))
+    }
+
+    pub(crate) async fn remove_connection(&self, user_id: i32, connection_id: Uuid) {
+        let mut users = self.users.write().await;
+        if let Some(connections) = users.get_mut(&user_id) {
+            connections.retain(|connection| connection.id != connection_id);
+            if connections.is_empty() {
+                users.remove(&user_id);
+            }
         }
     }
 
     pub async fn handle_socket(
         &self,
-        socket: WebSocket,
+        mut socket: WebSocket,
+        pool: PgPool,
         user_id: i32,
+        client_type: String,
+        expires_at: DateTime<Utc>,
     ) {
-        let (mut sender, mut receiver) = socket.split();
         let (tx, mut rx) = mpsc::channel::<Message>(100);
+        let connection_id = Uuid::new_v4();
+        let lagging = Arc::new(Notify::new());
 
-        // Almacenar el sender para este usuario
-        self.users.write().await.insert(user_id, tx);
+        // Registrar la conexión junto a las otras del usuario
+        let connection = Connection {
+            id: connection_id,
+            client_type: client_type.clone(),
+            connected_at: Utc::now(),
+            sender: ConnectionSender::WebSocket(tx.clone(), lagging.clone()),
+        };
+        if self.try_register(user_id, connection).await.is_err() {
+            let _ = socket
+                .send(close_message(CLOSE_TOO_MANY_CONNECTIONS, "Límite de conexiones alcanzado"))
+                .await;
+            return;
+        }
+        let (mut sender, mut receiver) = socket.split();
+        info!(
+            "WebSocket {} abierto para usuario {} ({})",
+            connection_id, user_id, client_type
+        );
 
         // Task para enviar mensajes al WebSocket
-        let send_task = tokio::spawn(async move {
+        let mut send_task = tokio::spawn(async move {
             while let Some(message) = rx.recv().await {
+                let is_close = matches!(message, Message::Close(_));
                 if let Err(e) = sender.send(message).await {
                     error!("Error enviando mensaje por WebSocket: {}", e);
                     break;
                 }
+                if is_close {
+                    break;
+                }
             }
         });
 
+        // Última actividad del cliente (ms Unix); cualquier frame cuenta,
+        // incluidos los pong de los heartbeats.
+        let last_seen = Arc::new(AtomicI64::new(Utc::now().timestamp_millis()));
+
         // Task para recibir mensajes del WebSocket
-        let users = self.users.clone();
-        let receive_task = tokio::spawn(async move {
+        let tickers = self.tickers.clone();
+        let receive_last_seen = last_seen.clone();
+        let context = ClientContext {
+            pool,
+            ticker_feed: self.ticker_feed.clone(),
+            user_id,
+            connection_id,
+            tx: tx.clone(),
+        };
+        let mut receive_task = tokio::spawn(async move {
             while let Some(Ok(message)) = receiver.next().await {
+                receive_last_seen.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
                 match message {
                     Message::Text(text) => {
-                        if let Ok(ws_message) = serde_json::from_str::<WebSocketMessage>(&text) {
-                            info!("Mensaje recibido de usuario {}: {:?}", user_id, ws_message);
-                            // Aquí puedes manejar diferentes tipos de mensajes
+                        match serde_json::from_str::<WebSocketMessage>(&text) {
+                            Ok(ws_message) => {
+                                debug!("Mensaje recibido de usuario {}: {:?}", user_id, ws_message);
+                                handle_client_message(&tickers, &context, ws_message).await;
+                            }
+                            Err(e) => {
+                                send_error(&context.tx, &format!("Mensaje inválido: {}", e)).await;
+                            }
                         }
                     }
-                    Message::Close(_) => {
-                        users.write().await.remove(&user_id);
-                        break;
-                    }
+                    Message::Close(_) => break,
                     _ => {}
                 }
             }
         });
 
+        // Task de heartbeats: envía pings y corta la conexión si el cliente
+        // deja de responder
+        let heartbeat_tx = tx.clone();
+        let mut heartbeat_task = tokio::spawn(async move {
+            let idle_timeout_ms = idle_timeout_secs() * 1000;
+            let mut interval = tokio::time::interval(heartbeat_interval());
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let idle_ms = Utc::now().timestamp_millis() - last_seen.load(Ordering::Relaxed);
+                if idle_ms > idle_timeout_ms {
+                    info!("WebSocket {} inactivo, cerrando conexión", connection_id);
+                    let _ = heartbeat_tx
+                        .send(close_message(CLOSE_IDLE_TIMEOUT, "Conexión inactiva"))
+                        .await;
+                    break;
+                }
+                if heartbeat_tx.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Cerrar el socket cuando expire el token
+        let until_expiry = (expires_at - Utc::now()).to_std().unwrap_or_default();
+        let expiry = tokio::time::sleep(until_expiry);
+        tokio::pin!(expiry);
+
         // Esperar a que cualquiera de las tasks termine
         tokio::select! {
-            _ = send_task => {},
-            _ = receive_task => {},
+            _ = &mut send_task => {},
+            _ = &mut receive_task => {},
+            _ = &mut heartbeat_task => {
+                let _ = tokio::time::timeout(Duration::from_secs(5), &mut send_task).await;
+            },
+            _ = &mut expiry => {
+                info!("Token expirado, cerrando WebSocket del usuario {}", user_id);
+                let _ = tx.send(close_message(CLOSE_UNAUTHORIZED, "Token expirado")).await;
+                let _ = tokio::time::timeout(Duration::from_secs(5), &mut send_task).await;
+            },
+            // La cola está llena: se corta sin frame de cierre y el cliente
+            // recupera lo perdido con `resume` al reconectar
+            _ = lagging.notified() => {
+                info!("WebSocket {} atrasado, cerrando conexión", connection_id);
+            },
         }
+        send_task.abort();
+        receive_task.abort();
+        heartbeat_task.abort();
 
-        // Limpiar la conexión
-        self.users.write().await.remove(&user_id);
+        // Limpiar solo esta conexión; las otras del usuario siguen abiertas
+        self.remove_connection(user_id, connection_id).await;
+        self.unsubscribe_all(connection_id).await;
+        info!("WebSocket {} cerrado para usuario {}", connection_id, user_id);
     }
 
+    /// Cierra las conexiones del usuario en esta instancia. Para cerrarlas
+    /// en todas hay que usar `bus::publish_disconnect`.
+    pub async fn disconnect_user(&self, user_id: i32, reason: &str) {
+        let connections = self.users.write().await.remove(&user_id).unwrap_or_default();
+        for connection in connections {
+            info!(
+                "Cerrando conexión {} del usuario {}: {}",
+                connection.id, user_id, reason
+            );
+            // Los streams SSE terminan al soltar su sender
+            if let ConnectionSender::WebSocket(sender, lagging) = connection.sender {
+                if sender.try_send(close_message(CLOSE_FORBIDDEN, reason)).is_err() {
+                    lagging.notify_one();
+                }
+            }
+        }
+    }
+
+    /// Escucha el bus de notificaciones y aplica cada mensaje a los sockets
+    /// de esta instancia. Si la suscripción se cae se reintenta
+    /// indefinidamente.
+    pub fn spawn_bus_listener(self: &Arc<Self>, bus: Arc<dyn NotificationBus>) -> JoinHandle<()> {
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match bus.subscribe().await {
+                    Ok(mut messages) => {
+                        while let Some(message) = messages.next().await {
+                            match message {
+                                BusMessage::Notification(notification) => {
+                                    server.broadcast_notification(&notification).await
+                                }
+                                BusMessage::Disconnect { user_id, reason } => {
+                                    server.disconnect_user(user_id, &reason).await
+                                }
+                            }
+                        }
+                        error!("Suscripción al bus de notificaciones finalizada");
+                    }
+                    Err(e) => error!("Error suscribiéndose al bus de notificaciones: {}", e),
+                }
+                tokio::time::sleep(BUS_RECONNECT_DELAY).await;
+            }
+        })
+    }
+
+    /// Entrega la notificación a los sockets abiertos en esta instancia. Para
+    /// llegar a todas las instancias hay que publicarla en el bus.
+    ///
+    /// No espera a nadie: una conexión con la cola llena se cierra para no
+    /// frenar al resto, y el cliente recupera lo perdido al reconectar
+    /// (`resume` o `Last-Event-ID`).
     pub async fn broadcast_notification(&self, notification: &Notification) {
-        let message = WebSocketMessage {
-            message_type: "notification".to_string(),
-            payload: serde_json::to_value(notification).unwrap_or_default(),
-        };
+        let message = WebSocketMessage::new("notification", json!(NotificationFrame::from(notification)));
 
-        if let Ok(message_json) = serde_json::to_string(&message) {
-            let ws_message = Message::Text(message_json);
-            let users = self.users.read().await;
-            
-            if let Some(sender) = users.get(&notification.user_id) {
-                if let Err(e) = sender.send(ws_message).await {
-                    error!(
-                        "Error enviando notificación al usuario {}: {}",
-                        notification.user_id, e
+        if let Some(ws_message) = message.to_message() {
+            // Clonar los senders para no mantener el lock mientras se envía
+            let connections = self
+                .users
+                .read()
+                .await
+                .get(&notification.user_id)
+                .cloned()
+                .unwrap_or_default();
+
+            for connection in connections {
+                let full = match &connection.sender {
+                    ConnectionSender::WebSocket(sender, _) => {
+                        matches!(sender.try_send(ws_message.clone()), Err(TrySendError::Full(_)))
+                    }
+                    ConnectionSender::Sse(sender) => {
+                        matches!(sender.try_send(notification.clone()), Err(TrySendError::Full(_)))
+                    }
+                };
+                if full {
+                    warn!(
+                        "Conexión {} del usuario {} atrasada, se cierra",
+                        connection.id, notification.user_id
                     );
+                    self.remove_connection(notification.user_id, connection.id).await;
+                    if let ConnectionSender::WebSocket(_, lagging) = &connection.sender {
+                        lagging.notify_one();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Guarda el último precio de un canal. El envío a los suscriptores lo
+    /// hace la task de `spawn_ticker_fanout`, así varias actualizaciones
+    /// dentro del mismo intervalo se agrupan en un solo mensaje.
+    pub async fn publish_ticker(&self, ticker: Ticker) {
+        let mut tickers = self.tickers.write().await;
+        if let Some(state) = tickers.get_mut(&ticker.channel) {
+            state.latest = Some(ticker);
+            state.dirty = true;
+        }
+    }
+
+    /// Canales de ticker con al menos un suscriptor.
+    pub async fn active_ticker_channels(&self) -> Vec<String> {
+        self.tickers
+            .read()
+            .await
+            .iter()
+            .filter(|(_, state)| !state.subscribers.is_empty())
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    /// Envía a los suscriptores, como mucho una vez por `throttle`, el
+    /// último precio de cada canal que haya cambiado.
+    pub fn spawn_ticker_fanout(self: &Arc<Self>, throttle: Duration) -> JoinHandle<()> {
+        let server = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(throttle);
+            loop {
+                interval.tick().await;
+                server.flush_tickers().await;
+            }
+        })
+    }
+
+    async fn flush_tickers(&self) {
+        let pending: Vec<(Ticker, Vec<Uuid>)> = {
+            let mut tickers = self.tickers.write().await;
+            tickers
+                .values_mut()
+                .filter(|state| state.dirty && !state.subscribers.is_empty())
+                .filter_map(|state| {
+                    state.dirty = false;
+                    state
+                        .latest
+                        .clone()
+                        .map(|ticker| (ticker, state.subscribers.iter().copied().collect()))
+                })
+                .collect()
+        };
+
+        if pending.is_empty() {
+            return;
+        }
+
+        let users = self.users.read().await;
+        let senders: HashMap<Uuid, &mpsc::Sender<Message>> = users
+            .values()
+            .flatten()
+            .filter_map(|connection| match &connection.sender {
+                ConnectionSender::WebSocket(sender, _) => Some((connection.id, sender)),
+                ConnectionSender::Sse(_) => None,
+            })
+            .collect();
+
+        for (ticker, subscribers) in pending {
+            let Some(message) = WebSocketMessage::new("ticker", json!(ticker)).to_message() else {
+                continue;
+            };
+
+            for connection_id in subscribers {
+                if let Some(sender) = senders.get(&connection_id) {
+                    // Si el cliente va atrasado se descarta el precio: el
+                    // próximo flush le enviará uno más reciente.
+                    if sender.try_send(message.clone()).is_err() {
+                        debug!("Ticker {} descartado para conexión {}", ticker.channel, connection_id);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn unsubscribe_all(&self, connection_id: Uuid) {
+        let mut tickers = self.tickers.write().await;
+        for state in tickers.values_mut() {
+            state.subscribers.remove(&connection_id);
+        }
+        tickers.retain(|_, state| !state.subscribers.is_empty());
+    }
+}
+
+async fn send_error(tx: &mpsc::Sender<Message>, error: &str) {
+    if let Some(message) = WebSocketMessage::new("error", json!({ "error": error })).to_message() {
+        let _ = tx.send(message).await;
+    }
+}
+
+async fn handle_client_message(tickers: &Tickers, context: &ClientContext, ws_message: WebSocketMessage) {
+    let tx = &context.tx;
+    let connection_id = context.connection_id;
+
+    match ws_message.message_type.as_str() {
+        "ping" => {
+            if let Some(message) = WebSocketMessage::new("pong", json!({})).to_message() {
+                let _ = tx.send(message).await;
+            }
+        }
+        "resume" => match serde_json::from_value::<ResumePayload>(ws_message.payload) {
+            Ok(payload) => resume(context, payload.last_event_id).await,
+            Err(e) => send_error(tx, &format!("Payload de resume inválido: {}", e)).await,
+        },
+        "ack" => match serde_json::from_value::<AckPayload>(ws_message.payload) {
+            Ok(payload) => ack(context, payload.into_event_ids()).await,
+            Err(e) => send_error(tx, &format!("Payload de ack inválido: {}", e)).await,
+        },
+        "subscribe" | "unsubscribe" => {
+            let channels = match serde_json::from_value::<SubscriptionPayload>(ws_message.payload) {
+                Ok(payload) => payload.into_channels(),
+                Err(e) => {
+                    send_error(tx, &format!("Payload de suscripción inválido: {}", e)).await;
+                    return;
                 }
+            };
+
+            if ws_message.message_type == "subscribe" {
+                subscribe(tickers, &context.ticker_feed, connection_id, tx, channels).await;
+            } else {
+                unsubscribe(tickers, connection_id, tx, channels).await;
+            }
+        }
+        other => {
+            send_error(tx, &format!("Tipo de mensaje no soportado: {}", other)).await;
+        }
+    }
+}
+
+/// Reenvía las notificaciones posteriores a `last_event_id`. Pueden llegar
+/// duplicadas con las que se entregan en vivo mientras tanto; el cliente
+/// las descarta por `event_id`.
+async fn resume(context: &ClientContext, last_event_id: i64) {
+    let missed = match notifications::list_notifications_after(
+        &context.pool,
+        context.user_id,
+        last_event_id,
+        MAX_RESUME_REPLAY,
+    )
+    .await
+    {
+        Ok(missed) => missed,
+        Err(e) => {
+            error!("Error obteniendo historial del usuario {}: {}", context.user_id, e);
+            send_error(&context.tx, "Error obteniendo notificaciones pendientes").await;
+            return;
+        }
+    };
+
+    let replayed = missed.len();
+    let last_replayed = missed.last().map_or(last_event_id, |n| n.event_id);
+    for notification in missed {
+        let message = WebSocketMessage::new("notification", json!(NotificationFrame::from(notification)));
+        if let Some(message) = message.to_message() {
+            if context.tx.send(message).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    let ack = WebSocketMessage::new(
+        "resumed",
+        json!({
+            "replayed": replayed,
+            "last_event_id": last_replayed,
+            // Si se llegó al límite el cliente debe volver a enviar `resume`
+            "has_more": replayed as i64 == MAX_RESUME_REPLAY,
+        }),
+    );
+    if let Some(message) = ack.to_message() {
+        let _ = context.tx.send(message).await;
+    }
+}
+
+async fn ack(context: &ClientContext, event_ids: Vec<i64>) {
+    if event_ids.is_empty() {
+        send_error(&context.tx, "Debe indicar al menos un event_id").await;
+        return;
+    }
+
+    match notifications::mark_notifications_read(&context.pool, context.user_id, &event_ids).await {
+        Ok(updated) => {
+            let ack = WebSocketMessage::new("acked", json!({ "event_ids": event_ids, "updated": updated }));
+            if let Some(message) = ack.to_message() {
+                let _ = context.tx.send(message).await;
+            }
+        }
+        Err(e) => {
+            error!("Error marcando notificaciones como leídas: {}", e);
+            send_error(&context.tx, "Error marcando notificaciones como leídas").await;
+        }
+    }
+}
+
+/// Resultado de anotar una conexión en varios canales de ticker.
+#[derive(Debug, Default)]
+pub(super) struct TickerSubscription {
+    pub subscribed: Vec<String>,
+    /// Último precio de los canales que ya tenían uno.
+    pub snapshots: Vec<Ticker>,
+    pub without_snapshot: Vec<TickerChannel>,
+    /// Se cortó en `MAX_TICKER_SUBSCRIPTIONS`.
+    pub limit_reached: bool,
+}
+
+pub(super) fn add_subscriptions(
+    tickers: &mut HashMap<String, TickerState>,
+    connection_id: Uuid,
+    channels: Vec<TickerChannel>,
+) -> TickerSubscription {
+    let mut added = TickerSubscription::default();
+    let mut current = tickers
+        .values()
+        .filter(|state| state.subscribers.contains(&connection_id))
+        .count();
+
+    for channel in channels {
+        let name = channel.name();
+        let state = tickers.entry(name.clone()).or_default();
+        if !state.subscribers.contains(&connection_id) {
+            if current >= MAX_TICKER_SUBSCRIPTIONS {
+                added.limit_reached = true;
+                break;
             }
+            state.subscribers.insert(connection_id);
+            current += 1;
+        }
+
+        match &state.latest {
+            Some(latest) => added.snapshots.push(latest.clone()),
+            None => added.without_snapshot.push(channel),
         }
+        added.subscribed.push(name);
     }
+
+    tickers.retain(|_, state| !state.subscribers.is_empty());
+    added
+}
+
+async fn subscribe(
+    tickers: &Tickers,
+    ticker_feed: &TickerFeed,
+    connection_id: Uuid,
+    tx: &mpsc::Sender<Message>,
+    channels: Vec<String>,
+) {
+    let mut errors = Vec::new();
+
+    // Sólo los canales con instrumento en el catálogo, si no el feed
+    // consultaría el exchange por canales que no existen
+    let mut parsed = Vec::new();
+    for channel in channels {
+        match TickerChannel::parse(&channel) {
+            Some(ticker_channel) => parsed.push((channel, ticker_channel)),
+            None => errors.push(format!("Canal inválido: {}", channel)),
+        }
+    }
+    let known = join_all(parsed.iter().map(|(_, channel)| ticker_feed.is_known(channel))).await;
+    let valid: Vec<TickerChannel> = parsed
+        .into_iter()
+        .zip(known)
+        .filter_map(|((channel, ticker_channel), known)| {
+            if !known {
+                errors.push(format!("Canal desconocido: {}", channel));
+            }
+            known.then_some(ticker_channel)
+        })
+        .collect();
+
+    let TickerSubscription {
+        subscribed,
+        mut snapshots,
+        without_snapshot,
+        limit_reached,
+    } = add_subscriptions(&mut *tickers.write().await, connection_id, valid);
+    if limit_reached {
+        errors.push("Límite de suscripciones alcanzado".to_string());
+    }
+
+    // Con el lock ya suelto: un cliente lento no frena al resto
+    for error in errors {
+        send_error(tx, &error).await;
+    }
+
+    // Primer suscriptor del canal: se pide el precio en vez de esperar al
+    // próximo ciclo del feed
+    let fetched: Vec<Ticker> = join_all(without_snapshot.iter().map(|channel| ticker_feed.fetch(channel)))
+        .await
+        .into_iter()
+        .flatten()
+        .collect();
+    if !fetched.is_empty() {
+        let mut tickers = tickers.write().await;
+        for ticker in &fetched {
+            if let Some(state) = tickers.get_mut(&ticker.channel) {
+                state.latest.get_or_insert_with(|| ticker.clone());
+            }
+        }
+    }
+    snapshots.extend(fetched);
+
+    if let Some(message) = WebSocketMessage::new("subscribed", json!({ "channels": subscribed })).to_message() {
+        let _ = tx.send(message).await;
+    }
+
+    for snapshot in snapshots {
+        if let Some(message) = WebSocketMessage::new("ticker_snapshot", json!(snapshot)).to_message() {
+            let _ = tx.send(message).await;
+        }
+    }
+}
+
+async fn unsubscribe(
+    tickers: &Tickers,
+    connection_id: Uuid,
+    tx: &mpsc::Sender<Message>,
+    channels: Vec<String>,
+) {
+    let mut unsubscribed = Vec::new();
+
+    {
+        let mut tickers = tickers.write().await;
+        for channel in channels {
+            let Some(name) = TickerChannel::parse(&channel).map(|c| c.name()) else {
+                continue;
+            };
+            if let Some(state) = tickers.get_mut(&name) {
+                if state.subscribers.remove(&connection_id) {
+                    unsubscribed.push(name);
+                }
+            }
+        }
+        tickers.retain(|_, state| !state.subscribers.is_empty());
+    }
+
+    if let Some(message) = WebSocketMessage::new("unsubscribed", json!({ "channels": unsubscribed })).to_message() {
+        let _ = tx.send(message).await;
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WsParams {
+    pub token: Option<String>,
+    /// Tipo de cliente (`web`, `mobile`, ...) que se muestra a los admins.
+    pub client: Option<String>,
+}
+
+fn close_message(code: u16, reason: &str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code,
+        reason: Cow::Owned(reason.to_string()),
+    }))
+}
+
+/// Valida el JWT de una conexión WebSocket: firma, expiración, usuario
+/// activo y que no haya sido revocado.
+pub(crate) async fn authenticate(pool: &PgPool, token: &str) -> Result<Claims, (u16, String)> {
+    let claims = jwt::verify_token(token)
+        .map_err(|_| (CLOSE_UNAUTHORIZED, "Token inválido".to_string()))?;
+
+    if claims.is_expired() {
+        return Err((CLOSE_UNAUTHORIZED, "Token expirado".to_string()));
+    }
+
+    let status = users::get_auth_status(pool, claims.user_id)
+        .await
+        .map_err(|e| {
+            error!("Error verificando usuario {}: {}", claims.user_id, e);
+            (CLOSE_INTERNAL_ERROR, "Error interno del servidor".to_string())
+        })?
+        .ok_or((CLOSE_UNAUTHORIZED, "Usuario no encontrado".to_string()))?;
+
+    if !status.is_active {
+        return Err((CLOSE_FORBIDDEN, "Usuario desactivado".to_string()));
+    }
+    if status.is_token_revoked(claims.iat) {
+        return Err((CLOSE_FORBIDDEN, "Token revocado".to_string()));
+    }
+
+    Ok(claims)
+}
+
+fn heartbeat_interval() -> Duration {
+    let secs = std::env::var("WS_HEARTBEAT_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_HEARTBEAT_INTERVAL_SECS);
+    Duration::from_secs(secs)
+}
+
+fn idle_timeout_secs() -> i64 {
+    std::env::var("WS_IDLE_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS)
+}
+
+pub(crate) fn max_connections_per_user() -> usize {
+    std::env::var("WS_MAX_CONNECTIONS_PER_USER")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONNECTIONS_PER_USER)
+}
+
+/// Espera el primer mensaje del cliente, que debe ser
+/// `{"message_type": "auth", "payload": {"token": "..."}}`.
+async fn authenticate_first_message(pool: &PgPool, socket: &mut WebSocket) -> Result<Claims, (u16, String)> {
+    let first = tokio::time::timeout(AUTH_TIMEOUT, socket.recv())
+        .await
+        .map_err(|_| (CLOSE_UNAUTHORIZED, "Tiempo de autenticación agotado".to_string()))?;
+
+    let Some(Ok(Message::Text(text))) = first else {
+        return Err((CLOSE_UNAUTHORIZED, "Se esperaba un mensaje de autenticación".to_string()));
+    };
+
+    let token = serde_json::from_str::<WebSocketMessage>(&text)
+        .ok()
+        .filter(|m| m.message_type == "auth")
+        .and_then(|m| m.payload["token"].as_str().map(str::to_string))
+        .ok_or((CLOSE_UNAUTHORIZED, "Se esperaba un mensaje de autenticación".to_string()))?;
+
+    authenticate(pool, &token).await
 }
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
-    user_id: i32,
-    ws_server: Arc<WebSocketServer>,
+    State(state): State<AppState>,
+    Query(params): Query<WsParams>,
 ) -> Response {
-    ws.on_upgrade(move |socket| async move {
-        ws_server.handle_socket(socket, user_id).await;
+    // Con el token en la query se rechaza antes del upgrade
+    let pre_authenticated = match params.token {
+        Some(token) => match authenticate(&state.pool, &token).await {
+            Ok(claims) => Some(claims),
+            Err((_, reason)) => return (StatusCode::UNAUTHORIZED, reason).into_response(),
+        },
+        None => None,
+    };
+
+    // Rechazo temprano; el límite se garantiza al registrar la conexión
+    if let Some(claims) = &pre_authenticated {
+        if state.ws_server.connection_count(claims.user_id).await >= max_connections_per_user() {
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Límite de conexiones alcanzado".to_string(),
+            )
+                .into_response();
+        }
+    }
+
+    ws.on_upgrade(move |mut socket| async move {
+        let claims = match pre_authenticated {
+            Some(claims) => claims,
+            None => match authenticate_first_message(&state.pool, &mut socket).await {
+                Ok(claims) => claims,
+                Err((code, reason)) => {
+                    let _ = socket.send(close_message(code, &reason)).await;
+                    return;
+                }
+            },
+        };
+
+        let expires_at = Utc
+            .timestamp_opt(claims.exp, 0)
+            .single()
+            .unwrap_or_else(Utc::now);
+        let client_type = params
+            .client
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| "unknown".to_string());
+        state
+            .ws_server
+            .handle_socket(socket, state.pool.clone(), claims.user_id, client_type, expires_at)
+            .await;
     })
 }
@@ -0,0 +1,416 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use super::{
+    dispatcher::{ChannelError, ChannelKind, NotificationChannel},
+    Notification,
+};
+use crate::db::whatsapp::{self, MessagePurpose};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const DEFAULT_API_BASE_URL: &str = "https://graph.facebook.com/v20.0";
+const DEFAULT_TEMPLATE_LANGUAGE: &str = "es";
+const DEFAULT_OTP_TEMPLATE: &str = "verification_code";
+/// Fuera de esta ventana desde el último mensaje del usuario sólo se pueden
+/// enviar plantillas aprobadas.
+pub const SESSION_WINDOW_HOURS: i64 = 24;
+pub const VERIFICATION_CODE_LEN: usize = 6;
+pub const VERIFICATION_CODE_TTL_MINUTES: i64 = 10;
+pub const VERIFICATION_MAX_ATTEMPTS: i32 = 5;
+/// Códigos que se pueden enviar en 24 horas, por usuario y por número.
+pub const VERIFICATION_MAX_SENDS_PER_DAY: i64 = 5;
+/// Header con la firma HMAC-SHA256 del cuerpo, con el app secret.
+pub const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
+/// Error 131047: pasaron más de 24 horas desde el último mensaje del usuario.
+const ERROR_REENGAGEMENT: i64 = 131047;
+/// El número no tiene WhatsApp o no acepta mensajes del negocio.
+const ERROR_UNDELIVERABLE: i64 = 131026;
+/// En modo de prueba, el número no está en la lista de destinatarios.
+const ERROR_RECIPIENT_NOT_ALLOWED: i64 = 131030;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WhatsAppError {
+    #[error("Error HTTP: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Error de la API de WhatsApp ({code}): {message}")]
+    Api { code: i64, message: String },
+}
+
+impl WhatsAppError {
+    fn is_outside_session_window(&self) -> bool {
+        matches!(self, WhatsAppError::Api { code: ERROR_REENGAGEMENT, .. })
+    }
+
+    fn is_unreachable_recipient(&self) -> bool {
+        matches!(
+            self,
+            WhatsAppError::Api { code: ERROR_UNDELIVERABLE | ERROR_RECIPIENT_NOT_ALLOWED, .. }
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    error: ApiError,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendResponse {
+    messages: Vec<SentMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SentMessage {
+    id: String,
+}
+
+/// Cliente mínimo de la Cloud API. La URL base es configurable para poder
+/// apuntarla a un servidor local en las pruebas.
+#[derive(Clone)]
+pub struct WhatsAppClient {
+    http_client: Client,
+    base_url: String,
+    phone_number_id: String,
+    access_token: String,
+    template_language: String,
+}
+
+impl WhatsAppClient {
+    pub fn new(
+        base_url: impl Into<String>,
+        phone_number_id: impl Into<String>,
+        access_token: impl Into<String>,
+    ) -> Self {
+        Self {
+            http_client: Client::builder()
+                .timeout(Duration::from_secs(15))
+                .build()
+                .unwrap_or_default(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            phone_number_id: phone_number_id.into(),
+            access_token: access_token.into(),
+            template_language: DEFAULT_TEMPLATE_LANGUAGE.to_string(),
+        }
+    }
+
+    pub fn with_template_language(mut self, language: impl Into<String>) -> Self {
+        self.template_language = language.into();
+        self
+    }
+
+    /// `WHATSAPP_ACCESS_TOKEN` y `WHATSAPP_PHONE_NUMBER_ID`; opcionalmente
+    /// `WHATSAPP_API_BASE_URL` y `WHATSAPP_TEMPLATE_LANGUAGE`. `None` si
+    /// falta alguno de los obligatorios.
+    pub fn from_env() -> Option<Self> {
+        let access_token = std::env::var("WHATSAPP_ACCESS_TOKEN").ok().filter(|t| !t.is_empty())?;
+        let phone_number_id = std::env::var("WHATSAPP_PHONE_NUMBER_ID").ok().filter(|id| !id.is_empty())?;
+        let base_url =
+            std::env::var("WHATSAPP_API_BASE_URL").unwrap_or_else(|_| DEFAULT_API_BASE_URL.to_string());
+        let client = Self::new(base_url, phone_number_id, access_token);
+
+        Some(match std::env::var("WHATSAPP_TEMPLATE_LANGUAGE") {
+            Ok(language) if !language.is_empty() => client.with_template_language(language),
+            _ => client,
+        })
+    }
+
+    /// Envía el mensaje y devuelve su id (`wamid`), con el que llegan luego
+    /// los cambios de estado.
+    async fn send(&self, body: &JsonValue) -> Result<String, WhatsAppError> {
+        let url = format!("{}/{}/messages", self.base_url, self.phone_number_id);
+        let response = self
+            .http_client
+            .post(&url)
+            .bearer_auth(&self.access_token)
+            .json(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error = match response.json::<ApiErrorBody>().await {
+                Ok(body) => WhatsAppError::Api { code: body.error.code, message: body.error.message },
+                Err(_) => WhatsAppError::Api { code: status.as_u16() as i64, message: status.to_string() },
+            };
+            return Err(error);
+        }
+
+        let sent: SendResponse = response.json().await?;
+        sent.messages
+            .into_iter()
+            .next()
+            .map(|message| message.id)
+            .ok_or_else(|| WhatsAppError::Api { code: 0, message: "respuesta sin id de mensaje".to_string() })
+    }
+
+    /// Texto libre: sólo se entrega dentro de la ventana de sesión.
+    pub async fn send_text(&self, to: &str, text: &str) -> Result<String, WhatsAppError> {
+        self.send(&json!({
+            "messaging_product": "whatsapp",
+            "to": to,
+            "type": "text",
+            "text": { "body": text },
+        }))
+        .await
+    }
+
+    /// Plantilla aprobada con parámetros de cuerpo `{{1}}`, `{{2}}`, ... y,
+    /// opcionalmente, el parámetro del primer botón URL.
+    pub async fn send_template(
+        &self,
+        to: &str,
+        template: &str,
+        body_params: &[String],
+        button_param: Option<&str>,
+    ) -> Result<String, WhatsAppError> {
+        let parameters: Vec<JsonValue> = body_params
+            .iter()
+            .map(|text| json!({ "type": "text", "text": template_param(text) }))
+            .collect();
+
+        let mut components = vec![json!({ "type": "body", "parameters": parameters })];
+        if let Some(param) = button_param {
+            components.push(json!({
+                "type": "button",
+                "sub_type": "url",
+                "index": "0",
+                "parameters": [{ "type": "text", "text": param }],
+            }));
+        }
+
+        self.send(&json!({
+            "messaging_product": "whatsapp",
+            "to": to,
+            "type": "template",
+            "template": {
+                "name": template,
+                "language": { "code": self.template_language },
+                "components": components,
+            },
+        }))
+        .await
+    }
+
+    /// Envía el código con la plantilla de autenticación
+    /// (`WHATSAPP_OTP_TEMPLATE`, por defecto `verification_code`), que lleva
+    /// el código en el cuerpo y en el botón de copiar.
+    pub async fn send_verification_code(&self, to: &str, code: &str) -> Result<String, WhatsAppError> {
+        let template =
+            std::env::var("WHATSAPP_OTP_TEMPLATE").unwrap_or_else(|_| DEFAULT_OTP_TEMPLATE.to_string());
+        self.send_template(to, &template, &[code.to_string()], Some(code)).await
+    }
+}
+
+/// Los parámetros de plantilla no admiten saltos de línea, tabulaciones ni
+/// más de cuatro espacios seguidos.
+fn template_param(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Normaliza un número en formato internacional (`+54 9 11 1234-5678`) a
+/// sólo dígitos, que es como lo identifica WhatsApp.
+pub fn normalize_phone_number(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    let rest = trimmed.strip_prefix('+').or_else(|| trimmed.strip_prefix("00"))?;
+
+    let mut digits = String::with_capacity(rest.len());
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            ' ' | '-' | '(' | ')' | '.' => {}
+            _ => return None,
+        }
+    }
+
+    (8..=15).contains(&digits.len()).then_some(digits).filter(|d| !d.starts_with('0'))
+}
+
+pub fn within_session_window(last_inbound_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    last_inbound_at.map_or(false, |at| now - at < chrono::Duration::hours(SESSION_WINDOW_HOURS))
+}
+
+pub fn generate_verification_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..VERIFICATION_CODE_LEN).map(|_| char::from(b'0' + rng.gen_range(0..10))).collect()
+}
+
+/// Sólo se guarda el hash del código.
+pub fn hash_verification_code(code: &str) -> String {
+    use sha2::Digest;
+    hex::encode(Sha256::digest(code.trim().as_bytes()))
+}
+
+/// Texto libre, para dentro de la ventana de sesión.
+pub fn format_notification(notification: &Notification) -> String {
+    format!("*{}*\n{}", notification.title, notification.message)
+}
+
+/// Comprueba `X-Hub-Signature-256` (`sha256=<hex>`) sobre el cuerpo crudo.
+pub fn verify_webhook_signature(app_secret: &str, body: &[u8], header: &str) -> bool {
+    let Some(signature) = header.strip_prefix("sha256=").and_then(|hex_sig| hex::decode(hex_sig).ok()) else {
+        return false;
+    };
+    let Ok(mut mac) = HmacSha256::new_from_slice(app_secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Cuerpo de los webhooks de la Cloud API. Sólo se leen los mensajes
+/// entrantes (abren la ventana de sesión) y los cambios de estado.
+#[derive(Debug, Deserialize)]
+pub struct WebhookPayload {
+    #[serde(default)]
+    pub entry: Vec<WebhookEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookEntry {
+    #[serde(default)]
+    pub changes: Vec<WebhookChange>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookChange {
+    pub value: WebhookValue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookValue {
+    #[serde(default)]
+    pub messages: Vec<InboundMessage>,
+    #[serde(default)]
+    pub statuses: Vec<StatusUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InboundMessage {
+    pub from: String,
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusUpdate {
+    /// `wamid` del mensaje enviado.
+    pub id: String,
+    /// `sent`, `delivered`, `read` o `failed`.
+    pub status: String,
+    #[serde(default)]
+    pub errors: Vec<StatusError>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusError {
+    pub code: i64,
+    #[serde(default)]
+    pub title: String,
+}
+
+/// Aplica un webhook: los mensajes entrantes renuevan la ventana de sesión y
+/// los estados se guardan contra el mensaje enviado.
+pub async fn handle_webhook(pool: &PgPool, payload: WebhookPayload) {
+    let values = payload.entry.into_iter().flat_map(|entry| entry.changes).map(|change| change.value);
+
+    for value in values {
+        for message in value.messages {
+            if let Err(e) = whatsapp::record_inbound_message(pool, &message.from).await {
+                error!("Error registrando mensaje entrante {}: {}", message.id, e);
+            }
+        }
+
+        for status in value.statuses {
+            let error = status
+                .errors
+                .first()
+                .map(|e| format!("{}: {}", e.code, e.title));
+            match whatsapp::update_message_status(pool, &status.id, &status.status, error.as_deref()).await {
+                Ok(true) => {}
+                Ok(false) => warn!("Estado {} ignorado para el mensaje {}", status.status, status.id),
+                Err(e) => error!("Error actualizando estado del mensaje {}: {}", status.id, e),
+            }
+        }
+    }
+}
+
+/// Canal del dispatcher: texto libre dentro de la ventana de sesión y, fuera
+/// de ella, la plantilla aprobada del tipo de notificación (con el título y
+/// el mensaje como parámetros).
+pub struct WhatsAppChannel {
+    pool: PgPool,
+    client: WhatsAppClient,
+}
+
+impl WhatsAppChannel {
+    pub fn new(pool: PgPool, client: WhatsAppClient) -> Self {
+        Self { pool, client }
+    }
+
+    async fn send_template(&self, to: &str, notification: &Notification) -> Result<String, WhatsAppError> {
+        let params = [notification.title.clone(), notification.message.clone()];
+        self.client
+            .send_template(to, notification.notification_type.as_str(), &params, None)
+            .await
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WhatsAppChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::WhatsApp
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), ChannelError> {
+        let recipient = whatsapp::get_recipient(&self.pool, notification.user_id)
+            .await
+            .map_err(|e| ChannelError::Failed(e.to_string()))?
+            .ok_or_else(|| ChannelError::NotConfigured("sin número de WhatsApp verificado".to_string()))?;
+
+        let result = if within_session_window(recipient.last_inbound_at, Utc::now()) {
+            match self.client.send_text(&recipient.phone_number, &format_notification(notification)).await {
+                // La ventana puede haberse cerrado sin que lo sepamos
+                Err(e) if e.is_outside_session_window() => self.send_template(&recipient.phone_number, notification).await,
+                other => other,
+            }
+        } else {
+            self.send_template(&recipient.phone_number, notification).await
+        };
+
+        match result {
+            Ok(message_id) => {
+                if let Err(e) = whatsapp::record_message(
+                    &self.pool,
+                    &message_id,
+                    notification.user_id,
+                    notification.event_id,
+                    MessagePurpose::Notification,
+                )
+                .await
+                {
+                    error!("Error registrando mensaje de WhatsApp {}: {}", message_id, e);
+                }
+                Ok(())
+            }
+            Err(e) if e.is_unreachable_recipient() => Err(ChannelError::NotConfigured(e.to_string())),
+            Err(e) => Err(ChannelError::Failed(e.to_string())),
+        }
+    }
+}
@@ -16,3 +16,20 @@ where
     let decimal_str = String::deserialize(deserializer)?;
     BigDecimal::from_str(&decimal_str).map_err(serde::de::Error::custom)
 }
+
+
+pub fn serialize_option_bigdecimal<S>(decimal: &Option<BigDecimal>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    decimal.as_ref().map(|d| d.to_string()).serialize(serializer)
+}
+
+pub fn deserialize_option_bigdecimal<'de, D>(deserializer: D) -> Result<Option<BigDecimal>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| BigDecimal::from_str(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}